@@ -0,0 +1,44 @@
+use crate::dataset::read_scan_log;
+use clap::Args;
+use eth_beacons_indoor::audit::{AuditConfig, Auditor};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub(crate) struct AuditArgs {
+    /// Scan log with the observations to check
+    log: PathBuf,
+
+    /// Report beacons heard further than this many metres from their position as moved
+    #[arg(long, default_value_t = AuditConfig::default().max_displacement)]
+    max_displacement: f64,
+
+    /// Write the report to this file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub(crate) fn run(args: AuditArgs) -> anyhow::Result<()> {
+    let log = read_scan_log(&args.log)?;
+    let auditor = Auditor::new(AuditConfig {
+        max_displacement: args.max_displacement,
+        ..Default::default()
+    });
+    let mut rendered = auditor.audit(&log.observations).to_string();
+    // Beacons with an id the dataset does not know, e.g. replaced or misconfigured ones
+    for unknown in &log.unknown_beacons {
+        rendered.push_str(&format!(
+            "-\tbeacon {}/{}\tnot in the dataset, heard {} times (uuid {})\n",
+            unknown.major, unknown.minor, unknown.sightings, unknown.uuid
+        ));
+    }
+
+    match args.output {
+        Some(path) => {
+            fs::write(&path, rendered)?;
+            println!("Report written to   : {}", path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
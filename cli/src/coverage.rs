@@ -0,0 +1,150 @@
+use crate::table::Table;
+use anyhow::{anyhow, bail};
+use clap::{Args, ValueEnum};
+use eth_beacons_indoor::coverage::{
+    write_geojson, Coverage, CoverageConfig, CoverageValue, FloorCoverage, Gap,
+};
+use eth_beacons_indoor::resolver::find_floor;
+use eth_beacons_indoor::room::Room;
+use eth_beacons_indoor::Building;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CoverageFormat {
+    /// Cells as polygons with their values as properties
+    Geojson,
+    /// ESRI ASCII raster in LV95 coordinates, needs `--floor`
+    AsciiGrid,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Value {
+    Beacons,
+    Gdop,
+    Error,
+}
+
+#[derive(Args)]
+pub(crate) struct CoverageArgs {
+    building: String,
+
+    /// Only this floor instead of all floors with beacons
+    #[arg(short, long)]
+    floor: Option<String>,
+
+    /// Edge length of a cell in metres
+    #[arg(long, default_value_t = CoverageConfig::default().cell_size)]
+    cell_size: f64,
+
+    /// Cells with fewer beacons in range are uncovered
+    #[arg(long, default_value_t = CoverageConfig::default().min_beacons)]
+    min_beacons: usize,
+
+    /// Number of gaps to list
+    #[arg(long, default_value_t = 10)]
+    gaps: usize,
+
+    /// Write the grid to this file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = CoverageFormat::Geojson)]
+    format: CoverageFormat,
+
+    /// Value of the cells of an ASCII grid
+    #[arg(long, value_enum, default_value_t = Value::Beacons)]
+    value: Value,
+}
+
+pub(crate) fn run(args: CoverageArgs) -> anyhow::Result<()> {
+    let building = Building::from_str(&args.building)
+        .map_err(|_| anyhow!("Unknown building {}", args.building))?;
+    let coverage = Coverage::new(CoverageConfig {
+        cell_size: args.cell_size,
+        min_beacons: args.min_beacons,
+        ..Default::default()
+    });
+    let floors = match &args.floor {
+        Some(floor) => {
+            let floor = find_floor(building, floor)
+                .ok_or_else(|| anyhow!("Unknown floor {} {}", args.building, floor))?;
+            coverage.floor(building, floor).into_iter().collect()
+        }
+        None => coverage.building(building),
+    };
+    if floors.is_empty() {
+        bail!("No footprint for building {}", args.building);
+    }
+
+    let mut table = Table::new(&[
+        "FLOOR",
+        "CELLS",
+        "COVERED",
+        "ERROR",
+        "GAPS",
+        "LARGEST GAP (m2)",
+    ]);
+    for floor in &floors {
+        let gaps = floor.gaps();
+        table.push(vec![
+            floor.floor.to_string(),
+            floor.inside().count().to_string(),
+            format!("{:.0}%", floor.covered_share() * 100.0),
+            floor
+                .mean_error()
+                .map_or("-".to_string(), |e| format!("{:.1} m", e)),
+            gaps.len().to_string(),
+            gaps.first()
+                .map_or("-".to_string(), |g| format!("{:.0}", g.area)),
+        ]);
+    }
+    print!("{}", table);
+
+    println!();
+    print!("{}", gap_table(&floors, args.gaps));
+
+    if let Some(path) = &args.output {
+        let mut writer = BufWriter::new(File::create(path)?);
+        match args.format {
+            CoverageFormat::Geojson => write_geojson(&floors, &mut writer)?,
+            CoverageFormat::AsciiGrid => {
+                let [floor] = floors.as_slice() else {
+                    bail!("An ASCII grid holds a single floor, choose one with --floor");
+                };
+                let value = match args.value {
+                    Value::Beacons => CoverageValue::Beacons,
+                    Value::Gdop => CoverageValue::Gdop,
+                    Value::Error => CoverageValue::ExpectedError,
+                };
+                floor.write_ascii_grid(&mut writer, value)?;
+            }
+        }
+        println!("Grid written to     : {}", path.display());
+    }
+    Ok(())
+}
+
+/// The largest gaps of all floors, with the closest room to find them on site.
+fn gap_table(floors: &[FloorCoverage], limit: usize) -> Table {
+    let mut gaps: Vec<(&FloorCoverage, Gap)> = floors
+        .iter()
+        .flat_map(|floor| floor.gaps().into_iter().map(move |gap| (floor, gap)))
+        .collect();
+    gaps.sort_by(|a, b| b.1.area.total_cmp(&a.1.area));
+
+    let mut table = Table::new(&["FLOOR", "AREA (m2)", "LAT", "LON", "NEAR ROOM"]);
+    for (floor, gap) in gaps.into_iter().take(limit) {
+        table.push(vec![
+            floor.floor.to_string(),
+            format!("{:.0}", gap.area),
+            format!("{:.6}", gap.centroid.lat),
+            format!("{:.6}", gap.centroid.lon),
+            Room::nearest(floor.building, floor.floor, &gap.centroid)
+                .map_or("-".to_string(), Room::code),
+        ]);
+    }
+    table
+}
@@ -0,0 +1,166 @@
+use anyhow::Context;
+use eth_beacons_indoor::scanlog::ScanLog;
+use eth_beacons_indoor::{Position, BEACONS, ETH_UUID};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Flat view of a beacon, independent of whether it comes from the compiled-in dataset or a
+/// snapshot of the API.
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct Record {
+    pub uuid: String,
+    pub major: u16,
+    pub minor: u16,
+    pub lat: f64,
+    pub lon: f64,
+    pub building: String,
+    pub floor: String,
+    pub room: String,
+}
+
+impl Record {
+    pub fn position(&self) -> Position {
+        Position {
+            lat: self.lat,
+            lon: self.lon,
+        }
+    }
+}
+
+/// Saved response of the beacon API. Pagination and fields not listed here are ignored.
+#[derive(Deserialize)]
+struct Snapshot {
+    beacons: Vec<SnapshotBeacon>,
+}
+
+#[derive(Deserialize)]
+struct SnapshotBeacon {
+    major: u16,
+    minor: u16,
+    location: SnapshotLocation,
+    indoor: SnapshotRoom,
+}
+
+#[derive(Deserialize)]
+struct SnapshotLocation {
+    lat: f64,
+    lon: f64,
+}
+
+#[derive(Deserialize)]
+struct SnapshotRoom {
+    building: String,
+    floor: String,
+    room: String,
+}
+
+/// Building whose beacons the API does not serve. The generator adds them itself, so they are
+/// taken from the compiled-in dataset.
+const STATIC_BUILDING: &str = "SON";
+
+/// Loads all beacons from `snapshot`, or from the dataset compiled into this binary.
+pub(crate) fn load(snapshot: Option<&Path>) -> anyhow::Result<Vec<Record>> {
+    let mut records = match snapshot {
+        Some(path) => {
+            let file =
+                File::open(path).context(format!("Failed to open snapshot {}", path.display()))?;
+            read_snapshot(BufReader::new(file)).context("Failed to read snapshot json")?
+        }
+        None => compiled().collect(),
+    };
+
+    records.sort_by_key(|r| (r.major, r.minor));
+    Ok(records)
+}
+
+fn read_snapshot<R: Read>(reader: R) -> serde_json::Result<Vec<Record>> {
+    let snapshot: Snapshot = serde_json::from_reader(reader)?;
+    Ok(snapshot
+        .beacons
+        .into_iter()
+        .filter(|beacon| beacon.indoor.building != STATIC_BUILDING)
+        .map(|beacon| Record {
+            uuid: ETH_UUID.to_string(),
+            major: beacon.major,
+            minor: beacon.minor,
+            lat: beacon.location.lat,
+            lon: beacon.location.lon,
+            building: beacon.indoor.building,
+            floor: beacon.indoor.floor,
+            room: beacon.indoor.room,
+        })
+        .chain(compiled().filter(|r| r.building == STATIC_BUILDING))
+        .collect())
+}
+
+fn compiled() -> impl Iterator<Item = Record> {
+    BEACONS.iter().map(|beacon| Record {
+        uuid: beacon.id.uuid.to_string(),
+        major: beacon.id.major,
+        minor: beacon.id.minor,
+        lat: beacon.position.lat,
+        lon: beacon.position.lon,
+        building: beacon.location.building.as_ref().to_string(),
+        floor: beacon.location.floor.to_string(),
+        room: beacon.location.room.to_string(),
+    })
+}
+
+/// Reads a scan log and warns about sightings of beacons that are not in the dataset.
+pub(crate) fn read_scan_log(path: &Path) -> anyhow::Result<ScanLog> {
+    let log = ScanLog::read(BufReader::new(File::open(path)?))?;
+    if !log.unknown_beacons.is_empty() {
+        eprintln!(
+            "Skipped {} sightings of {} beacons not in the dataset",
+            log.unknown_beacons
+                .iter()
+                .map(|u| u.sightings)
+                .sum::<usize>(),
+            log.unknown_beacons.len()
+        );
+    }
+    Ok(log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_a_snapshot_with_the_static_beacons() {
+        let json = r#"{
+            "beacons": [
+                {
+                    "major": 1,
+                    "minor": 4711,
+                    "location": { "lat": 47.40734, "lon": 8.50905, "accuracy": 2 },
+                    "indoor": { "building": "HPH", "floor": "G", "room": "3" }
+                },
+                {
+                    "major": 99,
+                    "minor": 1,
+                    "location": { "lat": 47.5, "lon": 8.3 },
+                    "indoor": { "building": "SON", "floor": "B", "room": "1" }
+                }
+            ],
+            "next": null
+        }"#;
+        let records = read_snapshot(json.as_bytes()).unwrap();
+
+        assert_eq!(records[0].minor, 4711);
+        assert_eq!(records[0].uuid, ETH_UUID);
+        assert_eq!(
+            (records[0].building.as_str(), records[0].floor.as_str()),
+            ("HPH", "G")
+        );
+        assert!(records[1..]
+            .iter()
+            .all(|r| r.building == "SON" && r.floor == "A"));
+        assert_eq!(
+            records.len(),
+            1 + compiled().filter(|r| r.building == "SON").count()
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use crate::dataset::read_scan_log;
+use crate::table::Table;
+use clap::Args;
+use eth_beacons_indoor::benchmark::evaluate;
+use eth_beacons_indoor::estimator::EstimatorKind;
+use eth_beacons_indoor::radio::PathLossModel;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Args)]
+pub(crate) struct EvaluateArgs {
+    /// Scan log with ground truth positions
+    log: PathBuf,
+
+    /// Estimator to evaluate, may be repeated. Defaults to all of strongest, centroid,
+    /// least-squares and trimmed
+    #[arg(long = "estimator", value_parser = EstimatorKind::from_str)]
+    estimators: Vec<EstimatorKind>,
+}
+
+pub(crate) fn run(args: EvaluateArgs) -> anyhow::Result<()> {
+    let log = read_scan_log(&args.log)?;
+    let estimators = if args.estimators.is_empty() {
+        EstimatorKind::ALL.to_vec()
+    } else {
+        args.estimators
+    };
+
+    let mut table = Table::new(&[
+        "ESTIMATOR",
+        "FIXES",
+        "MEAN",
+        "MEDIAN",
+        "P90",
+        "ACCURACY",
+        "FLOOR",
+    ]);
+    for kind in estimators {
+        let evaluation = evaluate(&kind.build(PathLossModel::default()), &log.observations);
+        table.push(vec![
+            kind.as_ref().to_string(),
+            format!("{}/{}", evaluation.fixes, evaluation.observations),
+            format!("{:.1}", evaluation.mean_error),
+            format!("{:.1}", evaluation.median_error),
+            format!("{:.1}", evaluation.p90_error),
+            format!("{:.1}", evaluation.mean_accuracy),
+            evaluation
+                .floor_rate
+                .map_or("-".to_string(), |rate| format!("{:.0}%", rate * 100.0)),
+        ]);
+    }
+    print!("{}", table);
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use crate::dataset::{self, Record};
+use clap::{Args, ValueEnum};
+use eth_beacons_indoor::Position;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Coordinates {
+    /// Latitude and longitude in degrees
+    Wgs84,
+    /// CH1903+/LV95 easting and northing in metres
+    Lv95,
+    /// Legacy CH1903/LV03 y and x in metres
+    Lv03,
+}
+
+impl Coordinates {
+    fn headers(self) -> [&'static str; 2] {
+        match self {
+            Coordinates::Wgs84 => ["lat", "lon"],
+            Coordinates::Lv95 => ["east", "north"],
+            Coordinates::Lv03 => ["y", "x"],
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Args)]
+pub(crate) struct ExportArgs {
+    #[arg(long, value_enum, default_value_t = Coordinates::Wgs84)]
+    coordinates: Coordinates,
+
+    #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+    format: ExportFormat,
+
+    /// Write the export to this file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Export a saved API response instead of the compiled-in dataset
+    #[arg(long)]
+    snapshot: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct Row {
+    uuid: String,
+    major: u16,
+    minor: u16,
+    building: String,
+    floor: String,
+    room: String,
+    #[serde(flatten)]
+    coordinates: Coordinate,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Coordinate {
+    Wgs84 { lat: f64, lon: f64 },
+    Lv95 { east: f64, north: f64 },
+    Lv03 { y: f64, x: f64 },
+}
+
+impl Coordinate {
+    fn new(coordinates: Coordinates, position: &Position) -> Self {
+        match coordinates {
+            Coordinates::Wgs84 => Coordinate::Wgs84 {
+                lat: position.lat,
+                lon: position.lon,
+            },
+            Coordinates::Lv95 => {
+                let lv95 = position.to_lv95();
+                Coordinate::Lv95 {
+                    east: round_dm(lv95.east),
+                    north: round_dm(lv95.north),
+                }
+            }
+            Coordinates::Lv03 => {
+                let lv03 = position.to_lv03();
+                Coordinate::Lv03 {
+                    y: round_dm(lv03.y),
+                    x: round_dm(lv03.x),
+                }
+            }
+        }
+    }
+
+    fn values(&self) -> [f64; 2] {
+        match *self {
+            Coordinate::Wgs84 { lat, lon } => [lat, lon],
+            Coordinate::Lv95 { east, north } => [east, north],
+            Coordinate::Lv03 { y, x } => [y, x],
+        }
+    }
+}
+
+/// Rounds to decimetres. The formulas are accurate to about a metre, more digits would only
+/// suggest precision.
+fn round_dm(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+pub(crate) fn run(args: ExportArgs) -> anyhow::Result<()> {
+    let records = dataset::load(args.snapshot.as_deref())?;
+    let rows: Vec<Row> = records
+        .iter()
+        .map(|record| row(record, args.coordinates))
+        .collect();
+
+    let rendered = match args.format {
+        ExportFormat::Json => serde_json::to_string_pretty(&rows)? + "\n",
+        ExportFormat::Csv => render_csv(&rows, args.coordinates),
+    };
+
+    match args.output {
+        Some(path) => {
+            fs::write(&path, rendered)?;
+            println!("Exported {} beacons to: {}", rows.len(), path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+fn row(record: &Record, coordinates: Coordinates) -> Row {
+    Row {
+        uuid: record.uuid.clone(),
+        major: record.major,
+        minor: record.minor,
+        building: record.building.clone(),
+        floor: record.floor.clone(),
+        room: record.room.clone(),
+        coordinates: Coordinate::new(coordinates, &record.position()),
+    }
+}
+
+fn render_csv(rows: &[Row], coordinates: Coordinates) -> String {
+    let [first, second] = coordinates.headers();
+    let mut csv = format!(
+        "uuid,major,minor,building,floor,room,{},{}\n",
+        first, second
+    );
+    for row in rows {
+        let [first, second] = row.coordinates.values();
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{}\n",
+            row.uuid,
+            row.major,
+            row.minor,
+            csv_field(&row.building),
+            csv_field(&row.floor),
+            csv_field(&row.room),
+            first,
+            second
+        ));
+    }
+    csv
+}
+
+/// Quotes fields containing separators or quotes.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
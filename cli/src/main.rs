@@ -0,0 +1,60 @@
+//! Lookups, reports and positioning tools over the compiled-in beacon dataset.
+
+mod audit;
+mod coverage;
+mod dataset;
+mod evaluate;
+mod export;
+mod matching;
+mod plan;
+mod query;
+mod route;
+mod stats;
+mod table;
+
+use clap::{Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(author, version, about)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Look up beacons by id, building, floor, room or position
+    Query(query::QueryArgs),
+    /// Print beacon counts, spacing and coverage per building and floor
+    Stats(stats::StatsArgs),
+    /// Export the dataset as CSV or JSON, optionally in Swiss LV95 or LV03 coordinates
+    Export(export::ExportArgs),
+    /// Report beacons that seem moved, missing or misconfigured in a scan log
+    Audit(audit::AuditArgs),
+    /// Compare position estimators on a scan log with ground truth positions
+    Evaluate(evaluate::EvaluateArgs),
+    /// Report beacon coverage and the largest uncovered areas of a building per floor
+    Coverage(coverage::CoverageArgs),
+    /// Propose positions for new beacons on a floor and write a worklist for installers
+    Plan(plan::PlanArgs),
+    /// Print turn-by-turn directions between two rooms
+    Route(route::RouteArgs),
+    /// Match the positions estimated from a scan log onto the beacon graph or a corridor network
+    Match(matching::MatchArgs),
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    match args.command {
+        Command::Query(query_args) => query::run(query_args),
+        Command::Stats(stats_args) => stats::run(stats_args),
+        Command::Export(export_args) => export::run(export_args),
+        Command::Audit(audit_args) => audit::run(audit_args),
+        Command::Evaluate(evaluate_args) => evaluate::run(evaluate_args),
+        Command::Coverage(coverage_args) => coverage::run(coverage_args),
+        Command::Plan(plan_args) => plan::run(plan_args),
+        Command::Route(route_args) => route::run(route_args),
+        Command::Match(match_args) => matching::run(match_args),
+    }
+}
@@ -0,0 +1,153 @@
+use crate::dataset::read_scan_log;
+use crate::table::Table;
+use anyhow::Context;
+use clap::Args;
+use eth_beacons_indoor::estimator::{Estimate, EstimatorKind};
+use eth_beacons_indoor::graph::{BeaconGraph, GraphConfig};
+use eth_beacons_indoor::matching::{
+    CorridorNetwork, MapMatcher, MatchedPath, MatcherConfig, WalkableGraph,
+};
+use eth_beacons_indoor::radio::PathLossModel;
+use eth_beacons_indoor::scanlog::Observation;
+use eth_beacons_indoor::{Building, Position};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Args)]
+pub(crate) struct MatchArgs {
+    /// Scan log to estimate positions from, with ground truth positions to compare against
+    log: PathBuf,
+
+    /// Estimator to compute the positions with
+    #[arg(long, default_value = "least-squares", value_parser = EstimatorKind::from_str)]
+    estimator: EstimatorKind,
+
+    /// Corridor network to match onto instead of the beacon neighbourhood graph
+    #[arg(long)]
+    corridors: Option<PathBuf>,
+
+    /// Only use the beacons of this building for the neighbourhood graph
+    #[arg(long, value_parser = Building::from_str)]
+    building: Option<Building>,
+
+    /// Write the matched positions as CSV to this file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub(crate) fn run(args: MatchArgs) -> anyhow::Result<()> {
+    let log = read_scan_log(&args.log)?;
+    let estimator = args.estimator.build(PathLossModel::default());
+    let (observations, estimates): (Vec<&Observation>, Vec<Estimate>) = log
+        .observations
+        .iter()
+        .filter_map(|o| estimator.estimate(&o.sightings).map(|e| (o, e)))
+        .unzip();
+
+    let path = match &args.corridors {
+        Some(corridors) => {
+            let network = CorridorNetwork::read(BufReader::new(File::open(corridors)?))
+                .with_context(|| format!("Failed to read {}", corridors.display()))?;
+            match_with(network, &estimates)
+        }
+        None => {
+            let config = GraphConfig::default();
+            let graph = match args.building {
+                Some(building) => BeaconGraph::for_building(building, &config),
+                None => BeaconGraph::new(&config),
+            };
+            match_with(graph, &estimates)
+        }
+    };
+
+    let mut table = Table::new(&["POSITIONS", "MATCHED", "MEAN", "FLOOR", "CONFIDENCE"]);
+    let mut raw = Summary::default();
+    let mut matched = Summary::default();
+    for ((observation, estimate), step) in observations.iter().zip(&estimates).zip(&path.steps) {
+        let Some(step) = step else {
+            continue;
+        };
+        raw.add(
+            observation,
+            &estimate.position,
+            estimate.location.map(|l| (l.building, l.floor)),
+        );
+        matched.add(observation, &step.position, step.floor);
+        matched.confidence += step.confidence;
+    }
+    for (name, summary) in [("estimated", &raw), ("matched", &matched)] {
+        let count = summary.count.max(1) as f64;
+        table.push(vec![
+            name.to_string(),
+            format!("{}/{}", summary.count, log.observations.len()),
+            format!("{:.1}", summary.error / count),
+            format!("{:.0}%", summary.floors as f64 / count * 100.0),
+            if name == "matched" {
+                format!("{:.2}", summary.confidence / count)
+            } else {
+                "-".to_string()
+            },
+        ]);
+    }
+    print!("{}", table);
+    println!("Breaks              : {}", path.breaks);
+
+    if let Some(output) = &args.output {
+        let mut writer = BufWriter::new(File::create(output)?);
+        writeln!(writer, "timestamp,lat,lon,building,floor,confidence,offset")?;
+        for (observation, step) in observations.iter().zip(&path.steps) {
+            let Some(step) = step else {
+                continue;
+            };
+            let (building, floor) = match &step.floor {
+                Some((building, floor)) => (building.as_ref(), *floor),
+                None => ("", ""),
+            };
+            writeln!(
+                writer,
+                "{},{:.7},{:.7},{},{},{:.3},{:.2}",
+                observation.timestamp,
+                step.position.lat,
+                step.position.lon,
+                building,
+                floor,
+                step.confidence,
+                step.offset
+            )?;
+        }
+        writer.flush()?;
+        println!("Matched positions   : {}", output.display());
+    }
+    Ok(())
+}
+
+fn match_with<G: WalkableGraph>(graph: G, estimates: &[Estimate]) -> MatchedPath {
+    MapMatcher::new(graph, MatcherConfig::default()).match_estimates(estimates)
+}
+
+#[derive(Default)]
+struct Summary {
+    count: usize,
+    error: f64,
+    floors: usize,
+    confidence: f64,
+}
+
+impl Summary {
+    /// Adds an estimate, whose floor counts as correct only in the same building as the ground
+    /// truth.
+    fn add(
+        &mut self,
+        observation: &Observation,
+        position: &Position,
+        floor: Option<(Building, &str)>,
+    ) {
+        self.count += 1;
+        self.error += position.distance(&observation.position);
+        if floor.is_some() && floor == observation.floor {
+            self.floors += 1;
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::table::Table;
+use anyhow::anyhow;
+use clap::Args;
+use eth_beacons_indoor::planner::{Objective, Planner, PlannerConfig};
+use eth_beacons_indoor::resolver::find_floor;
+use eth_beacons_indoor::room::Room;
+use eth_beacons_indoor::Building;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Args)]
+pub(crate) struct PlanArgs {
+    building: String,
+
+    #[arg(short, long)]
+    floor: String,
+
+    /// Number of beacons to propose
+    #[arg(short = 'n', long, default_value_t = 5)]
+    count: usize,
+
+    /// `coverage` to cover uncovered areas first, `accuracy` to lower the mean error
+    #[arg(long, default_value = "coverage", value_parser = Objective::from_str)]
+    objective: Objective,
+
+    /// Never propose a beacon closer than this many metres to another beacon
+    #[arg(long, default_value_t = PlannerConfig::default().min_spacing)]
+    min_spacing: f64,
+
+    /// Write the worklist for installers to this CSV file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub(crate) fn run(args: PlanArgs) -> anyhow::Result<()> {
+    let building = Building::from_str(&args.building)
+        .map_err(|_| anyhow!("Unknown building {}", args.building))?;
+    let floor = find_floor(building, &args.floor)
+        .ok_or_else(|| anyhow!("Unknown floor {} {}", args.building, args.floor))?;
+    let planner = Planner::new(PlannerConfig {
+        objective: args.objective,
+        min_spacing: args.min_spacing,
+        ..Default::default()
+    });
+    let plan = planner
+        .plan(building, floor, args.count)
+        .ok_or_else(|| anyhow!("No footprint for building {}", args.building))?;
+
+    let mut table = Table::new(&[
+        "#",
+        "NEAR ROOM",
+        "LAT",
+        "LON",
+        "EAST",
+        "NORTH",
+        "COVERED",
+        "ERROR",
+    ]);
+    for (index, proposal) in plan.proposals.iter().enumerate() {
+        table.push(vec![
+            (index + 1).to_string(),
+            proposal.room.map_or("-".to_string(), Room::code),
+            format!("{:.6}", proposal.position.lat),
+            format!("{:.6}", proposal.position.lon),
+            format!("{:.1}", proposal.lv95.east),
+            format!("{:.1}", proposal.lv95.north),
+            format!("{:.0}%", proposal.score.covered_share * 100.0),
+            format!("{:.1} m", proposal.score.mean_error),
+        ]);
+    }
+    print!("{}", table);
+
+    let (before, after) = (plan.before, plan.after());
+    println!();
+    println!("Existing beacons    : {}", plan.existing);
+    println!(
+        "Covered             : {:.0}% -> {:.0}%",
+        before.covered_share * 100.0,
+        after.covered_share * 100.0
+    );
+    println!(
+        "Mean error          : {:.1} m -> {:.1} m",
+        before.mean_error, after.mean_error
+    );
+
+    if let Some(path) = &args.output {
+        plan.write_worklist(&mut BufWriter::new(File::create(path)?))?;
+        println!("Worklist written to : {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use crate::dataset::{self, Record};
+use crate::table::{Format, Table};
+use clap::Args;
+use eth_beacons_indoor::Position;
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub(crate) struct QueryArgs {
+    #[arg(long)]
+    major: Option<u16>,
+
+    #[arg(long)]
+    minor: Option<u16>,
+
+    #[arg(short, long)]
+    building: Option<String>,
+
+    #[arg(short, long)]
+    floor: Option<String>,
+
+    /// Room number (`31`) or full room code (`SON A 31`)
+    #[arg(short, long)]
+    room: Option<String>,
+
+    /// Sort by distance to `lat,lon` and only keep the closest `--limit` beacons
+    #[arg(long, value_parser = parse_position)]
+    near: Option<Position>,
+
+    #[arg(long, default_value_t = 10)]
+    limit: usize,
+
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+
+    /// Query a saved API response instead of the compiled-in dataset
+    #[arg(long)]
+    snapshot: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct Match {
+    #[serde(flatten)]
+    record: Record,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    distance: Option<f64>,
+}
+
+pub(crate) fn run(args: QueryArgs) -> anyhow::Result<()> {
+    let records = dataset::load(args.snapshot.as_deref())?;
+    let matches = find(&args, records);
+
+    match args.format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&matches)?),
+        Format::Table => {
+            let mut headers = vec!["MAJOR", "MINOR", "BUILDING", "FLOOR", "ROOM", "LAT", "LON"];
+            if args.near.is_some() {
+                headers.push("DISTANCE [m]");
+            }
+
+            let mut table = Table::new(&headers);
+            for m in &matches {
+                let mut row = vec![
+                    m.record.major.to_string(),
+                    m.record.minor.to_string(),
+                    m.record.building.clone(),
+                    m.record.floor.clone(),
+                    m.record.room.clone(),
+                    format!("{:.6}", m.record.lat),
+                    format!("{:.6}", m.record.lon),
+                ];
+                if let Some(distance) = m.distance {
+                    row.push(format!("{:.1}", distance));
+                }
+                table.push(row);
+            }
+
+            print!("{}", table);
+            println!("{} beacon(s)", matches.len());
+        }
+    }
+
+    Ok(())
+}
+
+/// Records matching the filters of `args`, the closest first with `--near`.
+fn find(args: &QueryArgs, records: Vec<Record>) -> Vec<Match> {
+    let (building, floor, room) = match args.room.as_deref().map(split_room_code) {
+        Some((building, floor, room)) => (
+            building.or(args.building.clone()),
+            floor.or(args.floor.clone()),
+            Some(room),
+        ),
+        None => (args.building.clone(), args.floor.clone(), None),
+    };
+
+    let mut matches: Vec<Match> = records
+        .into_iter()
+        .filter(|r| args.major.is_none_or(|major| r.major == major))
+        .filter(|r| args.minor.is_none_or(|minor| r.minor == minor))
+        .filter(|r| matches_ignore_case(&building, &r.building))
+        .filter(|r| matches_ignore_case(&floor, &r.floor))
+        .filter(|r| matches_ignore_case(&room, &r.room))
+        .map(|record| Match {
+            distance: args.near.map(|near| near.distance(&record.position())),
+            record,
+        })
+        .collect();
+
+    if args.near.is_some() {
+        let distance = |m: &Match| m.distance.unwrap_or(f64::INFINITY);
+        matches.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+        matches.truncate(args.limit);
+    }
+    matches
+}
+
+fn matches_ignore_case(filter: &Option<String>, value: &str) -> bool {
+    filter
+        .as_ref()
+        .is_none_or(|filter| filter.eq_ignore_ascii_case(value))
+}
+
+/// Splits `HPH G 3` into building, floor and room. Shorter codes are read from the right, so
+/// `G 3` only carries floor and room.
+fn split_room_code(code: &str) -> (Option<String>, Option<String>, String) {
+    let mut parts: Vec<String> = code.split_whitespace().map(str::to_string).collect();
+    let room = parts.pop().unwrap_or_default();
+    let floor = parts.pop();
+    let building = parts.pop();
+    (building, floor, room)
+}
+
+pub(crate) fn parse_position(value: &str) -> Result<Position, String> {
+    let (lat, lon) = value
+        .split_once(',')
+        .ok_or_else(|| format!("expected `lat,lon`, got `{}`", value))?;
+
+    Ok(Position {
+        lat: parse_coordinate(lat, "latitude")?,
+        lon: parse_coordinate(lon, "longitude")?,
+    })
+}
+
+fn parse_coordinate(value: &str, name: &str) -> Result<f64, String> {
+    let coordinate: f64 = value
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid {}: {}", name, e))?;
+    if !coordinate.is_finite() {
+        return Err(format!(
+            "invalid {}: `{}` is not a finite number",
+            name,
+            value.trim()
+        ));
+    }
+    Ok(coordinate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        query: QueryArgs,
+    }
+
+    fn find_with(args: &[&str]) -> Vec<(u16, Option<f64>)> {
+        let cli = Cli::parse_from(std::iter::once("query").chain(args.iter().copied()));
+        let records = [
+            (1, 47.0, 8.0, "HPH", "G", "3"),
+            (2, 47.001, 8.0, "HPH", "G", "10.002"),
+            (3, 47.0, 8.002, "HG", "EO", "3"),
+            (4, 47.0005, 8.0, "HPH", "H", "3"),
+        ]
+        .map(|(minor, lat, lon, building, floor, room)| Record {
+            uuid: String::new(),
+            major: 1,
+            minor,
+            lat,
+            lon,
+            building: building.to_string(),
+            floor: floor.to_string(),
+            room: room.to_string(),
+        });
+        find(&cli.query, records.to_vec())
+            .into_iter()
+            .map(|m| (m.record.minor, m.distance))
+            .collect()
+    }
+
+    #[test]
+    fn splits_room_codes_from_the_right() {
+        assert_eq!(
+            split_room_code("HPH G 3"),
+            (
+                Some("HPH".to_string()),
+                Some("G".to_string()),
+                "3".to_string()
+            )
+        );
+        assert_eq!(
+            split_room_code(" G  3 "),
+            (None, Some("G".to_string()), "3".to_string())
+        );
+        assert_eq!(split_room_code("3"), (None, None, "3".to_string()));
+    }
+
+    #[test]
+    fn filters_rooms_ignoring_case() {
+        assert_eq!(find_with(&["--room", "hph g 3"]), [(1, None)]);
+        assert_eq!(
+            find_with(&["--room", "3"]),
+            [(1, None), (3, None), (4, None)]
+        );
+        assert_eq!(find_with(&["--room", "3", "--building", "hph"]).len(), 2);
+        assert!(find_with(&["--room", "HPH G 10"]).is_empty());
+    }
+
+    #[test]
+    fn keeps_the_closest_beacons_near_a_position() {
+        let found = find_with(&["--near", "47.0001, 8.0", "--limit", "3"]);
+        let minors: Vec<u16> = found.iter().map(|(minor, _)| *minor).collect();
+        assert_eq!(minors, [1, 4, 2]);
+        assert!((found[0].1.unwrap() - 11.1).abs() < 0.1);
+        assert!((found[1].1.unwrap() - 44.5).abs() < 0.1);
+
+        let filtered = find_with(&["--near", "47.0001,8.0", "--building", "HG"]);
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].0, 3);
+        assert!(Cli::try_parse_from(["query", "--near", "47.0"]).is_err());
+        assert!(Cli::try_parse_from(["query", "--near", "inf,8"]).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::{anyhow, Context};
+use clap::Args;
+use eth_beacons_indoor::routing::{Endpoint, RouteOverrides, Router, RouterConfig};
+use eth_beacons_indoor::wayfinding::{describe, Language};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Args)]
+pub(crate) struct RouteArgs {
+    /// Room code to start in, e.g. "HG E 41"
+    from: String,
+
+    /// Room code of the destination
+    to: String,
+
+    /// Language of the instructions, `de` or `en`
+    #[arg(long, default_value = "en", value_parser = Language::from_str)]
+    language: Language,
+
+    /// File with stairs, elevators, passages and closed links the beacon positions do not reveal
+    #[arg(long)]
+    overrides: Option<PathBuf>,
+}
+
+pub(crate) fn run(args: RouteArgs) -> anyhow::Result<()> {
+    let from = Endpoint::room(&args.from).ok_or_else(|| anyhow!("Unknown room {}", args.from))?;
+    let to = Endpoint::room(&args.to).ok_or_else(|| anyhow!("Unknown room {}", args.to))?;
+    let overrides = match &args.overrides {
+        Some(path) => RouteOverrides::read(BufReader::new(File::open(path)?))
+            .with_context(|| format!("Failed to read {}", path.display()))?,
+        None => RouteOverrides::new(),
+    };
+
+    let router = Router::with_overrides(RouterConfig::default(), &overrides);
+    let route = router
+        .route(&from, &to)
+        .ok_or_else(|| anyhow!("No route from {} to {}", args.from, args.to))?;
+
+    for (number, line) in describe(&route, args.language).iter().enumerate() {
+        println!("{:>2}. {}", number + 1, line);
+    }
+    println!("Distance            : {:.0} m", route.distance);
+    Ok(())
+}
@@ -0,0 +1,337 @@
+use crate::dataset::{self, Record};
+use crate::table::{Format, Table};
+use clap::Args;
+use eth_beacons_indoor::benchmark::percentile;
+use eth_beacons_indoor::floor::level;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub(crate) struct StatsArgs {
+    /// Floors with fewer beacons than this are reported as sparse
+    #[arg(long, default_value_t = 3)]
+    min_beacons: usize,
+
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+
+    /// Write the report to this file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Use a saved API response instead of the compiled-in dataset
+    #[arg(long)]
+    snapshot: Option<PathBuf>,
+}
+
+/// Nearest-neighbour distances between beacons of the same floor, in metres.
+#[derive(Serialize, Debug, Default)]
+struct Spacing {
+    min: f64,
+    p10: f64,
+    p50: f64,
+    p90: f64,
+    max: f64,
+}
+
+#[derive(Serialize, Debug)]
+struct FloorStats {
+    building: String,
+    floor: String,
+    beacons: usize,
+    rooms: usize,
+    mezzanine: bool,
+    sparse: bool,
+    spacing: Option<Spacing>,
+}
+
+#[derive(Serialize, Debug)]
+struct BuildingStats {
+    building: String,
+    beacons: usize,
+    floors: usize,
+    rooms: usize,
+    sparse_floors: Vec<String>,
+    mezzanine_share: f64,
+    spacing: Option<Spacing>,
+}
+
+#[derive(Serialize, Debug)]
+struct Report {
+    beacons: usize,
+    buildings: Vec<BuildingStats>,
+    floors: Vec<FloorStats>,
+}
+
+pub(crate) fn run(args: StatsArgs) -> anyhow::Result<()> {
+    let records = dataset::load(args.snapshot.as_deref())?;
+    let report = build_report(&records, args.min_beacons);
+
+    let rendered = match args.format {
+        Format::Json => serde_json::to_string_pretty(&report)? + "\n",
+        Format::Table => render_tables(&report),
+    };
+
+    match args.output {
+        Some(path) => {
+            fs::write(&path, rendered)?;
+            println!("Stats written to    : {}", path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+fn build_report(records: &[Record], min_beacons: usize) -> Report {
+    let mut grouped: BTreeMap<&str, BTreeMap<&str, Vec<&Record>>> = BTreeMap::new();
+    for record in records {
+        grouped
+            .entry(&record.building)
+            .or_default()
+            .entry(&record.floor)
+            .or_default()
+            .push(record);
+    }
+
+    let mut buildings = Vec::new();
+    let mut floors = Vec::new();
+
+    for (building, by_floor) in grouped {
+        let mut building_distances = Vec::new();
+        let mut building_rooms = BTreeSet::new();
+        let mut sparse_floors = Vec::new();
+        let mut beacons = 0;
+        let mut mezzanine_beacons = 0;
+
+        for (floor, floor_records) in by_floor.iter() {
+            let mut distances = nearest_neighbour_distances(floor_records);
+            let rooms: BTreeSet<&str> = floor_records.iter().map(|r| r.room.as_str()).collect();
+            let mezzanine = is_mezzanine(floor);
+            let sparse = floor_records.len() < min_beacons;
+
+            beacons += floor_records.len();
+            if mezzanine {
+                mezzanine_beacons += floor_records.len();
+            }
+            if sparse {
+                sparse_floors.push(floor.to_string());
+            }
+            building_rooms.extend(rooms.iter().map(|room| (*floor, *room)));
+            building_distances.extend_from_slice(&distances);
+
+            floors.push(FloorStats {
+                building: building.to_string(),
+                floor: floor.to_string(),
+                beacons: floor_records.len(),
+                rooms: rooms.len(),
+                mezzanine,
+                sparse,
+                spacing: spacing(&mut distances),
+            });
+        }
+
+        buildings.push(BuildingStats {
+            building: building.to_string(),
+            beacons,
+            floors: by_floor.len(),
+            rooms: building_rooms.len(),
+            sparse_floors,
+            mezzanine_share: mezzanine_beacons as f64 / beacons as f64,
+            spacing: spacing(&mut building_distances),
+        });
+    }
+
+    Report {
+        beacons: records.len(),
+        buildings,
+        floors,
+    }
+}
+
+/// Mezzanines like `EO` lie half a storey above their base floor.
+fn is_mezzanine(floor: &str) -> bool {
+    level(floor).is_some_and(|level| level.fract() != 0.0)
+}
+
+fn nearest_neighbour_distances(records: &[&Record]) -> Vec<f64> {
+    records
+        .iter()
+        .enumerate()
+        .filter_map(|(i, record)| {
+            records
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| i != *j)
+                .map(|(_, other)| record.position().distance(&other.position()))
+                .min_by(f64::total_cmp)
+        })
+        .collect()
+}
+
+fn spacing(distances: &mut [f64]) -> Option<Spacing> {
+    if distances.is_empty() {
+        return None;
+    }
+    distances.sort_by(f64::total_cmp);
+
+    Some(Spacing {
+        min: distances[0],
+        p10: percentile(distances, 0.1),
+        p50: percentile(distances, 0.5),
+        p90: percentile(distances, 0.9),
+        max: distances[distances.len() - 1],
+    })
+}
+
+fn render_tables(report: &Report) -> String {
+    let spacing_cells = |spacing: &Option<Spacing>| match spacing {
+        Some(s) => vec![
+            format!("{:.1}", s.p10),
+            format!("{:.1}", s.p50),
+            format!("{:.1}", s.p90),
+        ],
+        None => vec!["-".to_string(); 3],
+    };
+
+    let mut buildings = Table::new(&[
+        "BUILDING",
+        "BEACONS",
+        "FLOORS",
+        "ROOMS",
+        "NN P10 [m]",
+        "NN P50 [m]",
+        "NN P90 [m]",
+        "MEZZANINE",
+        "SPARSE FLOORS",
+    ]);
+    for b in &report.buildings {
+        let mut row = vec![
+            b.building.clone(),
+            b.beacons.to_string(),
+            b.floors.to_string(),
+            b.rooms.to_string(),
+        ];
+        row.extend(spacing_cells(&b.spacing));
+        row.push(format!("{:.0}%", b.mezzanine_share * 100.0));
+        row.push(b.sparse_floors.join(" "));
+        buildings.push(row);
+    }
+
+    let mut floors = Table::new(&[
+        "BUILDING",
+        "FLOOR",
+        "BEACONS",
+        "ROOMS",
+        "NN P10 [m]",
+        "NN P50 [m]",
+        "NN P90 [m]",
+        "FLAGS",
+    ]);
+    for f in &report.floors {
+        let mut row = vec![
+            f.building.clone(),
+            f.floor.clone(),
+            f.beacons.to_string(),
+            f.rooms.to_string(),
+        ];
+        row.extend(spacing_cells(&f.spacing));
+
+        let mut flags = Vec::new();
+        if f.mezzanine {
+            flags.push("mezzanine");
+        }
+        if f.sparse {
+            flags.push("sparse");
+        }
+        row.push(flags.join(","));
+        floors.push(row);
+    }
+
+    format!(
+        "{} beacons in {} buildings\n\n{}\n{}",
+        report.beacons,
+        report.buildings.len(),
+        buildings,
+        floors
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eth_beacons_indoor::Position;
+
+    fn record(building: &str, floor: &str, room: &str, lat: f64) -> Record {
+        Record {
+            uuid: String::new(),
+            major: 1,
+            minor: 1,
+            lat,
+            lon: 8.0,
+            building: building.to_string(),
+            floor: floor.to_string(),
+            room: room.to_string(),
+        }
+    }
+
+    #[test]
+    fn reports_sparse_floors_mezzanines_and_spacing() {
+        let records = [
+            record("HG", "E", "1", 47.0),
+            record("HG", "E", "1", 47.0001),
+            record("HG", "E", "2", 47.0003),
+            record("HG", "EO", "1", 47.0),
+            record("HPH", "O", "1", 47.0),
+            record("HPH", "O", "2", 47.0001),
+        ];
+        let report = build_report(&records, 2);
+        let step = Position {
+            lat: 47.0,
+            lon: 8.0,
+        }
+        .distance(&Position {
+            lat: 47.0001,
+            lon: 8.0,
+        });
+
+        assert_eq!(report.beacons, 6);
+        let floors: Vec<(&str, &str, usize, usize, bool, bool)> = report
+            .floors
+            .iter()
+            .map(|f| {
+                (
+                    f.building.as_str(),
+                    f.floor.as_str(),
+                    f.beacons,
+                    f.rooms,
+                    f.mezzanine,
+                    f.sparse,
+                )
+            })
+            .collect();
+        assert_eq!(
+            floors,
+            [
+                ("HG", "E", 3, 2, false, false),
+                ("HG", "EO", 1, 1, true, true),
+                ("HPH", "O", 2, 2, false, false),
+            ]
+        );
+
+        let hg = &report.buildings[0];
+        assert_eq!((hg.beacons, hg.floors, hg.rooms), (4, 2, 3));
+        assert_eq!(hg.sparse_floors, ["EO"]);
+        assert_eq!(hg.mezzanine_share, 0.25);
+        assert_eq!(report.buildings[1].mezzanine_share, 0.0);
+
+        // The nearest neighbours on E are one, one and two steps away
+        let spacing = report.floors[0].spacing.as_ref().unwrap();
+        assert!((spacing.min - step).abs() < 1e-6);
+        assert!((spacing.p50 - step).abs() < 1e-6);
+        assert!((spacing.max - 2.0 * step).abs() < 1e-6);
+        assert!(report.floors[1].spacing.is_none());
+        assert!((hg.spacing.as_ref().unwrap().max - 2.0 * step).abs() < 1e-6);
+    }
+}
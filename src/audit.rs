@@ -0,0 +1,491 @@
+use crate::radio::PathLossModel;
+use crate::scanlog::Observation;
+use crate::{Beacon, Building, Id, Position, BEACONS};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Metres per degree of latitude.
+const METRES_PER_DEGREE: f64 = 111_195.0;
+
+#[derive(Debug, Clone)]
+pub struct AuditConfig {
+    pub path_loss: PathLossModel,
+    /// Beacons with fewer sightings are not checked for moves, floors and transmit power.
+    pub min_sightings: usize,
+    /// Beacons heard around a point further than this many metres from their position are
+    /// reported as moved.
+    pub max_displacement: f64,
+    /// Share of the floor evidence another floor needs before a beacon is reported on the
+    /// wrong floor.
+    pub floor_share: f64,
+    /// Sightings at or below this RSSI in dBm carry no floor evidence.
+    pub min_rssi: f64,
+    /// Beacons whose median RSSI deviates from the path loss model by more than this many dB
+    /// are reported as misconfigured.
+    pub max_rssi_offset: f64,
+    /// Scans within this many metres of a beacon on its floor are expected to hear it.
+    pub detection_range: f64,
+    /// Beacons need at least this many such scans before they can be reported missing.
+    pub min_opportunities: usize,
+    /// Beacons heard by at most this share of those scans are reported missing.
+    pub max_detection_rate: f64,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        AuditConfig {
+            path_loss: PathLossModel::default(),
+            min_sightings: 10,
+            max_displacement: 15.0,
+            floor_share: 0.6,
+            min_rssi: -100.0,
+            max_rssi_offset: 12.0,
+            detection_range: 8.0,
+            min_opportunities: 5,
+            max_detection_rate: 0.05,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    /// Heard around `apparent`, `displacement` metres from its position in the dataset.
+    Moved {
+        apparent: Position,
+        displacement: f64,
+    },
+    /// Mostly heard from another floor, which has `share` of the floor evidence.
+    WrongFloor {
+        building: Building,
+        floor: &'static str,
+        share: f64,
+    },
+    /// The median RSSI is `offset` dB above or below the path loss model, e.g. because of a
+    /// wrong transmit power or an obstructed beacon.
+    Misconfigured { offset: f64 },
+    /// Heard by `detected` of `opportunities` scans within detection range.
+    Missing {
+        opportunities: usize,
+        detected: usize,
+    },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::Moved {
+                apparent,
+                displacement,
+            } => write!(
+                f,
+                "moved about {:.0} m, now near {:.6}, {:.6}",
+                displacement, apparent.lat, apparent.lon
+            ),
+            Issue::WrongFloor {
+                building,
+                floor,
+                share,
+            } => write!(
+                f,
+                "heard from floor {} {} ({:.0}% of sightings)",
+                building.as_ref(),
+                floor,
+                share * 100.0
+            ),
+            Issue::Misconfigured { offset } => {
+                write!(f, "signal {:+.0} dB off, check transmit power", offset)
+            }
+            Issue::Missing {
+                opportunities,
+                detected,
+            } => write!(
+                f,
+                "not heard, seen by {} of {} scans nearby",
+                detected, opportunities
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub beacon: &'static Beacon,
+    pub issue: Issue,
+    /// Number of sightings of the beacon in the audited observations.
+    pub sightings: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AuditReport {
+    pub observations: usize,
+    /// Number of distinct beacons sighted.
+    pub beacons_seen: usize,
+    /// Findings ordered by building, floor, room and beacon.
+    pub findings: Vec<Finding>,
+}
+
+/// Plain text report for facilities, one line per finding.
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} scans, {} beacons heard, {} findings",
+            self.observations,
+            self.beacons_seen,
+            self.findings.len()
+        )?;
+        for finding in &self.findings {
+            let location = &finding.beacon.location;
+            let id = &finding.beacon.id;
+            writeln!(
+                f,
+                "{} {} {}\tbeacon {}/{}\t{}",
+                location.building.as_ref(),
+                location.floor,
+                location.room,
+                id.major,
+                id.minor,
+                finding.issue
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct BeaconEvidence {
+    beacon: &'static Beacon,
+    sightings: usize,
+    /// Sum of weighted scanner positions and of the weights.
+    lat: f64,
+    lon: f64,
+    weight: f64,
+    /// Measured minus modelled RSSI of each sighting.
+    offsets: Vec<f64>,
+    floors: HashMap<(Building, &'static str), f64>,
+    opportunities: usize,
+    detected: usize,
+}
+
+impl BeaconEvidence {
+    fn new(beacon: &'static Beacon) -> Self {
+        BeaconEvidence {
+            beacon,
+            sightings: 0,
+            lat: 0.0,
+            lon: 0.0,
+            weight: 0.0,
+            offsets: Vec::new(),
+            floors: HashMap::new(),
+            opportunities: 0,
+            detected: 0,
+        }
+    }
+}
+
+/// Finds beacons whose sightings in a log of observations do not match the dataset.
+///
+/// A beacon is reported as moved if the scanner positions, weighted by how close the RSSI says
+/// the beacon was, centre far from its position, as on the wrong floor if most of its floor
+/// evidence comes from another floor, and as misconfigured if it is consistently louder or
+/// quieter than the [`PathLossModel`] expects. Beacons that scans within
+/// [`AuditConfig::detection_range`] on their floor hardly ever hear are reported missing, but
+/// only scans on a known floor that heard some beacon count, so neither a switched off scanner
+/// nor beacons on the floors above and below are mistaken for missing beacons.
+#[derive(Debug, Clone, Default)]
+pub struct Auditor {
+    config: AuditConfig,
+}
+
+impl Auditor {
+    pub fn new(config: AuditConfig) -> Self {
+        Auditor { config }
+    }
+
+    pub fn audit(&self, observations: &[Observation]) -> AuditReport {
+        self.audit_against(observations, BeaconsByLatitude::new(BEACONS))
+    }
+
+    fn audit_against(
+        &self,
+        observations: &[Observation],
+        by_latitude: BeaconsByLatitude,
+    ) -> AuditReport {
+        let config = &self.config;
+        let mut evidence: HashMap<Id, BeaconEvidence> = HashMap::new();
+
+        for observation in observations {
+            for sighting in &observation.sightings {
+                let beacon = sighting.beacon;
+                let entry = evidence
+                    .entry(beacon.id)
+                    .or_insert_with(|| BeaconEvidence::new(beacon));
+                entry.sightings += 1;
+
+                let distance = config.path_loss.distance(sighting.rssi).max(0.5);
+                let weight = 1.0 / (distance * distance + observation.accuracy.powi(2));
+                entry.lat += weight * observation.position.lat;
+                entry.lon += weight * observation.position.lon;
+                entry.weight += weight;
+
+                let actual = observation.position.distance(&beacon.position);
+                entry
+                    .offsets
+                    .push(sighting.rssi - config.path_loss.rssi(actual));
+
+                if let Some(floor) = observation.floor {
+                    if sighting.rssi > config.min_rssi {
+                        *entry.floors.entry(floor).or_default() +=
+                            10f64.powf((sighting.rssi - config.min_rssi) / 20.0);
+                    }
+                }
+            }
+
+            let Some((building, floor)) = observation.floor else {
+                continue;
+            };
+            if observation.sightings.is_empty() {
+                continue;
+            }
+            let range = config.detection_range + observation.accuracy;
+            for beacon in by_latitude.near(&observation.position, range) {
+                if beacon.location.building != building || beacon.location.floor != floor {
+                    continue;
+                }
+                let entry = evidence
+                    .entry(beacon.id)
+                    .or_insert_with(|| BeaconEvidence::new(beacon));
+                entry.opportunities += 1;
+                if observation
+                    .sightings
+                    .iter()
+                    .any(|s| s.beacon.id == beacon.id)
+                {
+                    entry.detected += 1;
+                }
+            }
+        }
+
+        let mut findings = Vec::new();
+        for evidence in evidence.values() {
+            let beacon = evidence.beacon;
+            findings.extend(
+                self.check(beacon, evidence)
+                    .into_iter()
+                    .map(|issue| Finding {
+                        beacon,
+                        issue,
+                        sightings: evidence.sightings,
+                    }),
+            );
+        }
+        findings.sort_by_key(|f| {
+            let location = &f.beacon.location;
+            (
+                location.building.as_ref(),
+                location.floor,
+                location.room,
+                f.beacon.id.major,
+                f.beacon.id.minor,
+            )
+        });
+
+        AuditReport {
+            observations: observations.len(),
+            beacons_seen: evidence.values().filter(|e| e.sightings > 0).count(),
+            findings,
+        }
+    }
+
+    fn check(&self, beacon: &'static Beacon, evidence: &BeaconEvidence) -> Vec<Issue> {
+        let config = &self.config;
+        let mut issues = Vec::new();
+
+        if evidence.opportunities >= config.min_opportunities.max(1)
+            && evidence.detected as f64 <= config.max_detection_rate * evidence.opportunities as f64
+        {
+            issues.push(Issue::Missing {
+                opportunities: evidence.opportunities,
+                detected: evidence.detected,
+            });
+        }
+
+        if evidence.sightings < config.min_sightings.max(1) {
+            return issues;
+        }
+
+        let apparent = Position {
+            lat: evidence.lat / evidence.weight,
+            lon: evidence.lon / evidence.weight,
+        };
+        let displacement = apparent.distance(&beacon.position);
+        let moved = displacement > config.max_displacement;
+        if moved {
+            issues.push(Issue::Moved {
+                apparent,
+                displacement,
+            });
+        }
+
+        let total: f64 = evidence.floors.values().sum();
+        let loudest = evidence.floors.iter().max_by(|a, b| {
+            a.1.total_cmp(b.1)
+                .then_with(|| (b.0 .0.as_ref(), b.0 .1).cmp(&(a.0 .0.as_ref(), a.0 .1)))
+        });
+        if let Some((&(building, floor), &weight)) = loudest {
+            let share = weight / total;
+            let location = &beacon.location;
+            if (building, floor) != (location.building, location.floor)
+                && share >= config.floor_share
+            {
+                issues.push(Issue::WrongFloor {
+                    building,
+                    floor,
+                    share,
+                });
+            }
+        }
+
+        // The model offset is meaningless if the beacon is not where the dataset says
+        if !moved {
+            let offset = median(&evidence.offsets);
+            if offset.abs() > config.max_rssi_offset {
+                issues.push(Issue::Misconfigured { offset });
+            }
+        }
+
+        issues
+    }
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let middle = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        sorted[middle]
+    } else {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    }
+}
+
+/// Beacons sorted by latitude, to find beacons near a position without comparing all.
+struct BeaconsByLatitude {
+    beacons: Vec<&'static Beacon>,
+}
+
+impl BeaconsByLatitude {
+    fn new(beacons: impl IntoIterator<Item = &'static Beacon>) -> Self {
+        let mut beacons: Vec<&'static Beacon> = beacons.into_iter().collect();
+        beacons.sort_by(|a, b| a.position.lat.total_cmp(&b.position.lat));
+        BeaconsByLatitude { beacons }
+    }
+
+    fn near<'a>(
+        &'a self,
+        position: &'a Position,
+        range: f64,
+    ) -> impl Iterator<Item = &'static Beacon> + 'a {
+        let band = range / METRES_PER_DEGREE;
+        let start = self
+            .beacons
+            .partition_point(|b| b.position.lat < position.lat - band);
+        self.beacons[start..]
+            .iter()
+            .take_while(move |b| b.position.lat <= position.lat + band)
+            .copied()
+            .filter(move |b| b.position.distance(position) <= range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sighting::Sighting;
+    use crate::testing::{beacon, offset, scan, sighting, square};
+
+    fn observation(
+        position: Position,
+        floor: Option<(Building, &'static str)>,
+        sightings: Vec<Sighting>,
+    ) -> Observation {
+        Observation {
+            timestamp: 0,
+            position,
+            accuracy: 0.0,
+            floor,
+            sightings,
+        }
+    }
+
+    #[test]
+    fn reports_beacons_heard_elsewhere_as_moved() {
+        let moved = beacon(1, Building::HG, "E", "1", offset(0.0, 0.0));
+        let observations: Vec<Observation> = (0..10)
+            .map(|i| {
+                let position = offset(40.0, i as f64);
+                observation(
+                    position,
+                    Some((Building::HG, "E")),
+                    scan(&[moved], &position, 0),
+                )
+            })
+            .collect();
+
+        let report =
+            Auditor::default().audit_against(&observations, BeaconsByLatitude::new([moved]));
+        assert_eq!(report.beacons_seen, 1);
+        assert_eq!(report.findings.len(), 1);
+        assert!(matches!(
+            report.findings[0].issue,
+            Issue::Moved { displacement, .. } if displacement > 35.0
+        ));
+    }
+
+    #[test]
+    fn reports_beacons_nobody_hears_as_missing() {
+        let beacons = square();
+        let heard = &beacons[1..];
+        let observations: Vec<Observation> = (0..10)
+            .map(|_| {
+                observation(
+                    offset(2.0, 2.0),
+                    Some((Building::HG, "E")),
+                    scan(heard, &offset(2.0, 2.0), 0),
+                )
+            })
+            .collect();
+
+        let auditor = Auditor::default();
+        let report = auditor.audit_against(&observations, BeaconsByLatitude::new(beacons.clone()));
+        let missing: Vec<u16> = report
+            .findings
+            .iter()
+            .filter(|f| matches!(f.issue, Issue::Missing { .. }))
+            .map(|f| f.beacon.id.minor)
+            .collect();
+        assert_eq!(missing, [1]);
+    }
+
+    #[test]
+    fn needs_the_floor_to_report_missing_beacons() {
+        let beacons = square();
+        let other_floor = beacon(5, Building::HG, "F", "1", offset(2.0, 2.0));
+        let observations: Vec<Observation> = (0..10)
+            .map(|_| {
+                observation(
+                    offset(2.0, 2.0),
+                    None,
+                    vec![sighting(other_floor, -50.0, 0)],
+                )
+            })
+            .collect();
+
+        let report = Auditor::default()
+            .audit_against(&observations, BeaconsByLatitude::new(beacons.clone()));
+        assert!(report
+            .findings
+            .iter()
+            .all(|f| !matches!(f.issue, Issue::Missing { .. })));
+    }
+}
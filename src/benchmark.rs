@@ -0,0 +1,70 @@
+use crate::estimator::PositionEstimator;
+use crate::scanlog::Observation;
+
+/// Errors of an estimator on recorded observations, in metres.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Evaluation {
+    pub observations: usize,
+    /// Number of observations the estimator produced an estimate for.
+    pub fixes: usize,
+    pub mean_error: f64,
+    pub median_error: f64,
+    pub p90_error: f64,
+    /// Mean accuracy the estimator claimed.
+    pub mean_accuracy: f64,
+    /// Share of estimates with the floor of the observation, `None` if no estimate had both a
+    /// location and a known floor to compare with.
+    pub floor_rate: Option<f64>,
+}
+
+/// Runs `estimator` on every observation and compares the estimates with the observed
+/// positions, which should be ground truth.
+pub fn evaluate<E: PositionEstimator + ?Sized>(
+    estimator: &E,
+    observations: &[Observation],
+) -> Evaluation {
+    let mut errors = Vec::new();
+    let mut accuracy = 0.0;
+    let (mut floors, mut floor_hits) = (0, 0);
+
+    for observation in observations {
+        let Some(estimate) = estimator.estimate(&observation.sightings) else {
+            continue;
+        };
+        errors.push(estimate.position.distance(&observation.position));
+        accuracy += estimate.accuracy;
+
+        if let (Some((building, floor)), Some(location)) = (observation.floor, estimate.location) {
+            floors += 1;
+            if location.building == building && location.floor == floor {
+                floor_hits += 1;
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        return Evaluation {
+            observations: observations.len(),
+            ..Default::default()
+        };
+    }
+    errors.sort_by(f64::total_cmp);
+    let fixes = errors.len();
+
+    Evaluation {
+        observations: observations.len(),
+        fixes,
+        mean_error: errors.iter().sum::<f64>() / fixes as f64,
+        median_error: percentile(&errors, 0.5),
+        p90_error: percentile(&errors, 0.9),
+        mean_accuracy: accuracy / fixes as f64,
+        floor_rate: (floors > 0).then(|| floor_hits as f64 / floors as f64),
+    }
+}
+
+/// Linearly interpolated percentile of sorted, non-empty `values`, `p` between 0 and 1.
+pub fn percentile(values: &[f64], p: f64) -> f64 {
+    let rank = p * (values.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    values[lower] + (values[upper] - values[lower]) * (rank - lower as f64)
+}
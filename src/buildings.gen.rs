@@ -1,7 +1,7 @@
 
-use strum_macros::AsRefStr;
+use strum_macros::{AsRefStr, EnumString};
 
-#[derive(Debug, Clone, AsRefStr)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsRefStr, EnumString)]
 pub enum Building {
     AGS,
     BEA,
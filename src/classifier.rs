@@ -0,0 +1,223 @@
+use crate::sighting::Sighting;
+use crate::{Id, Location};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct RoomClassifierConfig {
+    /// Sightings at or below this RSSI in dBm are ignored.
+    pub min_rssi: f64,
+    /// Softness of the ranking in dB. A room whose strongest beacon is this much weaker than
+    /// the overall strongest beacon gets `1/e` of its weight.
+    pub temperature: f64,
+    /// Rooms within this probability of the best room are reported as tied.
+    pub tie_margin: f64,
+}
+
+impl Default for RoomClassifierConfig {
+    fn default() -> Self {
+        RoomClassifierConfig {
+            min_rssi: -100.0,
+            temperature: 4.0,
+            tie_margin: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoomCandidate {
+    pub location: Location,
+    /// Probability of being in this room, all candidates sum up to one.
+    pub probability: f64,
+    /// Number of distinct beacons of this room that were sighted.
+    pub beacons: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RoomClassification {
+    /// Candidate rooms, most likely first.
+    pub candidates: Vec<RoomCandidate>,
+    /// Number of leading candidates within the tie margin of the best one, at least one if
+    /// there are any candidates.
+    pub tied: usize,
+}
+
+impl RoomClassification {
+    pub fn best(&self) -> Option<&RoomCandidate> {
+        self.candidates.first()
+    }
+
+    /// Whether the best room could not be told apart from the next one.
+    pub fn is_tie(&self) -> bool {
+        self.tied > 1
+    }
+
+    /// The best room and all rooms tied with it.
+    pub fn ties(&self) -> &[RoomCandidate] {
+        &self.candidates[..self.tied]
+    }
+}
+
+/// Ranks the rooms of the sighted beacons.
+///
+/// The RSSI of every beacon is averaged over the given sightings. Each room is weighted by
+/// `exp((rssi - strongest) / temperature)` summed over its beacons, so rooms with several
+/// strong beacons win over rooms with a single one.
+#[derive(Debug, Clone, Default)]
+pub struct RoomClassifier {
+    config: RoomClassifierConfig,
+}
+
+impl RoomClassifier {
+    pub fn new(config: RoomClassifierConfig) -> Self {
+        RoomClassifier { config }
+    }
+
+    pub fn classify(&self, sightings: &[Sighting]) -> RoomClassification {
+        let mut per_beacon: HashMap<Id, (Location, f64, usize)> = HashMap::new();
+        for sighting in sightings.iter().filter(|s| s.rssi > self.config.min_rssi) {
+            let entry =
+                per_beacon
+                    .entry(sighting.beacon.id)
+                    .or_insert((sighting.beacon.location, 0.0, 0));
+            entry.1 += sighting.rssi;
+            entry.2 += 1;
+        }
+
+        let mean_rssi: Vec<(Location, f64)> = per_beacon
+            .into_values()
+            .map(|(location, sum, count)| (location, sum / count as f64))
+            .collect();
+        let Some(strongest) = mean_rssi.iter().map(|(_, rssi)| *rssi).reduce(f64::max) else {
+            return RoomClassification::default();
+        };
+
+        let mut rooms: HashMap<Location, (f64, usize)> = HashMap::new();
+        for (location, rssi) in mean_rssi {
+            let room = rooms.entry(location).or_default();
+            room.0 += ((rssi - strongest) / self.config.temperature).exp();
+            room.1 += 1;
+        }
+
+        let total: f64 = rooms.values().map(|(weight, _)| weight).sum();
+        let mut candidates: Vec<RoomCandidate> = rooms
+            .into_iter()
+            .map(|(location, (weight, beacons))| RoomCandidate {
+                location,
+                probability: weight / total,
+                beacons,
+            })
+            .collect();
+        candidates.sort_by(|a, b| {
+            b.probability
+                .total_cmp(&a.probability)
+                .then_with(|| {
+                    a.location
+                        .building
+                        .as_ref()
+                        .cmp(b.location.building.as_ref())
+                })
+                .then_with(|| a.location.floor.cmp(b.location.floor))
+                .then_with(|| a.location.room.cmp(b.location.room))
+        });
+
+        let best = candidates[0].probability;
+        let tied = candidates
+            .iter()
+            .take_while(|c| best - c.probability <= self.config.tie_margin)
+            .count();
+
+        RoomClassification { candidates, tied }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{beacon, offset, sighting};
+    use crate::Building;
+
+    fn rooms(classification: &RoomClassification) -> Vec<(Building, &'static str)> {
+        classification
+            .candidates
+            .iter()
+            .map(|c| (c.location.building, c.location.room))
+            .collect()
+    }
+
+    #[test]
+    fn ranks_rooms_with_several_strong_beacons_first() {
+        let position = offset(0.0, 0.0);
+        let sightings = [
+            sighting(beacon(1, Building::HG, "E", "1", position), -62.0, 0),
+            sighting(beacon(2, Building::HG, "E", "1", position), -64.0, 0),
+            sighting(beacon(3, Building::HG, "E", "2", position), -61.0, 0),
+            sighting(beacon(4, Building::HG, "E", "3", position), -80.0, 0),
+            sighting(beacon(4, Building::HG, "E", "3", position), -70.0, 1_000),
+            sighting(beacon(5, Building::HG, "E", "4", position), -100.0, 0),
+        ];
+        let classification = RoomClassifier::default().classify(&sightings);
+
+        // Room 4 is below the minimum RSSI, room 3 is averaged to -75 dBm
+        assert_eq!(
+            rooms(&classification),
+            [
+                (Building::HG, "1"),
+                (Building::HG, "2"),
+                (Building::HG, "3")
+            ]
+        );
+        assert_eq!(classification.best().unwrap().beacons, 2);
+        let total: f64 = classification
+            .candidates
+            .iter()
+            .map(|c| c.probability)
+            .sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        let weight = |rssi: f64| ((rssi + 61.0) / 4.0).exp();
+        let expected = weight(-75.0) / (weight(-62.0) + weight(-64.0) + 1.0 + weight(-75.0));
+        assert!((classification.candidates[2].probability - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn counts_the_rooms_within_the_tie_margin() {
+        let position = offset(0.0, 0.0);
+        let sightings = [
+            sighting(beacon(1, Building::HG, "E", "1", position), -70.0, 0),
+            sighting(beacon(2, Building::HG, "E", "2", position), -70.5, 0),
+            sighting(beacon(3, Building::HG, "E", "3", position), -78.0, 0),
+        ];
+        let classification = RoomClassifier::default().classify(&sightings);
+        assert_eq!(classification.tied, 2);
+        assert!(classification.is_tie());
+        assert_eq!(classification.ties().len(), 2);
+
+        let strict = RoomClassifier::new(RoomClassifierConfig {
+            tie_margin: 0.0,
+            ..Default::default()
+        });
+        assert_eq!(strict.classify(&sightings).tied, 1);
+        assert_eq!(RoomClassifier::default().classify(&[]).tied, 0);
+    }
+
+    #[test]
+    fn breaks_ties_by_building() {
+        let position = offset(0.0, 0.0);
+        let sightings = [
+            sighting(beacon(1, Building::ML, "E", "1", position), -70.0, 0),
+            sighting(beacon(2, Building::HG, "E", "1", position), -70.0, 0),
+            sighting(beacon(3, Building::CAB, "E", "1", position), -70.0, 0),
+        ];
+        for _ in 0..10 {
+            let classification = RoomClassifier::default().classify(&sightings);
+            assert_eq!(
+                rooms(&classification),
+                [
+                    (Building::CAB, "1"),
+                    (Building::HG, "1"),
+                    (Building::ML, "1")
+                ]
+            );
+            assert_eq!(classification.tied, 3);
+        }
+    }
+}
@@ -0,0 +1,593 @@
+use crate::floor::level;
+use crate::geo::polygon_contains;
+use crate::radio::PathLossModel;
+use crate::swiss::Lv95;
+use crate::{Building, Position, BEACONS};
+use std::collections::VecDeque;
+use std::f64::consts::LN_10;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy)]
+pub struct CoverageConfig {
+    /// Edge length of a cell in metres.
+    pub cell_size: f64,
+    /// Radio model for the range of a beacon and the error of ranging to it.
+    pub path_loss: PathLossModel,
+    /// Beacons are in range while their expected RSSI is above this, in dBm.
+    pub min_rssi: f64,
+    /// Cells with fewer beacons in range are uncovered, 3 are needed for a position fix.
+    pub min_beacons: usize,
+}
+
+impl Default for CoverageConfig {
+    fn default() -> Self {
+        CoverageConfig {
+            cell_size: 2.0,
+            path_loss: PathLossModel::default(),
+            min_rssi: -90.0,
+            min_beacons: 3,
+        }
+    }
+}
+
+impl CoverageConfig {
+    /// Distance in metres up to which beacons are in range.
+    pub fn range(&self) -> f64 {
+        self.path_loss.distance(self.min_rssi)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoverageCell {
+    /// Centre of the cell.
+    pub position: Position,
+    /// Beacons in range.
+    pub beacons: usize,
+    /// Geometric dilution of precision of ranging to the beacons in range, `None` with fewer
+    /// than 3 beacons or when they are in a line.
+    pub gdop: Option<f64>,
+    /// Expected positioning error in metres, from the dilution and the ranging error of the
+    /// radio model, which grows with the distance to the beacons.
+    pub expected_error: Option<f64>,
+}
+
+/// Value of a cell to rasterise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverageValue {
+    Beacons,
+    Gdop,
+    ExpectedError,
+}
+
+/// Connected area of uncovered cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gap {
+    pub cells: usize,
+    /// Area in square metres.
+    pub area: f64,
+    /// Mean position of the cells.
+    pub centroid: Position,
+}
+
+/// Grid of square cells over the footprint of a building on one floor.
+///
+/// The grid is aligned with the Swiss LV95 axes, so it can be loaded into GIS tools as a
+/// raster without resampling. Rows count from the south.
+#[derive(Debug, Clone)]
+pub struct FloorCoverage {
+    pub building: Building,
+    pub floor: &'static str,
+    pub cell_size: f64,
+    /// LV95 easting of the west edge of the grid.
+    pub east: f64,
+    /// LV95 northing of the south edge of the grid.
+    pub north: f64,
+    pub columns: usize,
+    pub rows: usize,
+    /// Cells row by row, `None` outside the footprint.
+    pub cells: Vec<Option<CoverageCell>>,
+    min_beacons: usize,
+}
+
+/// Computes the coverage of floors by the [`BEACONS`] or by planned beacons.
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    config: CoverageConfig,
+}
+
+impl Coverage {
+    pub fn new(config: CoverageConfig) -> Self {
+        Coverage { config }
+    }
+
+    /// Coverage of every floor of `building` with beacons, from the lowest floor up.
+    pub fn building(&self, building: Building) -> Vec<FloorCoverage> {
+        let mut floors: Vec<&'static str> = BEACONS
+            .iter()
+            .filter(|b| b.location.building == building)
+            .map(|b| b.location.floor)
+            .collect();
+        floors.sort_by(|a, b| {
+            let (x, y) = (level(a).unwrap_or(f64::MIN), level(b).unwrap_or(f64::MIN));
+            x.total_cmp(&y).then(a.cmp(b))
+        });
+        floors.dedup();
+        floors
+            .into_iter()
+            .filter_map(|floor| self.floor(building, floor))
+            .collect()
+    }
+
+    /// Coverage of a floor by its beacons, `None` if the building has no footprint.
+    pub fn floor(&self, building: Building, floor: &'static str) -> Option<FloorCoverage> {
+        let beacons: Vec<Position> = BEACONS
+            .iter()
+            .filter(|b| b.location.building == building && b.location.floor == floor)
+            .map(|b| b.position)
+            .collect();
+        self.floor_with_beacons(building, floor, &beacons)
+    }
+
+    /// Coverage of a floor by beacons at `beacons`, e.g. the existing ones and planned ones.
+    pub fn floor_with_beacons(
+        &self,
+        building: Building,
+        floor: &'static str,
+        beacons: &[Position],
+    ) -> Option<FloorCoverage> {
+        let footprint = building.footprint()?;
+        Some(self.floor_in(building, floor, footprint, beacons))
+    }
+
+    /// Coverage of a floor within `footprint` rather than the footprint of the building.
+    pub(crate) fn floor_in(
+        &self,
+        building: Building,
+        floor: &'static str,
+        footprint: &[Position],
+        beacons: &[Position],
+    ) -> FloorCoverage {
+        let size = self.config.cell_size.max(0.1);
+        let corners: Vec<Lv95> = footprint.iter().map(Position::to_lv95).collect();
+        let min_east = corners.iter().map(|c| c.east).fold(f64::INFINITY, f64::min);
+        let max_east = corners
+            .iter()
+            .map(|c| c.east)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let min_north = corners
+            .iter()
+            .map(|c| c.north)
+            .fold(f64::INFINITY, f64::min);
+        let max_north = corners
+            .iter()
+            .map(|c| c.north)
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        // Snap the grid to multiples of the cell size, so grids of different floors line up
+        let east = (min_east / size).floor() * size;
+        let north = (min_north / size).floor() * size;
+        let columns = ((max_east - east) / size).ceil() as usize;
+        let rows = ((max_north - north) / size).ceil() as usize;
+
+        let mut coverage = FloorCoverage {
+            building,
+            floor,
+            cell_size: size,
+            east,
+            north,
+            columns,
+            rows,
+            cells: Vec::with_capacity(columns * rows),
+            min_beacons: self.config.min_beacons,
+        };
+        let beacons: Vec<Lv95> = beacons.iter().map(Position::to_lv95).collect();
+        for row in 0..rows {
+            for column in 0..columns {
+                let centre = coverage.centre(column, row);
+                let position = Position::from(centre);
+                let cell = polygon_contains(footprint, &position)
+                    .then(|| self.cell(position, &centre, &beacons));
+                coverage.cells.push(cell);
+            }
+        }
+        coverage
+    }
+
+    fn cell(&self, position: Position, centre: &Lv95, beacons: &[Lv95]) -> CoverageCell {
+        let mut sums = CellSums::default();
+        for beacon in beacons {
+            sums.add(&self.config, centre, beacon);
+        }
+        sums.cell(position)
+    }
+}
+
+/// Sums over the beacons in range of a cell, so beacons can be added one at a time.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct CellSums {
+    count: usize,
+    // Upper triangle of the normal matrix, plain and weighted by the ranging error
+    plain: [f64; 3],
+    weighted: [f64; 3],
+}
+
+impl CellSums {
+    /// Adds `beacon` if it is in range of the cell at `centre`.
+    pub(crate) fn add(&mut self, config: &CoverageConfig, centre: &Lv95, beacon: &Lv95) {
+        let (dx, dy) = (beacon.east - centre.east, beacon.north - centre.north);
+        let distance = dx.hypot(dy);
+        if distance > config.range() {
+            return;
+        }
+        self.count += 1;
+        if distance < 1e-3 {
+            return;
+        }
+        let model = &config.path_loss;
+        // Error of a range estimate per metre of distance, from the RSSI noise
+        let error_per_metre = LN_10 * model.sigma / (10.0 * model.exponent);
+        let (ux, uy) = (dx / distance, dy / distance);
+        let weight = 1.0 / (error_per_metre * distance.max(1.0)).powi(2);
+        for (matrix, w) in [(&mut self.plain, 1.0), (&mut self.weighted, weight)] {
+            matrix[0] += w * ux * ux;
+            matrix[1] += w * ux * uy;
+            matrix[2] += w * uy * uy;
+        }
+    }
+
+    pub(crate) fn cell(&self, position: Position) -> CoverageCell {
+        let dilution = |m: [f64; 3]| {
+            let determinant = m[0] * m[2] - m[1] * m[1];
+            // Trace of the inverse of a 2x2 matrix
+            (self.count >= 3 && determinant > 1e-9).then(|| ((m[0] + m[2]) / determinant).sqrt())
+        };
+        CoverageCell {
+            position,
+            beacons: self.count,
+            gdop: dilution(self.plain),
+            expected_error: dilution(self.weighted),
+        }
+    }
+}
+
+impl FloorCoverage {
+    /// Centre of a cell in LV95.
+    pub fn centre(&self, column: usize, row: usize) -> Lv95 {
+        Lv95 {
+            east: self.east + (column as f64 + 0.5) * self.cell_size,
+            north: self.north + (row as f64 + 0.5) * self.cell_size,
+            height: None,
+        }
+    }
+
+    pub fn cell(&self, column: usize, row: usize) -> Option<&CoverageCell> {
+        self.cells.get(row * self.columns + column)?.as_ref()
+    }
+
+    /// Whether a cell has too few beacons in range for a position fix.
+    pub fn is_uncovered(&self, cell: &CoverageCell) -> bool {
+        cell.beacons < self.min_beacons
+    }
+
+    /// Cells inside the footprint.
+    pub fn inside(&self) -> impl Iterator<Item = &CoverageCell> {
+        self.cells.iter().flatten()
+    }
+
+    /// Share of the cells inside the footprint that are covered.
+    pub fn covered_share(&self) -> f64 {
+        let (mut inside, mut covered) = (0, 0);
+        for cell in self.inside() {
+            inside += 1;
+            if !self.is_uncovered(cell) {
+                covered += 1;
+            }
+        }
+        if inside == 0 {
+            return 0.0;
+        }
+        covered as f64 / inside as f64
+    }
+
+    /// Mean expected error of the cells with a position fix.
+    pub fn mean_error(&self) -> Option<f64> {
+        let errors: Vec<f64> = self.inside().filter_map(|c| c.expected_error).collect();
+        (!errors.is_empty()).then(|| errors.iter().sum::<f64>() / errors.len() as f64)
+    }
+
+    /// Connected areas of uncovered cells, the largest first.
+    pub fn gaps(&self) -> Vec<Gap> {
+        let uncovered = |index: usize| {
+            self.cells[index]
+                .as_ref()
+                .is_some_and(|cell| self.is_uncovered(cell))
+        };
+        let mut seen = vec![false; self.cells.len()];
+        let mut gaps = Vec::new();
+        for start in 0..self.cells.len() {
+            if seen[start] || !uncovered(start) {
+                continue;
+            }
+            seen[start] = true;
+            let mut queue = VecDeque::from([start]);
+            let (mut cells, mut lat, mut lon) = (0, 0.0, 0.0);
+            while let Some(index) = queue.pop_front() {
+                if let Some(cell) = &self.cells[index] {
+                    cells += 1;
+                    lat += cell.position.lat;
+                    lon += cell.position.lon;
+                }
+                let (column, row) = (index % self.columns, index / self.columns);
+                let mut neighbours = Vec::with_capacity(4);
+                if column > 0 {
+                    neighbours.push(index - 1);
+                }
+                if column + 1 < self.columns {
+                    neighbours.push(index + 1);
+                }
+                if row > 0 {
+                    neighbours.push(index - self.columns);
+                }
+                if row + 1 < self.rows {
+                    neighbours.push(index + self.columns);
+                }
+                for next in neighbours {
+                    if !seen[next] && uncovered(next) {
+                        seen[next] = true;
+                        queue.push_back(next);
+                    }
+                }
+            }
+            gaps.push(Gap {
+                cells,
+                area: cells as f64 * self.cell_size * self.cell_size,
+                centroid: Position {
+                    lat: lat / cells as f64,
+                    lon: lon / cells as f64,
+                },
+            });
+        }
+        gaps.sort_by(|a, b| b.area.total_cmp(&a.area));
+        gaps
+    }
+
+    /// Corners of a cell in WGS84, counter-clockwise from the south-west.
+    pub fn cell_polygon(&self, column: usize, row: usize) -> [Position; 4] {
+        let corner = |c: usize, r: usize| {
+            Position::from(Lv95 {
+                east: self.east + c as f64 * self.cell_size,
+                north: self.north + r as f64 * self.cell_size,
+                height: None,
+            })
+        };
+        [
+            corner(column, row),
+            corner(column + 1, row),
+            corner(column + 1, row + 1),
+            corner(column, row + 1),
+        ]
+    }
+
+    /// Writes the grid as an ESRI ASCII raster in LV95 coordinates, with `-9999` outside the
+    /// footprint and where the value is undefined.
+    pub fn write_ascii_grid<W: Write>(
+        &self,
+        writer: &mut W,
+        value: CoverageValue,
+    ) -> io::Result<()> {
+        writeln!(writer, "ncols {}", self.columns)?;
+        writeln!(writer, "nrows {}", self.rows)?;
+        writeln!(writer, "xllcorner {}", self.east)?;
+        writeln!(writer, "yllcorner {}", self.north)?;
+        writeln!(writer, "cellsize {}", self.cell_size)?;
+        writeln!(writer, "NODATA_value -9999")?;
+        // Rasters list the northernmost row first
+        for row in (0..self.rows).rev() {
+            let line: Vec<String> = (0..self.columns)
+                .map(|column| {
+                    let cell = self.cell(column, row);
+                    let value = cell.and_then(|cell| match value {
+                        CoverageValue::Beacons => Some(cell.beacons as f64),
+                        CoverageValue::Gdop => cell.gdop,
+                        CoverageValue::ExpectedError => cell.expected_error,
+                    });
+                    match value {
+                        Some(value) => format!("{}", (value * 100.0).round() / 100.0),
+                        None => "-9999".to_string(),
+                    }
+                })
+                .collect();
+            writeln!(writer, "{}", line.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the cells of `floors` inside their footprints as a GeoJSON feature collection of
+/// polygons, with building, floor, beacons, gdop and expected error as properties.
+pub fn write_geojson<W: Write>(floors: &[FloorCoverage], writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "{{\"type\":\"FeatureCollection\",\"features\":[")?;
+    let mut first = true;
+    for floor in floors {
+        for row in 0..floor.rows {
+            for column in 0..floor.columns {
+                let Some(cell) = floor.cell(column, row) else {
+                    continue;
+                };
+                let polygon = floor.cell_polygon(column, row);
+                let ring: Vec<String> = polygon
+                    .iter()
+                    .chain(&polygon[..1])
+                    .map(|p| format!("[{},{}]", p.lon, p.lat))
+                    .collect();
+                let optional = |value: Option<f64>| match value {
+                    Some(value) => format!("{:.2}", value),
+                    None => "null".to_string(),
+                };
+                if !first {
+                    writeln!(writer, ",")?;
+                }
+                first = false;
+                write!(
+                    writer,
+                    "{{\"type\":\"Feature\",\"geometry\":{{\"type\":\"Polygon\",\"coordinates\":[[{}]]}},\
+                     \"properties\":{{\"building\":\"{}\",\"floor\":\"{}\",\"beacons\":{},\"gdop\":{},\
+                     \"expected_error\":{},\"uncovered\":{}}}}}",
+                    ring.join(","),
+                    floor.building.as_ref(),
+                    floor.floor,
+                    cell.beacons,
+                    optional(cell.gdop),
+                    optional(cell.expected_error),
+                    floor.is_uncovered(cell)
+                )?;
+            }
+        }
+    }
+    writeln!(writer, "\n]}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Position `east` and `north` metres from a point on the LV95 grid of 5 metre cells.
+    fn lv95(east: f64, north: f64) -> Position {
+        Position::from(Lv95 {
+            east: 2_683_000.0 + east,
+            north: 1_247_000.0 + north,
+            height: None,
+        })
+    }
+
+    /// Beacons are in range up to 10 metres.
+    fn config() -> CoverageConfig {
+        let path_loss = PathLossModel::default();
+        CoverageConfig {
+            cell_size: 5.0,
+            path_loss,
+            min_rssi: path_loss.rssi(10.0),
+            min_beacons: 3,
+        }
+    }
+
+    /// A 20 by 10 metre floor, 4 by 2 cells, with its north-east corner cut off, and three
+    /// beacons at its west end.
+    fn floor() -> FloorCoverage {
+        let footprint = [
+            lv95(0.01, 0.01),
+            lv95(19.99, 0.01),
+            lv95(14.99, 9.99),
+            lv95(0.01, 9.99),
+        ];
+        let beacons = [lv95(0.0, 0.0), lv95(0.0, 10.0), lv95(10.0, 5.0)];
+        Coverage::new(config()).floor_in(Building::HG, "E", &footprint, &beacons)
+    }
+
+    fn sums(beacons: &[(f64, f64)]) -> CoverageCell {
+        let centre = lv95(0.0, 0.0).to_lv95();
+        let mut sums = CellSums::default();
+        for &(east, north) in beacons {
+            sums.add(&config(), &centre, &lv95(east, north).to_lv95());
+        }
+        sums.cell(lv95(0.0, 0.0))
+    }
+
+    #[test]
+    fn dilutes_by_the_beacon_geometry() {
+        // Four beacons around the cell at equal distance are the best geometry
+        let cell = sums(&[(8.0, 0.0), (0.0, 8.0), (-8.0, 0.0), (0.0, -8.0)]);
+        assert_eq!(cell.beacons, 4);
+        assert!((cell.gdop.unwrap() - 1.0).abs() < 1e-6);
+        // The ranging error grows with the distance, at the same rate for all four beacons
+        let model = config().path_loss;
+        let error = 8.0 * LN_10 * model.sigma / (10.0 * model.exponent);
+        assert!((cell.expected_error.unwrap() - error).abs() < 1e-6);
+
+        // Beacons in a line cannot fix a position, beacons out of range do not count
+        let line = sums(&[(-8.0, 0.0), (4.0, 0.0), (8.0, 0.0), (0.0, 12.0)]);
+        assert_eq!(line.beacons, 3);
+        assert_eq!((line.gdop, line.expected_error), (None, None));
+    }
+
+    #[test]
+    fn covers_the_cells_near_enough_beacons() {
+        let floor = floor();
+        assert_eq!((floor.columns, floor.rows), (4, 2));
+        assert_eq!((floor.east, floor.north), (2_683_000.0, 1_247_000.0));
+
+        let beacons: Vec<Option<usize>> = (0..floor.columns)
+            .map(|column| floor.cell(column, 1).map(|c| c.beacons))
+            .collect();
+        assert_eq!(beacons, [Some(3), Some(2), Some(1), None]);
+        assert_eq!(floor.inside().count(), 7);
+        assert!((floor.covered_share() - 2.0 / 7.0).abs() < 1e-9);
+        assert!(floor.cell(0, 0).unwrap().gdop.is_some());
+        assert!(floor.cell(1, 0).unwrap().gdop.is_none());
+    }
+
+    #[test]
+    fn finds_connected_gaps() {
+        let gaps = floor().gaps();
+        assert_eq!(gaps.len(), 1);
+        let gap = gaps[0];
+        assert_eq!(gap.cells, 5);
+        assert!((gap.area - 125.0).abs() < 1e-9);
+        // Mean of the centres of the five eastern cells
+        assert!(gap.centroid.distance(&lv95(11.5, 4.5)) < 0.01);
+    }
+
+    #[test]
+    fn writes_an_ascii_grid() {
+        let mut grid = Vec::new();
+        floor()
+            .write_ascii_grid(&mut grid, CoverageValue::Beacons)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(grid).unwrap(),
+            "ncols 4\n\
+             nrows 2\n\
+             xllcorner 2683000\n\
+             yllcorner 1247000\n\
+             cellsize 5\n\
+             NODATA_value -9999\n\
+             3 2 1 -9999\n\
+             3 2 1 1\n"
+        );
+
+        let mut grid = Vec::new();
+        floor()
+            .write_ascii_grid(&mut grid, CoverageValue::Gdop)
+            .unwrap();
+        let grid = String::from_utf8(grid).unwrap();
+        let rows: Vec<&str> = grid.lines().skip(6).collect();
+        assert!(rows
+            .iter()
+            .all(|row| row.ends_with(" -9999 -9999 -9999") && !row.starts_with("-9999")));
+    }
+
+    #[test]
+    fn writes_a_feature_per_cell_inside() {
+        let floors = [floor(), floor()];
+        let mut geojson = Vec::new();
+        write_geojson(&floors, &mut geojson).unwrap();
+
+        let geojson: serde_json::Value = serde_json::from_slice(&geojson).unwrap();
+        assert_eq!(geojson["type"], "FeatureCollection");
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 14);
+
+        let first = &features[0];
+        assert_eq!(first["geometry"]["type"], "Polygon");
+        let ring = first["geometry"]["coordinates"][0].as_array().unwrap();
+        assert_eq!(ring.len(), 5);
+        assert_eq!(ring[0], ring[4]);
+        let properties = &first["properties"];
+        assert_eq!(properties["building"], "HG");
+        assert_eq!(properties["floor"], "E");
+        assert_eq!(properties["beacons"], 3);
+        assert_eq!(properties["uncovered"], false);
+        assert!(properties["gdop"].is_number());
+        assert!(features[1]["properties"]["gdop"].is_null());
+    }
+}
@@ -0,0 +1,184 @@
+use crate::floor::level;
+use crate::{Beacon, Building, Location, Position, Position3D};
+use std::collections::HashMap;
+
+/// Heights of the floors of a building.
+///
+/// Floors are assumed to be [`FloorElevations::storey_height`] apart, counted from the ground
+/// floor with [`level`], unless listed in [`FloorElevations::floors`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloorElevations {
+    pub ground_floor: String,
+    /// Height of the ground floor in metres, above sea level for absolute altitudes. The
+    /// default of 0 measures altitudes from the ground floor instead, which is enough for
+    /// distances between floors.
+    pub ground_altitude: f64,
+    /// Height of one storey in metres.
+    pub storey_height: f64,
+    /// Altitudes of floors that do not follow the storey height, e.g. above high lecture halls.
+    pub floors: HashMap<String, f64>,
+}
+
+impl Default for FloorElevations {
+    fn default() -> Self {
+        FloorElevations {
+            ground_floor: "E".to_string(),
+            ground_altitude: 0.0,
+            storey_height: 3.5,
+            floors: HashMap::new(),
+        }
+    }
+}
+
+impl FloorElevations {
+    pub fn new(ground_floor: &str, ground_altitude: f64, storey_height: f64) -> Self {
+        FloorElevations {
+            ground_floor: ground_floor.to_string(),
+            ground_altitude,
+            storey_height,
+            floors: HashMap::new(),
+        }
+    }
+
+    /// Sets the altitude of a single floor.
+    pub fn with_floor(mut self, floor: &str, altitude: f64) -> Self {
+        self.floors.insert(floor.to_string(), altitude);
+        self
+    }
+
+    /// Altitude of `floor` in metres, `None` for floor codes [`level`] does not know.
+    pub fn altitude(&self, floor: &str) -> Option<f64> {
+        if let Some(altitude) = self.floors.get(floor) {
+            return Some(*altitude);
+        }
+        let storeys = level(floor)? - level(&self.ground_floor)?;
+        Some(self.ground_altitude + storeys * self.storey_height)
+    }
+}
+
+/// Floor elevations per building.
+///
+/// The dataset carries no heights, so buildings without an entry use
+/// [`ElevationTable::default_elevations`], which puts every ground floor at altitude 0, and
+/// their altitudes are heights above the ground floor rather than above sea level. Fill in the
+/// buildings you need absolute altitudes for.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ElevationTable {
+    pub default_elevations: FloorElevations,
+    pub buildings: HashMap<Building, FloorElevations>,
+}
+
+impl ElevationTable {
+    pub fn new() -> Self {
+        ElevationTable::default()
+    }
+
+    pub fn insert(&mut self, building: Building, elevations: FloorElevations) {
+        self.buildings.insert(building, elevations);
+    }
+
+    pub fn elevations(&self, building: Building) -> &FloorElevations {
+        self.buildings
+            .get(&building)
+            .unwrap_or(&self.default_elevations)
+    }
+
+    pub fn altitude(&self, building: Building, floor: &str) -> Option<f64> {
+        self.elevations(building).altitude(floor)
+    }
+
+    /// Altitude of the floor of `location`.
+    pub fn location_altitude(&self, location: &Location) -> Option<f64> {
+        self.altitude(location.building, location.floor)
+    }
+
+    /// Position of `beacon` at the altitude of its floor.
+    pub fn beacon_position(&self, beacon: &Beacon) -> Option<Position3D> {
+        let altitude = self.location_altitude(&beacon.location)?;
+        Some(beacon.position.with_altitude(altitude))
+    }
+}
+
+impl Position3D {
+    /// Straight-line distance to `other` in metres, from the great-circle distance and the
+    /// difference in altitude.
+    pub fn distance(&self, other: &Position3D) -> f64 {
+        self.horizontal()
+            .distance(&other.horizontal())
+            .hypot(self.altitude - other.altitude)
+    }
+
+    pub fn horizontal(&self) -> Position {
+        Position {
+            lat: self.lat,
+            lon: self.lon,
+        }
+    }
+}
+
+impl Position {
+    pub fn with_altitude(&self, altitude: f64) -> Position3D {
+        Position3D {
+            lat: self.lat,
+            lon: self.lon,
+            altitude,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{beacon, offset};
+
+    #[test]
+    fn stacks_floors_from_the_ground_floor() {
+        let elevations = FloorElevations::default();
+        assert_eq!(elevations.altitude("E"), Some(0.0));
+        assert_eq!(elevations.altitude("G"), Some(7.0));
+        assert_eq!(elevations.altitude("D"), Some(-3.5));
+        assert_eq!(elevations.altitude("Z"), Some(-5.0 * 3.5));
+        // Mezzanines lie half a storey above their base floor
+        assert_eq!(elevations.altitude("EO"), Some(1.75));
+        assert_eq!(elevations.altitude("Garage"), None);
+
+        let absolute = FloorElevations::new("F", 480.0, 4.0);
+        assert_eq!(absolute.altitude("F"), Some(480.0));
+        assert_eq!(absolute.altitude("H"), Some(488.0));
+        assert_eq!(absolute.altitude("EO"), Some(478.0));
+    }
+
+    #[test]
+    fn overrides_single_floors() {
+        let elevations = FloorElevations::default()
+            .with_floor("G", 9.0)
+            .with_floor("Garage", -6.0);
+        assert_eq!(elevations.altitude("G"), Some(9.0));
+        assert_eq!(elevations.altitude("Garage"), Some(-6.0));
+        // Other floors still follow the storey height
+        assert_eq!(elevations.altitude("H"), Some(10.5));
+    }
+
+    #[test]
+    fn looks_up_the_building_of_a_beacon() {
+        let mut table = ElevationTable::new();
+        table.insert(Building::ML, FloorElevations::new("E", 450.0, 4.0));
+
+        let hg = beacon(1, Building::HG, "F", "1", offset(0.0, 0.0));
+        let ml = beacon(2, Building::ML, "F", "1", offset(10.0, 0.0));
+        assert_eq!(table.beacon_position(hg).unwrap().altitude, 3.5);
+        assert_eq!(table.beacon_position(ml).unwrap().altitude, 454.0);
+        assert_eq!(table.altitude(Building::ML, "X1"), None);
+    }
+
+    #[test]
+    fn combines_horizontal_and_vertical_distances() {
+        let low = offset(0.0, 0.0).with_altitude(0.0);
+        let high = offset(3.0, 0.0).with_altitude(4.0);
+        assert!((low.distance(&high) - 5.0).abs() < 1e-3);
+        assert_eq!(high.horizontal(), offset(3.0, 0.0));
+
+        let above = offset(0.0, 0.0).with_altitude(7.0);
+        assert!((low.distance(&above) - 7.0).abs() < 1e-9);
+    }
+}
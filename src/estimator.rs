@@ -0,0 +1,393 @@
+use crate::elevation::ElevationTable;
+use crate::geo::{LocalFrame, Point};
+use crate::radio::PathLossModel;
+use crate::sighting::Sighting;
+use crate::{Location, Position};
+use strum_macros::{AsRefStr, EnumString};
+
+/// Result of a single position estimate.
+#[derive(Debug, Clone)]
+pub struct Estimate {
+    pub position: Position,
+    /// Expected error in metres.
+    pub accuracy: f64,
+    /// Location of the strongest sighted beacon, if any.
+    pub location: Option<Location>,
+}
+
+/// Estimates a position from a single scan of sightings.
+pub trait PositionEstimator {
+    fn estimate(&self, sightings: &[Sighting]) -> Option<Estimate>;
+}
+
+impl<E: PositionEstimator + ?Sized> PositionEstimator for Box<E> {
+    fn estimate(&self, sightings: &[Sighting]) -> Option<Estimate> {
+        (**self).estimate(sightings)
+    }
+}
+
+/// Built-in estimators, to choose one by name from a configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum EstimatorKind {
+    Strongest,
+    Centroid,
+    LeastSquares,
+    /// [`LeastSquares`] with the worst fitting beacons [`Trimmed`].
+    Trimmed,
+}
+
+impl EstimatorKind {
+    pub const ALL: [EstimatorKind; 4] = [
+        EstimatorKind::Strongest,
+        EstimatorKind::Centroid,
+        EstimatorKind::LeastSquares,
+        EstimatorKind::Trimmed,
+    ];
+
+    pub fn build(self, path_loss: PathLossModel) -> Box<dyn PositionEstimator> {
+        match self {
+            EstimatorKind::Strongest => Box::new(StrongestBeacon { path_loss }),
+            EstimatorKind::Centroid => Box::new(WeightedCentroid {
+                path_loss,
+                max_beacons: None,
+            }),
+            EstimatorKind::LeastSquares => Box::new(LeastSquares {
+                path_loss,
+                ..Default::default()
+            }),
+            EstimatorKind::Trimmed => Box::new(Trimmed {
+                path_loss,
+                ..Trimmed::new(LeastSquares {
+                    path_loss,
+                    ..Default::default()
+                })
+            }),
+        }
+    }
+}
+
+/// Position of the strongest sighted beacon.
+#[derive(Debug, Clone, Default)]
+pub struct StrongestBeacon {
+    pub path_loss: PathLossModel,
+}
+
+impl PositionEstimator for StrongestBeacon {
+    fn estimate(&self, sightings: &[Sighting]) -> Option<Estimate> {
+        let strongest = sightings.iter().max_by(|a, b| a.rssi.total_cmp(&b.rssi))?;
+        Some(Estimate {
+            position: strongest.beacon.position,
+            accuracy: self.path_loss.distance(strongest.rssi).max(1.0),
+            location: Some(strongest.beacon.location),
+        })
+    }
+}
+
+/// Centroid of the sighted beacons, weighted by the inverse square of the distance the
+/// [`PathLossModel`] derives from their RSSI.
+#[derive(Debug, Clone, Default)]
+pub struct WeightedCentroid {
+    pub path_loss: PathLossModel,
+    /// Only use this many of the strongest sightings, all if `None`.
+    pub max_beacons: Option<usize>,
+}
+
+impl PositionEstimator for WeightedCentroid {
+    fn estimate(&self, sightings: &[Sighting]) -> Option<Estimate> {
+        let strongest = strongest_first(sightings, self.max_beacons);
+        let first = strongest.first()?;
+
+        let weights: Vec<f64> = strongest
+            .iter()
+            .map(|s| self.path_loss.distance(s.rssi).max(0.5).powi(-2))
+            .collect();
+        let total: f64 = weights.iter().sum();
+
+        let position = Position {
+            lat: strongest
+                .iter()
+                .zip(&weights)
+                .map(|(s, w)| w * s.beacon.position.lat)
+                .sum::<f64>()
+                / total,
+            lon: strongest
+                .iter()
+                .zip(&weights)
+                .map(|(s, w)| w * s.beacon.position.lon)
+                .sum::<f64>()
+                / total,
+        };
+
+        // Weighted spread of the beacons around the centroid, at least the distance to the
+        // closest beacon
+        let spread = strongest
+            .iter()
+            .zip(&weights)
+            .map(|(s, w)| w * s.beacon.position.distance(&position).powi(2))
+            .sum::<f64>()
+            / total;
+
+        Some(Estimate {
+            position,
+            accuracy: spread.sqrt().max(self.path_loss.distance(first.rssi)),
+            location: Some(first.beacon.location),
+        })
+    }
+}
+
+/// Multilateration on the distances the [`PathLossModel`] derives from the RSSI.
+///
+/// Minimises the squared range errors, each weighted by the inverse square of its range, with
+/// damped Gauss-Newton steps from the [`WeightedCentroid`]. With fewer than three beacons, or
+/// if the solution ends up further from the centroid than the longest range, the centroid is
+/// returned instead.
+#[derive(Debug, Clone)]
+pub struct LeastSquares {
+    pub path_loss: PathLossModel,
+    /// Only use this many of the strongest sightings, all if `None`.
+    pub max_beacons: Option<usize>,
+    pub iterations: usize,
+    /// Floor heights to take the vertical distance to beacons on other floors than the
+    /// strongest one out of their ranges. Without, all beacons count as on the same plane.
+    pub elevations: Option<ElevationTable>,
+}
+
+impl Default for LeastSquares {
+    fn default() -> Self {
+        LeastSquares {
+            path_loss: PathLossModel::default(),
+            max_beacons: Some(8),
+            iterations: 20,
+            elevations: None,
+        }
+    }
+}
+
+impl PositionEstimator for LeastSquares {
+    fn estimate(&self, sightings: &[Sighting]) -> Option<Estimate> {
+        let centroid = WeightedCentroid {
+            path_loss: self.path_loss,
+            max_beacons: self.max_beacons,
+        }
+        .estimate(sightings)?;
+        let strongest = strongest_first(sightings, self.max_beacons);
+        if strongest.len() < 3 {
+            return Some(centroid);
+        }
+
+        let frame = LocalFrame::new(centroid.position);
+        let scanner_altitude = self
+            .elevations
+            .as_ref()
+            .and_then(|e| e.location_altitude(&strongest[0].beacon.location));
+        // Horizontal range to each beacon, weighted by the inverse square of the full range
+        let ranges: Vec<(Point, f64, f64)> = strongest
+            .iter()
+            .map(|s| {
+                let range = self.path_loss.distance(s.rssi).max(0.5);
+                let height = match (&self.elevations, scanner_altitude) {
+                    (Some(elevations), Some(scanner)) => elevations
+                        .location_altitude(&s.beacon.location)
+                        .map_or(0.0, |altitude| altitude - scanner),
+                    _ => 0.0,
+                };
+                let horizontal = (range * range - height * height).max(0.25).sqrt();
+                (
+                    frame.to_local(&s.beacon.position),
+                    horizontal,
+                    range.powi(-2),
+                )
+            })
+            .collect();
+
+        let mut point = Point::default();
+        for _ in 0..self.iterations {
+            // Normal equations J^T W J step = J^T W r
+            let (mut a, mut b) = ([[0.0; 2]; 2], [0.0; 2]);
+            for (beacon, range, weight) in &ranges {
+                let (dx, dy) = (point.x - beacon.x, point.y - beacon.y);
+                let distance = (dx * dx + dy * dy).sqrt().max(1e-3);
+                let (jx, jy) = (dx / distance, dy / distance);
+                let residual = range - distance;
+                a[0][0] += weight * jx * jx;
+                a[0][1] += weight * jx * jy;
+                a[1][1] += weight * jy * jy;
+                b[0] += weight * jx * residual;
+                b[1] += weight * jy * residual;
+            }
+            // Damping keeps the step bounded when the beacons are nearly collinear
+            let damping = 1e-3 * (a[0][0] + a[1][1]);
+            a[0][0] += damping;
+            a[1][1] += damping;
+
+            let det = a[0][0] * a[1][1] - a[0][1] * a[0][1];
+            if det.abs() < 1e-12 {
+                break;
+            }
+            let step = Point {
+                x: (a[1][1] * b[0] - a[0][1] * b[1]) / det,
+                y: (a[0][0] * b[1] - a[0][1] * b[0]) / det,
+            };
+            point.x += step.x;
+            point.y += step.y;
+            if step.distance(&Point::default()) < 0.01 {
+                break;
+            }
+        }
+
+        let longest = ranges.iter().map(|(_, r, _)| *r).fold(0.0, f64::max);
+        if !point.x.is_finite() || point.distance(&Point::default()) > longest {
+            return Some(centroid);
+        }
+
+        let (sum, total) =
+            ranges
+                .iter()
+                .fold((0.0, 0.0), |(sum, total), (beacon, range, weight)| {
+                    let residual = range - point.distance(beacon);
+                    (sum + weight * residual * residual, total + weight)
+                });
+        Some(Estimate {
+            position: frame.to_position(&point),
+            accuracy: (sum / total).sqrt().max(1.0),
+            location: Some(strongest[0].beacon.location),
+        })
+    }
+}
+
+/// Robust wrapper that drops the beacons fitting the first estimate worst and estimates again.
+///
+/// A beacon fits badly if its distance to the first estimate and the distance derived from its
+/// RSSI differ by a large factor, as for beacons heard through an open door or another floor.
+#[derive(Debug, Clone)]
+pub struct Trimmed<E> {
+    pub estimator: E,
+    pub path_loss: PathLossModel,
+    /// Share of the sightings to drop.
+    pub trim: f64,
+    /// Never keep fewer sightings than this.
+    pub min_beacons: usize,
+}
+
+impl<E> Trimmed<E> {
+    pub fn new(estimator: E) -> Self {
+        Trimmed {
+            estimator,
+            path_loss: PathLossModel::default(),
+            trim: 0.25,
+            min_beacons: 3,
+        }
+    }
+}
+
+impl<E: PositionEstimator> PositionEstimator for Trimmed<E> {
+    fn estimate(&self, sightings: &[Sighting]) -> Option<Estimate> {
+        let first = self.estimator.estimate(sightings)?;
+        let keep =
+            ((sightings.len() as f64 * (1.0 - self.trim)).ceil() as usize).max(self.min_beacons);
+        if keep >= sightings.len() {
+            return Some(first);
+        }
+
+        let mut ranked: Vec<(f64, Sighting)> = sightings
+            .iter()
+            .map(|s| {
+                let actual = s.beacon.position.distance(&first.position).max(0.5);
+                let expected = self.path_loss.distance(s.rssi).max(0.5);
+                ((actual / expected).log10().abs(), *s)
+            })
+            .collect();
+        ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let kept: Vec<Sighting> = ranked.into_iter().take(keep).map(|(_, s)| s).collect();
+
+        self.estimator.estimate(&kept).or(Some(first))
+    }
+}
+
+/// Sightings ordered by descending RSSI, truncated to `limit`.
+pub(crate) fn strongest_first(sightings: &[Sighting], limit: Option<usize>) -> Vec<Sighting> {
+    let mut sorted = sightings.to_vec();
+    sorted.sort_by(|a, b| b.rssi.total_cmp(&a.rssi));
+    if let Some(limit) = limit {
+        sorted.truncate(limit);
+    }
+    sorted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+    use crate::testing::{beacon, offset, scan, sighting, square};
+    use crate::Building;
+
+    #[test]
+    fn strongest_reports_the_closest_beacon() {
+        let beacons = square();
+        let estimate = StrongestBeacon::default()
+            .estimate(&scan(&beacons, &offset(18.0, 3.0), 0))
+            .unwrap();
+        assert_eq!(estimate.position, beacons[1].position);
+        assert!(StrongestBeacon::default().estimate(&[]).is_none());
+    }
+
+    #[test]
+    fn centroid_leans_towards_the_strongest_beacons() {
+        let beacons = square();
+        let truth = offset(5.0, 5.0);
+        let estimate = WeightedCentroid::default()
+            .estimate(&scan(&beacons, &truth, 0))
+            .unwrap();
+        let centre = offset(10.0, 10.0);
+        assert!(estimate.position.distance(&truth) < centre.distance(&truth));
+    }
+
+    #[test]
+    fn least_squares_recovers_exact_ranges() {
+        let truth = offset(6.0, 12.0);
+        let estimate = LeastSquares::default()
+            .estimate(&scan(&square(), &truth, 0))
+            .unwrap();
+        assert!(estimate.position.distance(&truth) < 0.1);
+        assert!(estimate.accuracy <= 1.0);
+    }
+
+    #[test]
+    fn least_squares_beats_the_centroid_on_noisy_scans() {
+        let beacons = square();
+        let mut rng = Rng::new(42);
+        let (mut centroid, mut least_squares) = (0.0, 0.0);
+        for _ in 0..50 {
+            let truth = offset(20.0 * rng.uniform(), 20.0 * rng.uniform());
+            let mut sightings = scan(&beacons, &truth, 0);
+            for sighting in &mut sightings {
+                sighting.rssi += 1.0 * rng.normal();
+            }
+            let error = |estimate: Option<Estimate>| estimate.unwrap().position.distance(&truth);
+            centroid += error(WeightedCentroid::default().estimate(&sightings));
+            least_squares += error(LeastSquares::default().estimate(&sightings));
+        }
+        assert!(least_squares < centroid);
+    }
+
+    #[test]
+    fn trimmed_drops_a_beacon_that_does_not_fit() {
+        let mut sightings = scan(&square(), &offset(6.0, 12.0), 0);
+        // Heard as if 3 metres away, but 18 metres away, e.g. through an open door
+        let outlier = beacon(5, Building::HG, "E", "1", offset(6.0, 30.0));
+        sightings.push(sighting(outlier, PathLossModel::default().rssi(3.0), 0));
+
+        let error = |estimator: &dyn PositionEstimator| {
+            estimator
+                .estimate(&sightings)
+                .unwrap()
+                .position
+                .distance(&offset(6.0, 12.0))
+        };
+        let plain = error(&LeastSquares::default());
+        let trimmed = error(&Trimmed::new(LeastSquares::default()));
+        assert!(trimmed < 0.5);
+        assert!(trimmed < plain);
+    }
+}
@@ -0,0 +1,459 @@
+use crate::estimator::{Estimate, PositionEstimator};
+use crate::resolver::{find_beacon_by_id, find_location};
+use crate::sighting::Sighting;
+use crate::textformat::{format_error, parse, read_header, ReadError};
+use crate::{Building, Id, Location, Position};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+/// Format version written by [`FingerprintDatabase::write`].
+pub const FORMAT_VERSION: u32 = 1;
+
+const MAGIC: &str = "eth-beacons-fingerprints";
+
+/// Mean RSSI of every beacon heard at a surveyed position.
+#[derive(Debug, Clone)]
+pub struct Fingerprint {
+    pub position: Position,
+    pub location: Location,
+    pub rssi: HashMap<Id, f64>,
+}
+
+impl Fingerprint {
+    /// Averages the RSSI per beacon over all `sightings` taken at `position`.
+    pub fn record(position: Position, location: Location, sightings: &[Sighting]) -> Self {
+        Fingerprint {
+            position,
+            location,
+            rssi: mean_rssi(sightings),
+        }
+    }
+}
+
+/// Error of reading a [`FingerprintDatabase`].
+pub type FingerprintError = ReadError;
+
+/// Survey fingerprints, stored as a text file with tab separated fields:
+///
+/// ```text
+/// eth-beacons-fingerprints 1
+/// F <lat> <lon> <building> <floor> <room>
+/// B <uuid> <major> <minor> <rssi>
+/// ```
+///
+/// Every `F` line starts a fingerprint, followed by one `B` line per beacon. Beacons and
+/// locations are resolved against the dataset when reading.
+#[derive(Debug, Clone, Default)]
+pub struct FingerprintDatabase {
+    pub fingerprints: Vec<Fingerprint>,
+    /// Fingerprints skipped by [`FingerprintDatabase::read`] because their room is not in the
+    /// dataset, e.g. of a building that is not enabled.
+    pub unknown_rooms: usize,
+}
+
+impl FingerprintDatabase {
+    pub fn new() -> Self {
+        FingerprintDatabase::default()
+    }
+
+    pub fn add(&mut self, fingerprint: Fingerprint) {
+        self.fingerprints.push(fingerprint);
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "{}\t{}", MAGIC, FORMAT_VERSION)?;
+        for fingerprint in &self.fingerprints {
+            let location = &fingerprint.location;
+            writeln!(
+                writer,
+                "F\t{}\t{}\t{}\t{}\t{}",
+                fingerprint.position.lat,
+                fingerprint.position.lon,
+                location.building.as_ref(),
+                location.floor,
+                location.room
+            )?;
+
+            let mut beacons: Vec<(&Id, &f64)> = fingerprint.rssi.iter().collect();
+            beacons.sort_by_key(|(id, _)| (id.major, id.minor));
+            for (id, rssi) in beacons {
+                writeln!(
+                    writer,
+                    "B\t{}\t{}\t{}\t{}",
+                    id.uuid, id.major, id.minor, rssi
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a database. Beacons that are not in the dataset are skipped, and so are
+    /// fingerprints of unknown rooms, which are counted in
+    /// [`unknown_rooms`](FingerprintDatabase::unknown_rooms).
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, FingerprintError> {
+        let mut lines = reader.lines().enumerate();
+        read_header(&mut lines, MAGIC, FORMAT_VERSION)?;
+
+        let mut database = FingerprintDatabase::new();
+        // Whether the `B` lines belong to a skipped fingerprint
+        let mut skipping = false;
+        for (index, line) in lines {
+            let line = line?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields.as_slice() {
+                [""] => {}
+                ["F", lat, lon, building, floor, room] => {
+                    let position = Position {
+                        lat: parse(lat, index)?,
+                        lon: parse(lon, index)?,
+                    };
+                    let location = Building::from_str(building)
+                        .ok()
+                        .and_then(|building| find_location(building, floor, room));
+                    skipping = location.is_none();
+                    match location {
+                        Some(location) => database.add(Fingerprint {
+                            position,
+                            location,
+                            rssi: HashMap::new(),
+                        }),
+                        None => database.unknown_rooms += 1,
+                    }
+                }
+                ["B", _, _, _, _] if skipping => {}
+                ["B", uuid, major, minor, rssi] => {
+                    let fingerprint = database
+                        .fingerprints
+                        .last_mut()
+                        .ok_or_else(|| format_error(index, "beacon before first fingerprint"))?;
+                    if let Some(beacon) =
+                        find_beacon_by_id(uuid, parse(major, index)?, parse(minor, index)?)
+                    {
+                        fingerprint.rssi.insert(beacon.id, parse(rssi, index)?);
+                    }
+                }
+                _ => return Err(format_error(index, "unexpected line")),
+            }
+        }
+        Ok(database)
+    }
+}
+
+fn mean_rssi(sightings: &[Sighting]) -> HashMap<Id, f64> {
+    let mut sums: HashMap<Id, (f64, usize)> = HashMap::new();
+    for sighting in sightings {
+        let sum = sums.entry(sighting.beacon.id).or_default();
+        sum.0 += sighting.rssi;
+        sum.1 += 1;
+    }
+    sums.into_iter()
+        .map(|(id, (sum, count))| (id, sum / count as f64))
+        .collect()
+}
+
+/// How beacons that appear in only one of the scan and the fingerprint are compared.
+#[derive(Debug, Clone, Copy)]
+pub enum MissingBeacons {
+    /// Only compare beacons present in both.
+    Ignore,
+    /// Treat a missing beacon as if it was heard with this RSSI in dBm.
+    Substitute(f64),
+}
+
+#[derive(Debug, Clone)]
+pub struct KnnConfig {
+    /// Number of nearest fingerprints to average.
+    pub k: usize,
+    pub missing: MissingBeacons,
+    /// Fingerprints sharing fewer beacons with the scan are not considered.
+    pub min_common_beacons: usize,
+}
+
+impl Default for KnnConfig {
+    fn default() -> Self {
+        KnnConfig {
+            k: 3,
+            missing: MissingBeacons::Substitute(-100.0),
+            min_common_beacons: 1,
+        }
+    }
+}
+
+/// Weighted k-nearest-neighbour matching of scans against survey fingerprints.
+///
+/// Fingerprints are compared by the root mean square RSSI difference. The `k` closest ones are
+/// averaged, weighted by the inverse of that difference, and the location with the highest
+/// total weight is reported.
+#[derive(Debug, Clone)]
+pub struct FingerprintMatcher {
+    pub database: FingerprintDatabase,
+    pub config: KnnConfig,
+}
+
+impl FingerprintMatcher {
+    pub fn new(database: FingerprintDatabase, config: KnnConfig) -> Self {
+        FingerprintMatcher { database, config }
+    }
+
+    /// RMS RSSI difference between a scan and a fingerprint, `None` if they share too few
+    /// beacons.
+    fn signal_distance(&self, scan: &HashMap<Id, f64>, fingerprint: &Fingerprint) -> Option<f64> {
+        let common = scan
+            .keys()
+            .filter(|id| fingerprint.rssi.contains_key(id))
+            .count();
+        if common < self.config.min_common_beacons.max(1) {
+            return None;
+        }
+
+        let (sum, count) = match self.config.missing {
+            MissingBeacons::Ignore => scan
+                .iter()
+                .filter_map(|(id, rssi)| fingerprint.rssi.get(id).map(|other| rssi - other))
+                .fold((0.0, 0), |(sum, count), diff| {
+                    (sum + diff * diff, count + 1)
+                }),
+            MissingBeacons::Substitute(missing) => {
+                let scan_side = scan
+                    .iter()
+                    .map(|(id, rssi)| rssi - fingerprint.rssi.get(id).copied().unwrap_or(missing));
+                let fingerprint_side = fingerprint
+                    .rssi
+                    .iter()
+                    .filter(|(id, _)| !scan.contains_key(id))
+                    .map(|(_, rssi)| missing - rssi);
+                scan_side
+                    .chain(fingerprint_side)
+                    .fold((0.0, 0), |(sum, count), diff| {
+                        (sum + diff * diff, count + 1)
+                    })
+            }
+        };
+        Some((sum / count as f64).sqrt())
+    }
+}
+
+impl PositionEstimator for FingerprintMatcher {
+    fn estimate(&self, sightings: &[Sighting]) -> Option<Estimate> {
+        let scan = mean_rssi(sightings);
+
+        let mut neighbours: Vec<(f64, &Fingerprint)> = self
+            .database
+            .fingerprints
+            .iter()
+            .filter_map(|f| self.signal_distance(&scan, f).map(|d| (d, f)))
+            .collect();
+        neighbours.sort_by(|a, b| a.0.total_cmp(&b.0));
+        neighbours.truncate(self.config.k.max(1));
+        if neighbours.is_empty() {
+            return None;
+        }
+
+        let weights: Vec<f64> = neighbours.iter().map(|(d, _)| 1.0 / (d + 1.0)).collect();
+        let total: f64 = weights.iter().sum();
+        let weighted = |value: fn(&Fingerprint) -> f64| {
+            neighbours
+                .iter()
+                .zip(&weights)
+                .map(|((_, f), w)| w * value(f))
+                .sum::<f64>()
+                / total
+        };
+        let position = Position {
+            lat: weighted(|f| f.position.lat),
+            lon: weighted(|f| f.position.lon),
+        };
+
+        let mut votes: Vec<(Location, f64)> = Vec::new();
+        for ((_, fingerprint), weight) in neighbours.iter().zip(&weights) {
+            match votes.iter_mut().find(|(l, _)| *l == fingerprint.location) {
+                Some((_, total)) => *total += weight,
+                None => votes.push((fingerprint.location, *weight)),
+            }
+        }
+        let location = votes
+            .into_iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(location, _)| location);
+
+        let spread = neighbours
+            .iter()
+            .zip(&weights)
+            .map(|((_, f), w)| w * f.position.distance(&position).powi(2))
+            .sum::<f64>()
+            / total;
+
+        Some(Estimate {
+            position,
+            accuracy: spread.sqrt().max(1.0),
+            location,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{beacon, offset, sighting};
+    use crate::Beacon;
+
+    fn location(room: &'static str) -> Location {
+        Location {
+            building: Building::HG,
+            floor: "E",
+            room,
+        }
+    }
+
+    /// Fingerprint of `room` at `east` metres on a corridor.
+    fn fingerprint(room: &'static str, east: f64, rssi: &[(&'static Beacon, f64)]) -> Fingerprint {
+        let sightings: Vec<Sighting> = rssi.iter().map(|&(b, rssi)| sighting(b, rssi, 0)).collect();
+        Fingerprint::record(offset(east, 0.0), location(room), &sightings)
+    }
+
+    fn beacons() -> [&'static Beacon; 3] {
+        [1, 2, 3].map(|minor| beacon(minor, Building::HG, "E", "1", offset(0.0, 0.0)))
+    }
+
+    fn matcher(
+        fingerprints: Vec<Fingerprint>,
+        k: usize,
+        missing: MissingBeacons,
+    ) -> FingerprintMatcher {
+        FingerprintMatcher::new(
+            FingerprintDatabase {
+                fingerprints,
+                unknown_rooms: 0,
+            },
+            KnnConfig {
+                k,
+                missing,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn averages_the_sightings_of_a_fingerprint() {
+        let [a, _, _] = beacons();
+        let sightings = [sighting(a, -60.0, 0), sighting(a, -70.0, 1_000)];
+        let fingerprint = Fingerprint::record(offset(0.0, 0.0), location("1"), &sightings);
+        assert_eq!(fingerprint.rssi.len(), 1);
+        assert_eq!(fingerprint.rssi[&a.id], -65.0);
+    }
+
+    #[test]
+    fn weights_the_nearest_fingerprints() {
+        let [a, b, _] = beacons();
+        let fingerprints = vec![
+            fingerprint("1", 0.0, &[(a, -60.0), (b, -80.0)]),
+            fingerprint("2", 10.0, &[(a, -80.0), (b, -60.0)]),
+            fingerprint("3", 20.0, &[(a, -90.0), (b, -50.0)]),
+        ];
+        let scan = [sighting(a, -64.0, 0), sighting(b, -76.0, 0)];
+
+        let nearest = matcher(fingerprints.clone(), 1, MissingBeacons::Ignore)
+            .estimate(&scan)
+            .unwrap();
+        assert!(nearest.position.distance(&offset(0.0, 0.0)) < 0.01);
+        assert_eq!(nearest.location, Some(location("1")));
+
+        // 4 dB from the first fingerprint and 16 dB from the second
+        let estimate = matcher(fingerprints, 2, MissingBeacons::Ignore)
+            .estimate(&scan)
+            .unwrap();
+        let (first, second) = (1.0 / 5.0, 1.0 / 17.0);
+        let east = second * 10.0 / (first + second);
+        assert!(estimate.position.distance(&offset(east, 0.0)) < 0.01);
+        assert_eq!(estimate.location, Some(location("1")));
+        assert!(estimate.accuracy >= 1.0);
+    }
+
+    #[test]
+    fn compares_missing_beacons_as_configured() {
+        let [a, b, c] = beacons();
+        // The first fingerprint matches the scan exactly but also heard a strong third beacon
+        let fingerprints = vec![
+            fingerprint("1", 0.0, &[(a, -60.0), (b, -80.0), (c, -60.0)]),
+            fingerprint("2", 10.0, &[(a, -63.0), (b, -77.0)]),
+        ];
+        let scan = [sighting(a, -60.0, 0), sighting(b, -80.0, 0)];
+
+        let ignoring = matcher(fingerprints.clone(), 1, MissingBeacons::Ignore);
+        assert_eq!(
+            ignoring.estimate(&scan).unwrap().location,
+            Some(location("1"))
+        );
+
+        let substituting = matcher(fingerprints, 1, MissingBeacons::Substitute(-100.0));
+        assert_eq!(
+            substituting.estimate(&scan).unwrap().location,
+            Some(location("2"))
+        );
+    }
+
+    #[test]
+    fn needs_common_beacons() {
+        let [a, b, c] = beacons();
+        let matcher = matcher(
+            vec![fingerprint("1", 0.0, &[(a, -60.0), (b, -70.0)])],
+            3,
+            MissingBeacons::Substitute(-100.0),
+        );
+        assert!(matcher.estimate(&[sighting(c, -60.0, 0)]).is_none());
+        assert!(matcher.estimate(&[]).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "HG")]
+    fn round_trips_through_the_text_format() {
+        let beacons: Vec<&'static Beacon> = crate::BEACONS
+            .iter()
+            .filter(|b| b.location.building == Building::HG)
+            .take(2)
+            .collect();
+        let sightings = [
+            sighting(beacons[0], -61.5, 0),
+            sighting(beacons[1], -80.0, 0),
+        ];
+        let mut database = FingerprintDatabase::new();
+        database.add(Fingerprint::record(
+            beacons[0].position,
+            beacons[0].location,
+            &sightings,
+        ));
+
+        let mut file = Vec::new();
+        database.write(&mut file).unwrap();
+        let read = FingerprintDatabase::read(file.as_slice()).unwrap();
+
+        assert_eq!(read.unknown_rooms, 0);
+        assert_eq!(read.fingerprints.len(), 1);
+        let (written, read) = (&database.fingerprints[0], &read.fingerprints[0]);
+        assert_eq!(read.position, written.position);
+        assert_eq!(read.location, written.location);
+        assert_eq!(read.rssi, written.rssi);
+    }
+
+    #[test]
+    fn skips_fingerprints_of_unknown_rooms() {
+        let file = "eth-beacons-fingerprints\t1\n\
+                    F\t47.3763\t8.5476\tHG\tX\tnowhere\n\
+                    B\t58793564-459c-548d-bfcc-367ffd4fcd70\t0\t1\t-70\n\
+                    F\t47.3763\t8.5476\tXYZ\tE\t1\n";
+        let database = FingerprintDatabase::read(file.as_bytes()).unwrap();
+        assert!(database.fingerprints.is_empty());
+        assert_eq!(database.unknown_rooms, 2);
+    }
+
+    #[test]
+    fn rejects_beacons_before_the_first_fingerprint() {
+        let file = "eth-beacons-fingerprints\t1\n\
+                    B\t58793564-459c-548d-bfcc-367ffd4fcd70\t0\t1\t-70\n";
+        assert!(matches!(
+            FingerprintDatabase::read(file.as_bytes()),
+            Err(FingerprintError::Format { line: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,252 @@
+use crate::sighting::Sighting;
+use crate::Building;
+use std::collections::HashMap;
+
+type FloorKey = (Building, &'static str);
+
+#[derive(Debug, Clone)]
+pub struct FloorEstimatorConfig {
+    /// Time in milliseconds after which the evidence of a sighting has lost half its weight.
+    pub half_life: u64,
+    /// Sightings at or below this RSSI in dBm carry no evidence.
+    pub min_rssi: f64,
+    /// Confidence by which another floor has to lead the current one before switching.
+    pub switch_margin: f64,
+    /// Time in milliseconds another floor has to keep that lead before switching.
+    pub switch_delay: u64,
+}
+
+impl Default for FloorEstimatorConfig {
+    fn default() -> Self {
+        FloorEstimatorConfig {
+            half_life: 4_000,
+            min_rssi: -100.0,
+            switch_margin: 0.2,
+            switch_delay: 2_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloorEstimate {
+    pub building: Building,
+    pub floor: &'static str,
+    /// Share of the current evidence supporting this floor, between 0 and 1.
+    pub confidence: f64,
+}
+
+/// Estimates the floor from a stream of sightings.
+///
+/// Every sighting adds evidence for the floor of its beacon, weighted by its signal amplitude
+/// above [`FloorEstimatorConfig::min_rssi`]. Evidence decays exponentially, and the reported
+/// floor only changes once another floor has led by `switch_margin` for `switch_delay`, so
+/// beacons heard through stairwells and atria do not make the estimate flip-flop.
+#[derive(Debug, Clone)]
+pub struct FloorEstimator {
+    config: FloorEstimatorConfig,
+    evidence: HashMap<FloorKey, f64>,
+    last_timestamp: Option<u64>,
+    current: Option<FloorKey>,
+    candidate: Option<(FloorKey, u64)>,
+}
+
+impl FloorEstimator {
+    pub fn new(config: FloorEstimatorConfig) -> Self {
+        FloorEstimator {
+            config,
+            evidence: HashMap::new(),
+            last_timestamp: None,
+            current: None,
+            candidate: None,
+        }
+    }
+
+    /// Adds a sighting and returns the updated estimate. Sightings older than the latest one
+    /// are counted without decay.
+    pub fn update(&mut self, sighting: &Sighting) -> Option<FloorEstimate> {
+        let now = self
+            .last_timestamp
+            .unwrap_or(sighting.timestamp)
+            .max(sighting.timestamp);
+        self.decay(now);
+
+        if sighting.rssi > self.config.min_rssi {
+            // Signal amplitude relative to `min_rssi`, so a beacon 6 dB stronger counts twice
+            let weight = 10f64.powf((sighting.rssi - self.config.min_rssi) / 20.0);
+            let location = &sighting.beacon.location;
+            *self
+                .evidence
+                .entry((location.building, location.floor))
+                .or_default() += weight;
+        }
+
+        self.apply_hysteresis(now);
+        self.estimate()
+    }
+
+    /// Adds all `sightings` in order and returns the final estimate.
+    pub fn update_all<'a>(
+        &mut self,
+        sightings: impl IntoIterator<Item = &'a Sighting>,
+    ) -> Option<FloorEstimate> {
+        for sighting in sightings {
+            self.update(sighting);
+        }
+        self.estimate()
+    }
+
+    /// The current floor with the share of evidence supporting it.
+    pub fn estimate(&self) -> Option<FloorEstimate> {
+        self.current.map(|key| FloorEstimate {
+            building: key.0,
+            floor: key.1,
+            confidence: self.confidence(&key),
+        })
+    }
+
+    /// Share of evidence of every floor seen, strongest first.
+    pub fn distribution(&self) -> Vec<FloorEstimate> {
+        let mut floors: Vec<FloorEstimate> = self
+            .evidence
+            .keys()
+            .map(|key| FloorEstimate {
+                building: key.0,
+                floor: key.1,
+                confidence: self.confidence(key),
+            })
+            .collect();
+        floors.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        floors
+    }
+
+    /// Forgets all evidence, e.g. after the device was switched off.
+    pub fn reset(&mut self) {
+        self.evidence.clear();
+        self.last_timestamp = None;
+        self.current = None;
+        self.candidate = None;
+    }
+
+    fn decay(&mut self, now: u64) {
+        if let Some(last) = self.last_timestamp {
+            let elapsed = now.saturating_sub(last) as f64;
+            let factor = 0.5f64.powf(elapsed / self.config.half_life.max(1) as f64);
+            self.evidence.retain(|_, weight| {
+                *weight *= factor;
+                *weight > f64::EPSILON
+            });
+        }
+        self.last_timestamp = Some(now);
+    }
+
+    fn apply_hysteresis(&mut self, now: u64) {
+        let Some(leader) = self
+            .evidence
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(key, _)| *key)
+        else {
+            return;
+        };
+
+        let current = match self.current {
+            Some(current) if self.evidence.contains_key(&current) => current,
+            _ => {
+                self.current = Some(leader);
+                self.candidate = None;
+                return;
+            }
+        };
+
+        let lead = self.confidence(&leader) - self.confidence(&current);
+        if leader == current || lead < self.config.switch_margin {
+            self.candidate = None;
+            return;
+        }
+
+        let since = match self.candidate {
+            Some((candidate, since)) if candidate == leader => since,
+            _ => {
+                self.candidate = Some((leader, now));
+                now
+            }
+        };
+        if now.saturating_sub(since) >= self.config.switch_delay {
+            self.current = Some(leader);
+            self.candidate = None;
+        }
+    }
+
+    fn confidence(&self, key: &FloorKey) -> f64 {
+        let total: f64 = self.evidence.values().sum();
+        if total > 0.0 {
+            self.evidence.get(key).copied().unwrap_or(0.0) / total
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for FloorEstimator {
+    fn default() -> Self {
+        FloorEstimator::new(FloorEstimatorConfig::default())
+    }
+}
+
+/// Storeys of `floor` above floor A. Floors Z, Y and so on down to R lie below A, and
+/// mezzanines like `EO` half a storey above their base floor.
+pub fn level(floor: &str) -> Option<f64> {
+    let letter_level = |letter: u8| match letter {
+        b'A'..=b'Q' => Some((letter - b'A') as f64),
+        b'R'..=b'Z' => Some(letter as f64 - b'Z' as f64 - 1.0),
+        _ => None,
+    };
+    match floor.as_bytes() {
+        [letter] => letter_level(*letter),
+        [letter, b'O'] => letter_level(*letter).map(|level| level + 0.5),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{beacon, offset, sighting};
+
+    #[test]
+    fn reports_the_floor_with_the_most_evidence() {
+        let e = beacon(1, Building::HG, "E", "1", offset(0.0, 0.0));
+        let f = beacon(2, Building::HG, "F", "1", offset(0.0, 0.0));
+        let mut estimator = FloorEstimator::default();
+
+        estimator.update_all(&[sighting(e, -60.0, 0), sighting(f, -80.0, 0)]);
+        let estimate = estimator.estimate().unwrap();
+        assert_eq!((estimate.building, estimate.floor), (Building::HG, "E"));
+        assert!(estimate.confidence > 0.9);
+    }
+
+    #[test]
+    fn switches_floor_only_after_the_delay() {
+        let e = beacon(1, Building::HG, "E", "1", offset(0.0, 0.0));
+        let f = beacon(2, Building::HG, "F", "1", offset(0.0, 0.0));
+        let mut estimator = FloorEstimator::default();
+        estimator.update(&sighting(e, -60.0, 0));
+
+        // F leads from the first sighting on, but only for less than the switch delay
+        for timestamp in (500..2_000).step_by(500) {
+            estimator.update(&sighting(f, -50.0, timestamp));
+            assert_eq!(estimator.estimate().unwrap().floor, "E");
+        }
+        estimator.update(&sighting(f, -50.0, 2_500));
+        assert_eq!(estimator.estimate().unwrap().floor, "F");
+    }
+
+    #[test]
+    fn levels_of_floor_codes() {
+        assert_eq!(level("A"), Some(0.0));
+        assert_eq!(level("E"), Some(4.0));
+        assert_eq!(level("EO"), Some(4.5));
+        assert_eq!(level("Z"), Some(-1.0));
+        assert_eq!(level("E1"), None);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::geo::{convex_hull, polygon_area, polygon_contains, LocalFrame, Point};
+use crate::{Beacon, Building, Position, BEACONS};
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::sync::OnceLock;
+
+include!("footprints.gen.rs");
+
+/// Metres the convex hull of the beacons is widened by, as beacons hang inside the outer walls.
+pub const HULL_MARGIN: f64 = 3.0;
+
+/// Footprints of all buildings with beacons or an override, built on first use.
+fn footprints() -> &'static HashMap<Building, Vec<Position>> {
+    static FOOTPRINTS: OnceLock<HashMap<Building, Vec<Position>>> = OnceLock::new();
+    FOOTPRINTS.get_or_init(|| footprints_of(BEACONS, FOOTPRINT_OVERRIDES))
+}
+
+/// Footprints of the buildings of `beacons`, replaced by or added from `overrides`.
+fn footprints_of<'a>(
+    beacons: impl IntoIterator<Item = &'a Beacon>,
+    overrides: &[(Building, &[Position])],
+) -> HashMap<Building, Vec<Position>> {
+    let mut positions: HashMap<Building, Vec<Position>> = HashMap::new();
+    for beacon in beacons {
+        positions
+            .entry(beacon.location.building)
+            .or_default()
+            .push(beacon.position);
+    }
+
+    let mut footprints: HashMap<Building, Vec<Position>> = positions
+        .into_iter()
+        .map(|(building, positions)| (building, hull(&positions, HULL_MARGIN)))
+        .collect();
+    for (building, polygon) in overrides {
+        footprints.insert(*building, polygon.to_vec());
+    }
+    footprints
+}
+
+/// Buildings of `footprints` containing `position`, the smallest footprint first.
+fn buildings_at(
+    footprints: &HashMap<Building, Vec<Position>>,
+    position: &Position,
+) -> Vec<Building> {
+    let mut buildings: Vec<(Building, f64)> = footprints
+        .iter()
+        .filter(|(_, footprint)| polygon_contains(footprint, position))
+        .map(|(building, footprint)| (*building, polygon_area(footprint)))
+        .collect();
+    buildings.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.as_ref().cmp(b.0.as_ref())));
+    buildings
+        .into_iter()
+        .map(|(building, _)| building)
+        .collect()
+}
+
+/// Convex hull of `positions`, each widened to a circle of `margin` metres, so single beacons
+/// and beacons along a line get a footprint as well.
+fn hull(positions: &[Position], margin: f64) -> Vec<Position> {
+    let frame = LocalFrame::new(positions[0]);
+    let corners: Vec<Point> = positions
+        .iter()
+        .flat_map(|position| {
+            let center = frame.to_local(position);
+            (0..8).map(move |i| {
+                let angle = TAU * i as f64 / 8.0;
+                Point {
+                    x: center.x + margin * angle.cos(),
+                    y: center.y + margin * angle.sin(),
+                }
+            })
+        })
+        .collect();
+    convex_hull(&corners)
+        .iter()
+        .map(|point| frame.to_position(point))
+        .collect()
+}
+
+impl Building {
+    /// Outline of the building, the override passed to the generator or else the convex hull of
+    /// its beacons widened by [`HULL_MARGIN`]. `None` for buildings without either.
+    pub fn footprint(&self) -> Option<&'static [Position]> {
+        footprints().get(self).map(Vec::as_slice)
+    }
+
+    /// Whether `position` lies inside the footprint.
+    pub fn contains(&self, position: &Position) -> bool {
+        self.footprint()
+            .is_some_and(|footprint| polygon_contains(footprint, position))
+    }
+
+    /// All buildings whose footprint contains `position`, the smallest footprint first.
+    pub fn all_at(position: &Position) -> Vec<Building> {
+        buildings_at(footprints(), position)
+    }
+
+    /// The building containing `position`. Where convex footprints overlap, the smallest one
+    /// wins, as a small building is more likely to be hidden in the hull of a large one than
+    /// the other way round.
+    pub fn at(position: &Position) -> Option<Building> {
+        Building::all_at(position).into_iter().next()
+    }
+}
+
+/// Whether `position` lies inside any building, e.g. to reject estimates that drifted outside.
+pub fn is_indoor(position: &Position) -> bool {
+    footprints()
+        .values()
+        .any(|footprint| polygon_contains(footprint, position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{beacon, offset};
+
+    fn local(polygon: &[Position]) -> Vec<Point> {
+        let frame = LocalFrame::new(offset(0.0, 0.0));
+        polygon.iter().map(|p| frame.to_local(p)).collect()
+    }
+
+    #[test]
+    fn widens_the_hull_of_a_line() {
+        let positions = [(0.0, 0.0), (10.0, 0.0), (20.0, 0.0), (10.0, 0.0)]
+            .map(|(east, north)| offset(east, north));
+        let hull = hull(&positions, HULL_MARGIN);
+
+        // Half an octagon around each end, the points along the sides are dropped
+        assert_eq!(hull.len(), 10);
+        let points = local(&hull);
+        let (min_x, max_x) = points
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), p| {
+                (min.min(p.x), max.max(p.x))
+            });
+        assert!((min_x + HULL_MARGIN).abs() < 0.01);
+        assert!((max_x - 20.0 - HULL_MARGIN).abs() < 0.01);
+
+        let inside = |east, north| polygon_contains(&hull, &offset(east, north));
+        assert!(inside(10.0, HULL_MARGIN - 0.1));
+        assert!(!inside(10.0, HULL_MARGIN + 0.1));
+        assert!(inside(20.0 + HULL_MARGIN - 0.1, 0.0));
+        assert!(!inside(-HULL_MARGIN - 0.1, 0.0));
+    }
+
+    #[test]
+    fn gives_a_single_position_an_octagon() {
+        let hull = hull(&[offset(0.0, 0.0), offset(0.0, 0.0)], HULL_MARGIN);
+        assert_eq!(hull.len(), 8);
+        assert!(local(&hull)
+            .iter()
+            .all(|p| (p.x.hypot(p.y) - HULL_MARGIN).abs() < 0.01));
+    }
+
+    #[test]
+    fn prefers_overrides_to_the_hull() {
+        let beacons = [
+            beacon(1, Building::HG, "E", "1", offset(0.0, 0.0)),
+            beacon(2, Building::HG, "E", "1", offset(20.0, 0.0)),
+            beacon(3, Building::HG, "E", "1", offset(10.0, 20.0)),
+            beacon(4, Building::ML, "E", "1", offset(100.0, 0.0)),
+        ];
+        let square = [
+            offset(-5.0, -5.0),
+            offset(30.0, -5.0),
+            offset(30.0, 30.0),
+            offset(-5.0, 30.0),
+        ];
+        let cab = [offset(200.0, 0.0), offset(210.0, 0.0), offset(210.0, 10.0)];
+        let overrides: &[(Building, &[Position])] =
+            &[(Building::HG, &square), (Building::CAB, &cab)];
+        let footprints = footprints_of(beacons, overrides);
+
+        assert_eq!(footprints.len(), 3);
+        assert_eq!(footprints[&Building::HG], square);
+        assert_eq!(footprints[&Building::CAB], cab);
+        assert_eq!(footprints[&Building::ML].len(), 8);
+    }
+
+    #[test]
+    fn finds_the_smallest_building_first() {
+        let square = |west: f64, south: f64, size: f64| {
+            vec![
+                offset(west, south),
+                offset(west + size, south),
+                offset(west + size, south + size),
+                offset(west, south + size),
+            ]
+        };
+        let footprints = HashMap::from([
+            (Building::ML, square(0.0, 0.0, 100.0)),
+            (Building::HG, square(40.0, 40.0, 20.0)),
+        ]);
+        let at = |east, north| buildings_at(&footprints, &offset(east, north));
+
+        assert_eq!(at(50.0, 50.0), [Building::HG, Building::ML]);
+        assert_eq!(at(10.0, 10.0), [Building::ML]);
+        assert_eq!(at(99.9, 50.0), [Building::ML]);
+        assert!(at(100.1, 50.0).is_empty());
+        assert!(at(-0.1, 50.0).is_empty());
+    }
+
+    #[test]
+    fn knows_no_building_far_from_eth() {
+        let gulf_of_guinea = Position { lat: 0.0, lon: 0.0 };
+        assert_eq!(Building::at(&gulf_of_guinea), None);
+        assert!(Building::all_at(&gulf_of_guinea).is_empty());
+        assert!(!is_indoor(&gulf_of_guinea));
+    }
+
+    #[test]
+    #[cfg(feature = "HG")]
+    fn contains_the_beacons_of_the_dataset() {
+        let beacon = BEACONS
+            .iter()
+            .find(|b| b.location.building == Building::HG)
+            .unwrap();
+        assert!(Building::HG.contains(&beacon.position));
+        assert!(Building::all_at(&beacon.position).contains(&Building::HG));
+        assert!(is_indoor(&beacon.position));
+        assert!(!Building::HG.contains(&Position { lat: 0.0, lon: 0.0 }));
+    }
+}
@@ -0,0 +1,3 @@
+
+pub(crate) static FOOTPRINT_OVERRIDES: &[(Building, &[Position])] = &[
+];
@@ -0,0 +1,133 @@
+use crate::Position;
+
+/// Mean earth radius in metres (IUGG).
+pub const EARTH_RADIUS: f64 = 6_371_008.8;
+
+impl Position {
+    /// Great-circle distance to `other` in metres.
+    pub fn distance(&self, other: &Position) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.lon - self.lon).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
+    }
+}
+
+/// Point in a [`LocalFrame`], `x` metres east and `y` metres north of its origin.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn distance(&self, other: &Point) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+}
+
+/// Flat east/north frame tangent to the earth at `origin`.
+///
+/// Good to a few centimetres across a campus, which is all the positioning code needs.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalFrame {
+    origin: Position,
+    metres_per_lat: f64,
+    metres_per_lon: f64,
+}
+
+impl LocalFrame {
+    pub fn new(origin: Position) -> Self {
+        let metres_per_lat = EARTH_RADIUS.to_radians();
+        LocalFrame {
+            origin,
+            metres_per_lat,
+            metres_per_lon: metres_per_lat * origin.lat.to_radians().cos(),
+        }
+    }
+
+    pub fn origin(&self) -> Position {
+        self.origin
+    }
+
+    pub fn to_local(&self, position: &Position) -> Point {
+        Point {
+            x: (position.lon - self.origin.lon) * self.metres_per_lon,
+            y: (position.lat - self.origin.lat) * self.metres_per_lat,
+        }
+    }
+
+    pub fn to_position(&self, point: &Point) -> Position {
+        Position {
+            lat: self.origin.lat + point.y / self.metres_per_lat,
+            lon: self.origin.lon + point.x / self.metres_per_lon,
+        }
+    }
+}
+
+/// Whether `position` lies inside `polygon`, by ray casting. The polygon may be open or closed.
+pub fn polygon_contains(polygon: &[Position], position: &Position) -> bool {
+    let mut inside = false;
+    let mut previous = match polygon.last() {
+        Some(last) => last,
+        None => return false,
+    };
+    for vertex in polygon {
+        if (vertex.lat > position.lat) != (previous.lat > position.lat) {
+            let lon = vertex.lon
+                + (position.lat - vertex.lat) / (previous.lat - vertex.lat)
+                    * (previous.lon - vertex.lon);
+            if position.lon < lon {
+                inside = !inside;
+            }
+        }
+        previous = vertex;
+    }
+    inside
+}
+
+/// Convex hull of `points`, counter-clockwise without repeating the first point.
+pub fn convex_hull(points: &[Point]) -> Vec<Point> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    // Andrew's monotone chain, lower hull then upper hull
+    let cross =
+        |o: &Point, a: &Point, b: &Point| (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x);
+    let mut hull: Vec<Point> = Vec::with_capacity(2 * sorted.len());
+    for pass in [sorted.clone(), sorted.into_iter().rev().collect()] {
+        let start = hull.len();
+        for point in pass {
+            while hull.len() >= start + 2
+                && cross(&hull[hull.len() - 2], &hull[hull.len() - 1], &point) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(point);
+        }
+        // The last point of each chain starts the other one
+        hull.pop();
+    }
+    hull
+}
+
+/// Area of `polygon` in square metres.
+pub fn polygon_area(polygon: &[Position]) -> f64 {
+    let Some(first) = polygon.first() else {
+        return 0.0;
+    };
+    let frame = LocalFrame::new(*first);
+    let points: Vec<Point> = polygon.iter().map(|p| frame.to_local(p)).collect();
+    let twice: f64 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum();
+    twice.abs() / 2.0
+}
@@ -0,0 +1,303 @@
+use crate::estimator::Estimate;
+use crate::geo::polygon_contains;
+use crate::resolver::find_room;
+use crate::{Building, Location, Position};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Zone {
+    Building(Building),
+    Floor {
+        building: Building,
+        floor: String,
+    },
+    Room {
+        building: Building,
+        floor: String,
+        room: String,
+    },
+    /// Area enclosed by the given positions, on any floor.
+    Polygon(Vec<Position>),
+}
+
+impl Zone {
+    /// Zone of a room with beacons by its code like `HPH G 3`, see [`find_room`].
+    pub fn room_code(code: &str) -> Option<Zone> {
+        find_room(code).map(|location| Zone::Room {
+            building: location.building,
+            floor: location.floor.to_string(),
+            room: location.room.to_string(),
+        })
+    }
+
+    /// Whether a fix lies in this zone, `None` if the fix cannot tell. Building, floor and
+    /// room zones need a location, polygons a position.
+    pub fn contains(&self, fix: &Fix) -> Option<bool> {
+        Some(match (self, &fix.location, &fix.position) {
+            (Zone::Building(building), Some(location), _) => location.building == *building,
+            (Zone::Floor { building, floor }, Some(location), _) => {
+                location.building == *building && location.floor == floor
+            }
+            (
+                Zone::Room {
+                    building,
+                    floor,
+                    room,
+                },
+                Some(location),
+                _,
+            ) => location.building == *building && location.floor == floor && location.room == room,
+            (Zone::Polygon(polygon), _, Some(position)) => polygon_contains(polygon, position),
+            _ => return None,
+        })
+    }
+}
+
+/// Position and/or room estimate at a point in time.
+#[derive(Debug, Clone, Copy)]
+pub struct Fix {
+    pub timestamp: u64,
+    pub position: Option<Position>,
+    pub location: Option<Location>,
+}
+
+impl Fix {
+    pub fn from_estimate(estimate: &Estimate, timestamp: u64) -> Self {
+        Fix {
+            timestamp,
+            position: Some(estimate.position),
+            location: estimate.location,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeofenceEventKind {
+    Enter,
+    Exit,
+    /// Emitted once per visit after staying inside for the dwell time.
+    Dwell,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeofenceEvent {
+    /// Index returned by [`Geofence::add_zone`].
+    pub zone: usize,
+    pub kind: GeofenceEventKind,
+    /// When the event happened: the first fix inside or outside the zone for enter and exit,
+    /// the moment the dwell time was reached for dwell.
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeofenceConfig {
+    /// Milliseconds fixes have to stay inside a zone before entering it.
+    pub enter_delay: u64,
+    /// Milliseconds fixes have to stay outside a zone before leaving it.
+    pub exit_delay: u64,
+    /// Milliseconds after entering a zone until a dwell event is emitted.
+    pub dwell_time: u64,
+}
+
+impl Default for GeofenceConfig {
+    fn default() -> Self {
+        GeofenceConfig {
+            enter_delay: 3_000,
+            exit_delay: 10_000,
+            dwell_time: 60_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct ZoneState {
+    inside: bool,
+    /// Start of the fixes disagreeing with `inside`.
+    pending_since: Option<u64>,
+    entered_at: u64,
+    dwell_reported: bool,
+}
+
+/// Emits debounced enter, exit and dwell events for a set of zones from a stream of fixes.
+#[derive(Debug, Clone, Default)]
+pub struct Geofence {
+    config: GeofenceConfig,
+    zones: Vec<(Zone, ZoneState)>,
+}
+
+impl Geofence {
+    pub fn new(config: GeofenceConfig) -> Self {
+        Geofence {
+            config,
+            zones: Vec::new(),
+        }
+    }
+
+    /// Adds a zone and returns its index used in events.
+    pub fn add_zone(&mut self, zone: Zone) -> usize {
+        self.zones.push((zone, ZoneState::default()));
+        self.zones.len() - 1
+    }
+
+    pub fn zone(&self, index: usize) -> Option<&Zone> {
+        self.zones.get(index).map(|(zone, _)| zone)
+    }
+
+    /// Whether the zone is currently considered entered.
+    pub fn is_inside(&self, index: usize) -> bool {
+        self.zones.get(index).is_some_and(|(_, state)| state.inside)
+    }
+
+    /// Processes a fix. Fixes are expected in chronological order. Zones the fix cannot tell
+    /// about, e.g. rooms for a fix without a location, keep their state.
+    pub fn update(&mut self, fix: &Fix) -> Vec<GeofenceEvent> {
+        let now = fix.timestamp;
+        let mut events = Vec::new();
+
+        for (index, (zone, state)) in self.zones.iter_mut().enumerate() {
+            let mut emit = |kind, timestamp| {
+                events.push(GeofenceEvent {
+                    zone: index,
+                    kind,
+                    timestamp,
+                })
+            };
+
+            match zone.contains(fix) {
+                None => {}
+                Some(inside) if inside == state.inside => state.pending_since = None,
+                Some(_) => {
+                    let since = *state.pending_since.get_or_insert(now);
+                    let delay = if state.inside {
+                        self.config.exit_delay
+                    } else {
+                        self.config.enter_delay
+                    };
+
+                    if now.saturating_sub(since) >= delay {
+                        state.inside = !state.inside;
+                        state.pending_since = None;
+                        if state.inside {
+                            state.entered_at = since;
+                            state.dwell_reported = false;
+                            emit(GeofenceEventKind::Enter, since);
+                        } else {
+                            emit(GeofenceEventKind::Exit, since);
+                        }
+                    }
+                }
+            }
+
+            if state.inside
+                && !state.dwell_reported
+                && now.saturating_sub(state.entered_at) >= self.config.dwell_time
+            {
+                state.dwell_reported = true;
+                emit(
+                    GeofenceEventKind::Dwell,
+                    state.entered_at + self.config.dwell_time,
+                );
+            }
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HG_E: Location = Location {
+        building: Building::HG,
+        floor: "E",
+        room: "1",
+    };
+    const HG_F: Location = Location {
+        building: Building::HG,
+        floor: "F",
+        room: "1",
+    };
+
+    fn fix(location: Option<Location>, timestamp: u64) -> Fix {
+        Fix {
+            timestamp,
+            position: None,
+            location,
+        }
+    }
+
+    fn kinds(events: &[GeofenceEvent]) -> Vec<(GeofenceEventKind, u64)> {
+        events.iter().map(|e| (e.kind, e.timestamp)).collect()
+    }
+
+    fn hg_e() -> Geofence {
+        let mut geofence = Geofence::default();
+        geofence.add_zone(Zone::Floor {
+            building: Building::HG,
+            floor: "E".to_string(),
+        });
+        geofence
+    }
+
+    #[test]
+    fn enters_and_exits_after_the_delays() {
+        let mut geofence = hg_e();
+        let mut events = Vec::new();
+        for timestamp in (0..=20_000).step_by(1_000) {
+            events.extend(geofence.update(&fix(Some(HG_E), timestamp)));
+        }
+        assert_eq!(kinds(&events), [(GeofenceEventKind::Enter, 0)]);
+
+        events.clear();
+        for timestamp in (21_000..=35_000).step_by(1_000) {
+            events.extend(geofence.update(&fix(Some(HG_F), timestamp)));
+            assert_eq!(geofence.is_inside(0), timestamp < 31_000);
+        }
+        assert_eq!(kinds(&events), [(GeofenceEventKind::Exit, 21_000)]);
+    }
+
+    #[test]
+    fn ignores_short_visits() {
+        let mut geofence = hg_e();
+        geofence.update(&fix(Some(HG_E), 0));
+        geofence.update(&fix(Some(HG_E), 2_000));
+        assert!(geofence.update(&fix(Some(HG_F), 2_500)).is_empty());
+        assert!(geofence.update(&fix(Some(HG_E), 4_000)).is_empty());
+        assert!(!geofence.is_inside(0));
+    }
+
+    #[test]
+    fn reports_dwell_once_per_visit() {
+        let mut geofence = hg_e();
+        let mut events = Vec::new();
+        for timestamp in (0..=120_000).step_by(5_000) {
+            events.extend(geofence.update(&fix(Some(HG_E), timestamp)));
+        }
+        assert_eq!(
+            kinds(&events),
+            [
+                (GeofenceEventKind::Enter, 0),
+                (GeofenceEventKind::Dwell, 60_000)
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_state_without_a_location() {
+        let mut geofence = hg_e();
+        geofence.update(&fix(Some(HG_E), 0));
+        geofence.update(&fix(Some(HG_E), 3_000));
+        assert!(geofence.is_inside(0));
+
+        for timestamp in (4_000..=30_000).step_by(1_000) {
+            assert!(geofence.update(&fix(None, timestamp)).is_empty());
+        }
+        assert!(geofence.is_inside(0));
+    }
+
+    #[test]
+    fn rejects_unknown_room_codes() {
+        assert_eq!(Zone::room_code("HG X nowhere"), None);
+        assert_eq!(Zone::room_code("HG E"), None);
+    }
+}
@@ -0,0 +1,424 @@
+use crate::elevation::ElevationTable;
+use crate::floor::level;
+use crate::{Beacon, Building, Id, BEACONS};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+#[derive(Debug, Clone)]
+pub struct GraphConfig {
+    /// Beacons on the same floor at most this many metres apart are neighbours.
+    pub max_distance: f64,
+    /// Beacons with fewer neighbours are also linked to their closest beacons on the floor, up
+    /// to this many, so sparse areas stay connected.
+    pub min_neighbours: usize,
+    /// Never link beacons on the same floor further apart than this many metres.
+    pub max_link_distance: f64,
+    /// Beacons on adjacent floors at most this many metres apart horizontally are linked, as
+    /// beacons stacked above each other usually mark stairs and elevators.
+    pub vertical_distance: f64,
+    /// Floor heights for the length of vertical links.
+    pub elevations: ElevationTable,
+}
+
+impl Default for GraphConfig {
+    fn default() -> Self {
+        GraphConfig {
+            max_distance: 10.0,
+            min_neighbours: 2,
+            max_link_distance: 25.0,
+            vertical_distance: 2.0,
+            elevations: ElevationTable::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Between beacons on the same floor.
+    Horizontal,
+    /// Between beacons on adjacent floors.
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edge {
+    /// Node at the other end.
+    pub to: usize,
+    /// Length in metres, including the floor height for vertical edges.
+    pub distance: f64,
+    pub kind: EdgeKind,
+}
+
+/// Graph of neighbouring beacons, with one node per beacon.
+///
+/// Nodes are indices into [`BeaconGraph::beacons`]. Edges are undirected and stored in both
+/// directions.
+#[derive(Debug, Clone, Default)]
+pub struct BeaconGraph {
+    beacons: Vec<&'static Beacon>,
+    edges: Vec<Vec<Edge>>,
+    nodes: HashMap<Id, usize>,
+}
+
+impl BeaconGraph {
+    /// Graph of all [`BEACONS`].
+    pub fn new(config: &GraphConfig) -> Self {
+        BeaconGraph::from_beacons(BEACONS.iter(), config)
+    }
+
+    /// Graph of the beacons of one building.
+    pub fn for_building(building: Building, config: &GraphConfig) -> Self {
+        BeaconGraph::from_beacons(
+            BEACONS.iter().filter(|b| b.location.building == building),
+            config,
+        )
+    }
+
+    pub fn from_beacons(
+        beacons: impl IntoIterator<Item = &'static Beacon>,
+        config: &GraphConfig,
+    ) -> Self {
+        let beacons: Vec<&'static Beacon> = beacons.into_iter().collect();
+        let mut graph = BeaconGraph {
+            edges: vec![Vec::new(); beacons.len()],
+            nodes: beacons.iter().enumerate().map(|(i, b)| (b.id, i)).collect(),
+            beacons,
+        };
+
+        let mut floors: HashMap<(Building, &'static str), Vec<usize>> = HashMap::new();
+        for (node, beacon) in graph.beacons.iter().enumerate() {
+            floors
+                .entry((beacon.location.building, beacon.location.floor))
+                .or_default()
+                .push(node);
+        }
+
+        // Link in a fixed order, so the edges of a node are always listed in the same order
+        let mut keys: Vec<(Building, &'static str)> = floors.keys().copied().collect();
+        keys.sort_by_key(|(building, floor)| (building.as_ref().to_string(), *floor));
+        for key in &keys {
+            graph.link_floor(&floors[key], config);
+        }
+
+        let mut buildings: Vec<(Building, Vec<(f64, &'static str)>)> = Vec::new();
+        for &(building, floor) in &keys {
+            let Some(level) = level(floor) else {
+                continue;
+            };
+            match buildings.last_mut() {
+                Some((last, levels)) if *last == building => levels.push((level, floor)),
+                _ => buildings.push((building, vec![(level, floor)])),
+            }
+        }
+        for (building, mut levels) in buildings {
+            levels.sort_by(|a, b| a.0.total_cmp(&b.0));
+            for pair in levels.windows(2) {
+                graph.link_floors(
+                    &floors[&(building, pair[0].1)],
+                    &floors[&(building, pair[1].1)],
+                    config,
+                );
+            }
+        }
+
+        graph
+    }
+
+    fn link_floor(&mut self, nodes: &[usize], config: &GraphConfig) {
+        for &a in nodes {
+            let mut candidates: Vec<(f64, usize)> = nodes
+                .iter()
+                .filter(|&&b| b != a)
+                .map(|&b| {
+                    (
+                        self.beacons[a].position.distance(&self.beacons[b].position),
+                        b,
+                    )
+                })
+                .filter(|(distance, _)| *distance <= config.max_link_distance)
+                .collect();
+            candidates.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+            for (rank, (distance, b)) in candidates.into_iter().enumerate() {
+                if distance > config.max_distance && rank >= config.min_neighbours {
+                    break;
+                }
+                self.add_edge(a, b, distance, EdgeKind::Horizontal);
+            }
+        }
+    }
+
+    fn link_floors(&mut self, lower: &[usize], upper: &[usize], config: &GraphConfig) {
+        for &a in lower {
+            for &b in upper {
+                let (beacon_a, beacon_b) = (self.beacons[a], self.beacons[b]);
+                let horizontal = beacon_a.position.distance(&beacon_b.position);
+                if horizontal > config.vertical_distance {
+                    continue;
+                }
+                let altitudes = (
+                    config.elevations.location_altitude(&beacon_a.location),
+                    config.elevations.location_altitude(&beacon_b.location),
+                );
+                let distance = match altitudes {
+                    (Some(x), Some(y)) => horizontal.hypot(x - y),
+                    _ => horizontal,
+                };
+                self.add_edge(a, b, distance, EdgeKind::Vertical);
+            }
+        }
+    }
+
+    /// Links two nodes, unless they are already linked.
+    pub fn add_edge(&mut self, a: usize, b: usize, distance: f64, kind: EdgeKind) {
+        if self.edges[a].iter().any(|e| e.to == b) {
+            return;
+        }
+        self.edges[a].push(Edge {
+            to: b,
+            distance,
+            kind,
+        });
+        self.edges[b].push(Edge {
+            to: a,
+            distance,
+            kind,
+        });
+    }
+
+    /// Removes the link between two nodes, if any.
+    pub fn remove_edge(&mut self, a: usize, b: usize) {
+        self.edges[a].retain(|e| e.to != b);
+        self.edges[b].retain(|e| e.to != a);
+    }
+
+    pub fn len(&self) -> usize {
+        self.beacons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.beacons.is_empty()
+    }
+
+    pub fn beacons(&self) -> &[&'static Beacon] {
+        &self.beacons
+    }
+
+    pub fn beacon(&self, node: usize) -> &'static Beacon {
+        self.beacons[node]
+    }
+
+    /// Node of the beacon with `id`, if it is in the graph.
+    pub fn node(&self, id: &Id) -> Option<usize> {
+        self.nodes.get(id).copied()
+    }
+
+    pub fn edges(&self, node: usize) -> &[Edge] {
+        &self.edges[node]
+    }
+
+    /// Neighbouring beacons of `beacon` with the distance to them.
+    pub fn neighbours(&self, beacon: &Beacon) -> Vec<(&'static Beacon, f64)> {
+        self.node(&beacon.id)
+            .map(|node| {
+                self.edges[node]
+                    .iter()
+                    .map(|e| (self.beacons[e.to], e.distance))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn are_neighbours(&self, a: &Beacon, b: &Beacon) -> bool {
+        match (self.node(&a.id), self.node(&b.id)) {
+            (Some(a), Some(b)) => self.edges[a].iter().any(|e| e.to == b),
+            _ => false,
+        }
+    }
+
+    /// Breadth-first traversal from `start`, every reached node with its number of hops.
+    pub fn breadth_first(&self, start: usize, max_hops: usize) -> Vec<(usize, usize)> {
+        let mut hops = vec![usize::MAX; self.len()];
+        let mut queue = VecDeque::from([start]);
+        let mut reached = Vec::new();
+        hops[start] = 0;
+
+        while let Some(node) = queue.pop_front() {
+            reached.push((node, hops[node]));
+            if hops[node] == max_hops {
+                continue;
+            }
+            for edge in &self.edges[node] {
+                if hops[edge.to] == usize::MAX {
+                    hops[edge.to] = hops[node] + 1;
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+        reached
+    }
+
+    /// Every node reachable from `start` along edges within `max_distance` metres, with its
+    /// shortest distance, closest first.
+    pub fn within(&self, start: usize, max_distance: f64) -> Vec<(usize, f64)> {
+        let distances = self.shortest_distances(start, max_distance);
+        let mut reached: Vec<(usize, f64)> = distances
+            .into_iter()
+            .enumerate()
+            .filter(|(_, d)| d.is_finite())
+            .collect();
+        reached.sort_by(|a, b| a.1.total_cmp(&b.1));
+        reached
+    }
+
+    /// Shortest distance along edges from `start` to every node, infinite for nodes further
+    /// than `max_distance` or not connected at all.
+    pub fn shortest_distances(&self, start: usize, max_distance: f64) -> Vec<f64> {
+        let mut distances = vec![f64::INFINITY; self.len()];
+        let mut heap = BinaryHeap::from([Visit {
+            distance: 0.0,
+            node: start,
+        }]);
+        distances[start] = 0.0;
+
+        while let Some(Visit { distance, node }) = heap.pop() {
+            if distance > distances[node] {
+                continue;
+            }
+            for edge in &self.edges[node] {
+                let next = distance + edge.distance;
+                if next < distances[edge.to] && next <= max_distance {
+                    distances[edge.to] = next;
+                    heap.push(Visit {
+                        distance: next,
+                        node: edge.to,
+                    });
+                }
+            }
+        }
+        distances
+    }
+
+    /// Connected components, largest first. Several components on one floor hint at gaps in
+    /// the beacon coverage.
+    pub fn components(&self) -> Vec<Vec<usize>> {
+        let mut seen = vec![false; self.len()];
+        let mut components = Vec::new();
+        for start in 0..self.len() {
+            if seen[start] {
+                continue;
+            }
+            let component: Vec<usize> = self
+                .breadth_first(start, usize::MAX)
+                .into_iter()
+                .map(|(node, _)| node)
+                .collect();
+            for &node in &component {
+                seen[node] = true;
+            }
+            components.push(component);
+        }
+        components.sort_by_key(|c| std::cmp::Reverse(c.len()));
+        components
+    }
+}
+
+/// Entry of the Dijkstra queue, ordered so the closest node is popped first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Visit {
+    pub distance: f64,
+    pub node: usize,
+}
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .distance
+            .total_cmp(&self.distance)
+            .then(self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{beacon, offset};
+
+    /// A corridor on floor E with beacons 5 metres apart, a beacon out of reach at its end and
+    /// one on floor F above its start.
+    fn graph() -> BeaconGraph {
+        let beacons = [
+            beacon(1, Building::HG, "E", "1", offset(0.0, 0.0)),
+            beacon(2, Building::HG, "E", "1", offset(5.0, 0.0)),
+            beacon(3, Building::HG, "E", "1", offset(10.0, 0.0)),
+            beacon(4, Building::HG, "E", "1", offset(50.0, 0.0)),
+            beacon(5, Building::HG, "F", "1", offset(0.5, 0.0)),
+        ];
+        BeaconGraph::from_beacons(beacons, &GraphConfig::default())
+    }
+
+    fn edges(graph: &BeaconGraph, node: usize) -> Vec<(usize, EdgeKind)> {
+        let mut edges: Vec<(usize, EdgeKind)> =
+            graph.edges(node).iter().map(|e| (e.to, e.kind)).collect();
+        edges.sort_by_key(|(to, _)| *to);
+        edges
+    }
+
+    #[test]
+    fn links_neighbours_on_a_floor_and_between_floors() {
+        let graph = graph();
+        assert_eq!(
+            edges(&graph, 0),
+            [
+                (1, EdgeKind::Horizontal),
+                (2, EdgeKind::Horizontal),
+                (4, EdgeKind::Vertical)
+            ]
+        );
+        assert!(edges(&graph, 3).is_empty());
+
+        // The vertical link spans one storey of the default elevations
+        let vertical = graph.edges(0).iter().find(|e| e.to == 4).unwrap();
+        assert!((vertical.distance - 3.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn finds_shortest_distances_and_hops() {
+        let graph = graph();
+        let distances = graph.shortest_distances(4, f64::INFINITY);
+        assert!((distances[2] - (3.5f64.hypot(0.5) + 10.0)).abs() < 0.01);
+        assert!(distances[3].is_infinite());
+
+        let within: Vec<usize> = graph.within(0, 6.0).into_iter().map(|(n, _)| n).collect();
+        assert_eq!(within, [0, 4, 1]);
+
+        let mut hops = graph.breadth_first(4, 1);
+        hops.sort();
+        assert_eq!(hops, [(0, 1), (4, 0)]);
+    }
+
+    #[test]
+    fn reports_disconnected_beacons_as_components() {
+        let components = graph().components();
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0].len(), 4);
+        assert_eq!(components[1], [3]);
+    }
+
+    #[test]
+    fn removes_edges_in_both_directions() {
+        let mut graph = graph();
+        let (a, b) = (graph.beacon(0), graph.beacon(1));
+        assert!(graph.are_neighbours(a, b));
+        graph.remove_edge(0, 1);
+        assert!(!graph.are_neighbours(a, b) && !graph.are_neighbours(b, a));
+    }
+}
@@ -1,14 +1,52 @@
+pub mod audit;
+pub mod benchmark;
+pub mod classifier;
+pub mod coverage;
+pub mod elevation;
+pub mod estimator;
+pub mod fingerprint;
+pub mod floor;
+pub mod footprint;
+pub mod geo;
+pub mod geofence;
+pub mod graph;
+pub mod matching;
+pub mod particle;
+pub mod planner;
+pub mod radio;
 pub mod resolver;
+mod rng;
+pub mod room;
+pub mod routing;
+pub mod scan;
+pub mod scanlog;
+pub mod sighting;
+pub mod simulator;
+pub mod swiss;
+#[cfg(test)]
+mod testing;
+pub mod textformat;
+pub mod tracking;
+pub mod wayfinding;
 
 include!("buildings.gen.rs");
 
-#[derive(Debug, Clone, Default, Copy)]
+#[derive(Debug, Clone, Default, Copy, PartialEq)]
 pub struct Position {
     pub lat: f64,
     pub lon: f64,
 }
 
-#[derive(Debug, Clone)]
+/// [`Position`] with an altitude in metres, above sea level unless it comes from an
+/// [`ElevationTable`](elevation::ElevationTable) without absolute altitudes for its building.
+#[derive(Debug, Clone, Default, Copy, PartialEq)]
+pub struct Position3D {
+    pub lat: f64,
+    pub lon: f64,
+    pub altitude: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Id {
     pub uuid: &'static str,
     pub major: u16,
@@ -22,7 +60,7 @@ pub struct Beacon {
     pub location: Location,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Location {
     pub building: Building,
     pub floor: &'static str,
@@ -0,0 +1,767 @@
+use crate::elevation::ElevationTable;
+use crate::estimator::Estimate;
+use crate::floor::level;
+use crate::geo::{LocalFrame, Point};
+use crate::graph::{BeaconGraph, Visit};
+use crate::textformat::{commented_fields, format_error, parse, parse_floor, ReadError};
+use crate::{Building, Position};
+use std::collections::{BinaryHeap, HashMap};
+use std::io::BufRead;
+
+/// Graph of walkable places that position estimates are matched onto.
+pub trait WalkableGraph {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn position(&self, node: usize) -> Position;
+
+    /// Building and floor of a node, if known.
+    fn floor(&self, node: usize) -> Option<(Building, &'static str)>;
+
+    /// Nodes linked to `node` with the walking distance to them in metres.
+    fn neighbours(&self, node: usize) -> Vec<(usize, f64)>;
+}
+
+impl WalkableGraph for BeaconGraph {
+    fn len(&self) -> usize {
+        BeaconGraph::len(self)
+    }
+
+    fn position(&self, node: usize) -> Position {
+        self.beacon(node).position
+    }
+
+    fn floor(&self, node: usize) -> Option<(Building, &'static str)> {
+        let location = &self.beacon(node).location;
+        Some((location.building, location.floor))
+    }
+
+    fn neighbours(&self, node: usize) -> Vec<(usize, f64)> {
+        self.edges(node)
+            .iter()
+            .map(|e| (e.to, e.distance))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CorridorNode {
+    pub position: Position,
+    pub floor: Option<(Building, &'static str)>,
+}
+
+/// Error of reading a [`CorridorNetwork`].
+pub type CorridorError = ReadError;
+
+/// Corridor network drawn from floor plans, with undirected edges between nodes.
+///
+/// Read from a text file with tab separated fields and `#` comments, where floors are
+/// resolved against the dataset and `-` marks an unknown floor:
+///
+/// ```text
+/// N <id> <lat> <lon> <building> <floor>
+/// E <id> <id>
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CorridorNetwork {
+    nodes: Vec<CorridorNode>,
+    edges: Vec<Vec<(usize, f64)>>,
+}
+
+impl CorridorNetwork {
+    pub fn new() -> Self {
+        CorridorNetwork::default()
+    }
+
+    /// Adds a node and returns its index.
+    pub fn add_node(&mut self, node: CorridorNode) -> usize {
+        self.nodes.push(node);
+        self.edges.push(Vec::new());
+        self.nodes.len() - 1
+    }
+
+    /// Links two nodes, with the length from their positions and, between floors, the floor
+    /// heights of `elevations`.
+    pub fn add_edge(&mut self, a: usize, b: usize, elevations: &ElevationTable) {
+        let (x, y) = (&self.nodes[a], &self.nodes[b]);
+        let horizontal = x.position.distance(&y.position);
+        let altitude = |node: &CorridorNode| {
+            node.floor
+                .and_then(|(building, floor)| elevations.altitude(building, floor))
+        };
+        let distance = match (altitude(x), altitude(y)) {
+            (Some(x), Some(y)) => horizontal.hypot(x - y),
+            _ => horizontal,
+        };
+        self.edges[a].push((b, distance));
+        self.edges[b].push((a, distance));
+    }
+
+    pub fn nodes(&self) -> &[CorridorNode] {
+        &self.nodes
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, CorridorError> {
+        let elevations = ElevationTable::default();
+        let mut network = CorridorNetwork::new();
+        let mut ids: HashMap<String, usize> = HashMap::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let Some(fields) = commented_fields(&line) else {
+                continue;
+            };
+            match fields.as_slice() {
+                ["N", id, lat, lon, building, floor] => {
+                    let floor = parse_floor(building, floor, index)?;
+                    let node = network.add_node(CorridorNode {
+                        position: Position {
+                            lat: parse(lat, index)?,
+                            lon: parse(lon, index)?,
+                        },
+                        floor,
+                    });
+                    if ids.insert(id.to_string(), node).is_some() {
+                        return Err(format_error(index, &format!("duplicate node `{}`", id)));
+                    }
+                }
+                ["E", a, b] => {
+                    let node = |id: &str| {
+                        ids.get(id)
+                            .copied()
+                            .ok_or_else(|| format_error(index, &format!("unknown node `{}`", id)))
+                    };
+                    let (a, b) = (node(a)?, node(b)?);
+                    network.add_edge(a, b, &elevations);
+                }
+                _ => return Err(format_error(index, "unexpected line")),
+            }
+        }
+        Ok(network)
+    }
+}
+
+impl WalkableGraph for CorridorNetwork {
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn position(&self, node: usize) -> Position {
+        self.nodes[node].position
+    }
+
+    fn floor(&self, node: usize) -> Option<(Building, &'static str)> {
+        self.nodes[node].floor
+    }
+
+    fn neighbours(&self, node: usize) -> Vec<(usize, f64)> {
+        self.edges[node].clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MatcherConfig {
+    /// Edges further than this many metres from an estimate are no candidates for it.
+    pub search_radius: f64,
+    /// Most likely points on edges considered per estimate.
+    pub max_candidates: usize,
+    /// Lower bound of the estimate accuracy in metres, so overconfident estimates do not pin
+    /// the path to the closest edge.
+    pub min_sigma: f64,
+    /// Scale in metres of the difference between the walking distance and the straight-line
+    /// distance of consecutive estimates. Smaller values keep the path straighter.
+    pub beta: f64,
+    /// Log-likelihood lost by a candidate on another floor than the estimate.
+    pub floor_penalty: f64,
+    /// Log-likelihood lost by every floor change between consecutive steps.
+    pub floor_change_cost: f64,
+    /// Walking distances longer than the straight-line distance plus this many metres are
+    /// impossible.
+    pub max_detour: f64,
+}
+
+impl Default for MatcherConfig {
+    fn default() -> Self {
+        MatcherConfig {
+            search_radius: 20.0,
+            max_candidates: 8,
+            min_sigma: 3.0,
+            beta: 5.0,
+            floor_penalty: 4.0,
+            floor_change_cost: 3.0,
+            max_detour: 50.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MatchedStep {
+    /// Nodes of the edge the estimate was snapped onto, the same node twice for a node
+    /// without edges.
+    pub edge: (usize, usize),
+    /// How far along the edge the position lies, from 0 at the first node to 1 at the second.
+    pub fraction: f64,
+    pub position: Position,
+    /// Floor of the closer node of the edge.
+    pub floor: Option<(Building, &'static str)>,
+    /// Posterior probability of the match given all estimates, between 0 and 1.
+    pub confidence: f64,
+    /// Distance in metres from the estimate.
+    pub offset: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatchedPath {
+    /// One step per estimate, `None` where no edge was within the search radius.
+    pub steps: Vec<Option<MatchedStep>>,
+    /// Nodes walked through between the matched steps.
+    pub nodes: Vec<usize>,
+    /// Number of times no walk was possible between consecutive estimates, so matching
+    /// started over.
+    pub breaks: usize,
+}
+
+/// Snaps a sequence of position estimates onto the edges of a [`WalkableGraph`] with a hidden
+/// Markov model.
+///
+/// Candidates of an estimate are the closest points on nearby edges, more likely the closer
+/// they are and on the estimated floor. Transitions prefer walks along the graph about as long
+/// as the straight line between the estimates and few floor changes. Viterbi decoding finds
+/// the most likely sequence of points, forward-backward its confidence per step.
+#[derive(Debug, Clone)]
+pub struct MapMatcher<G> {
+    graph: G,
+    config: MatcherConfig,
+    /// Every edge once as `(a, b, length)`, and nodes without edges as `(a, a, 0)`.
+    segments: Vec<(usize, usize, f64)>,
+}
+
+/// Point on an edge an estimate may be matched to.
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    from: usize,
+    to: usize,
+    fraction: f64,
+    length: f64,
+    position: Position,
+    emission: f64,
+}
+
+impl Candidate {
+    /// Both nodes of the edge with the distance to them.
+    fn ends(&self) -> [(usize, f64); 2] {
+        [
+            (self.from, self.fraction * self.length),
+            (self.to, (1.0 - self.fraction) * self.length),
+        ]
+    }
+
+    fn node(&self) -> usize {
+        if self.fraction <= 0.5 {
+            self.from
+        } else {
+            self.to
+        }
+    }
+}
+
+/// Candidates of one estimate.
+struct Layer {
+    estimate: usize,
+    candidates: Vec<Candidate>,
+    /// Log-likelihood of coming from each candidate of the previous layer, `None` at the start
+    /// of the path or after a break.
+    transitions: Option<Vec<Vec<f64>>>,
+    /// Whether a candidate can be reached from the start of the path.
+    reachable: Vec<bool>,
+}
+
+impl<G: WalkableGraph> MapMatcher<G> {
+    pub fn new(graph: G, config: MatcherConfig) -> Self {
+        let mut segments = Vec::new();
+        for a in 0..graph.len() {
+            let neighbours = graph.neighbours(a);
+            if neighbours.is_empty() {
+                segments.push((a, a, 0.0));
+            }
+            for (b, length) in neighbours {
+                if a < b {
+                    segments.push((a, b, length));
+                }
+            }
+        }
+        MapMatcher {
+            graph,
+            config,
+            segments,
+        }
+    }
+
+    pub fn graph(&self) -> &G {
+        &self.graph
+    }
+
+    pub fn match_estimates(&self, estimates: &[Estimate]) -> MatchedPath {
+        let mut path = MatchedPath {
+            steps: vec![None; estimates.len()],
+            ..Default::default()
+        };
+
+        let mut layers: Vec<Layer> = Vec::new();
+        for (index, estimate) in estimates.iter().enumerate() {
+            let candidates = self.candidates(estimate);
+            if candidates.is_empty() {
+                continue;
+            }
+            let transitions = layers.last().map(|previous| {
+                self.transitions(
+                    previous,
+                    &estimates[previous.estimate],
+                    estimate,
+                    &candidates,
+                )
+            });
+            let reachable = match (layers.last(), &transitions) {
+                (Some(previous), Some(transitions)) => (0..candidates.len())
+                    .map(|j| {
+                        previous
+                            .reachable
+                            .iter()
+                            .zip(transitions)
+                            .any(|(&reachable, t)| reachable && t[j].is_finite())
+                    })
+                    .collect(),
+                _ => vec![true; candidates.len()],
+            };
+            let (transitions, reachable) = if reachable.contains(&true) {
+                (transitions, reachable)
+            } else {
+                path.breaks += 1;
+                self.decode(&layers, estimates, &mut path);
+                layers.clear();
+                (None, vec![true; candidates.len()])
+            };
+            layers.push(Layer {
+                estimate: index,
+                candidates,
+                transitions,
+                reachable,
+            });
+        }
+        self.decode(&layers, estimates, &mut path);
+        path
+    }
+
+    fn candidates(&self, estimate: &Estimate) -> Vec<Candidate> {
+        let floor = estimate.location.map(|l| (l.building, l.floor));
+        let sigma = estimate.accuracy.max(self.config.min_sigma);
+        let frame = LocalFrame::new(estimate.position);
+
+        let mut candidates = Vec::new();
+        for &(from, to, length) in &self.segments {
+            let a = frame.to_local(&self.graph.position(from));
+            let b = frame.to_local(&self.graph.position(to));
+            let (dx, dy) = (b.x - a.x, b.y - a.y);
+            let squared = dx * dx + dy * dy;
+            let fraction = if squared > 0.0 {
+                (-(a.x * dx + a.y * dy) / squared).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let point = Point {
+                x: a.x + fraction * dx,
+                y: a.y + fraction * dy,
+            };
+            let distance = point.x.hypot(point.y);
+            if distance > self.config.search_radius {
+                continue;
+            }
+            let mut candidate = Candidate {
+                from,
+                to,
+                fraction,
+                length,
+                position: frame.to_position(&point),
+                emission: -0.5 * (distance / sigma).powi(2),
+            };
+            if floor.is_some() && self.graph.floor(candidate.node()) != floor {
+                candidate.emission -= self.config.floor_penalty;
+            }
+            candidates.push(candidate);
+        }
+        // Other floors lie right above, so rank by likelihood rather than distance
+        candidates.sort_by(|a, b| b.emission.total_cmp(&a.emission));
+
+        // The end of every edge at a node projects to the same point, keep it once
+        let mut seen_nodes = Vec::new();
+        candidates.retain(|candidate| {
+            if candidate.fraction > 0.0 && candidate.fraction < 1.0 {
+                return true;
+            }
+            let node = candidate.node();
+            let first = !seen_nodes.contains(&node);
+            seen_nodes.push(node);
+            first
+        });
+        candidates.truncate(self.config.max_candidates);
+        candidates
+    }
+
+    fn transitions(
+        &self,
+        previous: &Layer,
+        from: &Estimate,
+        to: &Estimate,
+        candidates: &[Candidate],
+    ) -> Vec<Vec<f64>> {
+        let straight = from.position.distance(&to.position);
+        let limit = straight + self.config.max_detour;
+        let mut walks: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
+        previous
+            .candidates
+            .iter()
+            .map(|p| {
+                candidates
+                    .iter()
+                    .map(|q| match self.connection(p, q, limit, &mut walks) {
+                        Some((walked, _)) => {
+                            -(walked - straight).abs() / self.config.beta
+                                - self.floor_changes(p.node(), q.node())
+                                    * self.config.floor_change_cost
+                        }
+                        None => f64::NEG_INFINITY,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Shortest walk from `p` to `q` within `limit` metres, with the nodes it leaves and enters
+    /// the edges of `p` and `q` at.
+    fn connection(
+        &self,
+        p: &Candidate,
+        q: &Candidate,
+        limit: f64,
+        walks: &mut HashMap<usize, HashMap<usize, f64>>,
+    ) -> Option<(f64, Option<(usize, usize)>)> {
+        let same_edge = (p.from, p.to) == (q.from, q.to);
+        let mut best = same_edge.then(|| ((p.fraction - q.fraction).abs() * p.length, None));
+        for (u, to_u) in p.ends() {
+            let distances = walks.entry(u).or_insert_with(|| self.walk(u, limit).0);
+            for (v, from_v) in q.ends() {
+                if let Some(walked) = distances.get(&v) {
+                    let total = to_u + walked + from_v;
+                    if total <= limit && best.is_none_or(|(b, _)| total < b) {
+                        best = Some((total, Some((u, v))));
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    fn floor_changes(&self, a: usize, b: usize) -> f64 {
+        match (self.graph.floor(a), self.graph.floor(b)) {
+            (Some(x), Some(y)) if x == y => 0.0,
+            (Some((_, x)), Some((_, y))) => match (level(x), level(y)) {
+                (Some(x), Some(y)) => (x - y).abs().ceil().max(1.0),
+                _ => 1.0,
+            },
+            _ => 0.0,
+        }
+    }
+
+    /// Walking distances from `start` up to `limit` metres, with the previous node of each.
+    fn walk(&self, start: usize, limit: f64) -> (HashMap<usize, f64>, HashMap<usize, usize>) {
+        let mut distances = HashMap::from([(start, 0.0)]);
+        let mut previous = HashMap::new();
+        let mut heap = BinaryHeap::from([Visit {
+            distance: 0.0,
+            node: start,
+        }]);
+        while let Some(Visit { distance, node }) = heap.pop() {
+            if distance > distances[&node] {
+                continue;
+            }
+            for (next, length) in self.graph.neighbours(node) {
+                let reached = distance + length;
+                if reached <= limit && distances.get(&next).is_none_or(|d| reached < *d) {
+                    distances.insert(next, reached);
+                    previous.insert(next, node);
+                    heap.push(Visit {
+                        distance: reached,
+                        node: next,
+                    });
+                }
+            }
+        }
+        (distances, previous)
+    }
+
+    /// Decodes a connected run of layers into `path`.
+    fn decode(&self, layers: &[Layer], estimates: &[Estimate], path: &mut MatchedPath) {
+        let Some(last) = layers.last() else {
+            return;
+        };
+        let emissions =
+            |layer: &Layer| -> Vec<f64> { layer.candidates.iter().map(|c| c.emission).collect() };
+
+        // Viterbi with back pointers
+        let mut scores = vec![emissions(&layers[0])];
+        let mut pointers: Vec<Vec<usize>> = vec![Vec::new()];
+        for layer in &layers[1..] {
+            let before = scores.last().unwrap();
+            let transitions = layer.transitions.as_ref().unwrap();
+            let mut score = Vec::with_capacity(layer.candidates.len());
+            let mut pointer = Vec::with_capacity(layer.candidates.len());
+            for (j, candidate) in layer.candidates.iter().enumerate() {
+                let (best, from) = before
+                    .iter()
+                    .enumerate()
+                    .map(|(i, s)| (s + transitions[i][j], i))
+                    .max_by(|a, b| a.0.total_cmp(&b.0))
+                    .unwrap();
+                score.push(best + candidate.emission);
+                pointer.push(from);
+            }
+            scores.push(score);
+            pointers.push(pointer);
+        }
+
+        let mut states = vec![0; layers.len()];
+        states[layers.len() - 1] = argmax(scores.last().unwrap());
+        for t in (1..layers.len()).rev() {
+            states[t - 1] = pointers[t][states[t]];
+        }
+
+        // Forward-backward for the posterior of every candidate
+        let mut forward = vec![emissions(&layers[0])];
+        for layer in &layers[1..] {
+            let before = forward.last().unwrap();
+            let transitions = layer.transitions.as_ref().unwrap();
+            let alpha = layer
+                .candidates
+                .iter()
+                .enumerate()
+                .map(|(j, candidate)| {
+                    log_sum(
+                        before
+                            .iter()
+                            .enumerate()
+                            .map(|(i, a)| a + transitions[i][j]),
+                    ) + candidate.emission
+                })
+                .collect();
+            forward.push(alpha);
+        }
+        let mut backward = vec![vec![0.0; last.candidates.len()]];
+        for t in (1..layers.len()).rev() {
+            let after = backward.last().unwrap();
+            let layer = &layers[t];
+            let transitions = layer.transitions.as_ref().unwrap();
+            let beta =
+                (0..layers[t - 1].candidates.len())
+                    .map(|i| {
+                        log_sum(layer.candidates.iter().enumerate().map(|(j, candidate)| {
+                            transitions[i][j] + candidate.emission + after[j]
+                        }))
+                    })
+                    .collect();
+            backward.push(beta);
+        }
+        backward.reverse();
+        let evidence = log_sum(forward.last().unwrap().iter().copied());
+
+        for (t, layer) in layers.iter().enumerate() {
+            let state = states[t];
+            let candidate = &layer.candidates[state];
+            let estimate = &estimates[layer.estimate];
+            let posterior = (forward[t][state] + backward[t][state] - evidence).exp();
+            path.steps[layer.estimate] = Some(MatchedStep {
+                edge: (candidate.from, candidate.to),
+                fraction: candidate.fraction,
+                position: candidate.position,
+                floor: self.graph.floor(candidate.node()),
+                confidence: posterior.clamp(0.0, 1.0),
+                offset: candidate.position.distance(&estimate.position),
+            });
+
+            if t == 0 {
+                path.nodes.push(candidate.node());
+                continue;
+            }
+            // Walk from the previous step within the same limit as its transitions
+            let previous = &layers[t - 1].candidates[states[t - 1]];
+            let straight = estimates[layers[t - 1].estimate]
+                .position
+                .distance(&estimate.position);
+            let limit = straight + self.config.max_detour;
+            let mut walk = match self.connection(previous, candidate, limit, &mut HashMap::new()) {
+                Some((_, Some((u, v)))) => {
+                    let (_, before) = self.walk(u, limit);
+                    let mut nodes = vec![v];
+                    while let Some(&node) = nodes.last().and_then(|n| before.get(n)) {
+                        nodes.push(node);
+                    }
+                    nodes.reverse();
+                    nodes
+                }
+                _ => Vec::new(),
+            };
+            walk.push(candidate.node());
+            for node in walk {
+                if path.nodes.last() != Some(&node) {
+                    path.nodes.push(node);
+                }
+            }
+        }
+    }
+}
+
+fn argmax(values: &[f64]) -> usize {
+    values
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map_or(0, |(i, _)| i)
+}
+
+/// Logarithm of the sum of the exponentials of `values`, without overflow.
+fn log_sum(values: impl Iterator<Item = f64> + Clone) -> f64 {
+    let max = values.clone().fold(f64::NEG_INFINITY, f64::max);
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    max + values.map(|v| (v - max).exp()).sum::<f64>().ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::offset;
+    use crate::Location;
+
+    /// Network of corridors from `[east, north]` of one end to `[east, north]` of the other, in
+    /// metres and on unknown floors.
+    fn network(corridors: &[[f64; 4]]) -> CorridorNetwork {
+        let mut network = CorridorNetwork::new();
+        for &[ax, ay, bx, by] in corridors {
+            let mut node = |east, north| {
+                network.add_node(CorridorNode {
+                    position: offset(east, north),
+                    floor: None,
+                })
+            };
+            let (a, b) = (node(ax, ay), node(bx, by));
+            network.add_edge(a, b, &ElevationTable::default());
+        }
+        network
+    }
+
+    fn estimate(east: f64, north: f64) -> Estimate {
+        Estimate {
+            position: offset(east, north),
+            accuracy: 3.0,
+            location: None,
+        }
+    }
+
+    fn matched(network: CorridorNetwork, estimates: &[Estimate]) -> MatchedPath {
+        MapMatcher::new(network, MatcherConfig::default()).match_estimates(estimates)
+    }
+
+    #[test]
+    fn snaps_onto_the_corridor() {
+        let network = network(&[[0.0, 0.0, 30.0, 0.0]]);
+        let estimates: Vec<Estimate> = (0..6).map(|i| estimate(i as f64 * 5.0, 2.0)).collect();
+        let path = matched(network, &estimates);
+
+        assert_eq!(path.breaks, 0);
+        for (i, step) in path.steps.iter().enumerate() {
+            let step = step.unwrap();
+            assert_eq!(step.edge, (0, 1));
+            assert!(step.position.distance(&offset(i as f64 * 5.0, 0.0)) < 0.1);
+            assert!((step.offset - 2.0).abs() < 0.1);
+            assert!(step.confidence > 0.5 && step.confidence <= 1.0 + 1e-9);
+        }
+    }
+
+    #[test]
+    fn stays_on_the_walkable_corridor() {
+        // Parallel corridors 8 metres apart, only linked at the far end. The middle estimate is
+        // closer to the upper corridor, but walking there and back is far too long.
+        let mut network = network(&[[0.0, 0.0, 40.0, 0.0], [0.0, 8.0, 40.0, 8.0]]);
+        network.add_edge(1, 3, &ElevationTable::default());
+        let estimates = [
+            estimate(0.0, 1.0),
+            estimate(5.0, 1.0),
+            estimate(10.0, 5.0),
+            estimate(15.0, 1.0),
+            estimate(20.0, 1.0),
+        ];
+        let path = matched(network, &estimates);
+
+        assert_eq!(path.breaks, 0);
+        assert!(path.steps.iter().all(|s| s.unwrap().edge == (0, 1)));
+    }
+
+    #[test]
+    fn starts_over_without_a_walk() {
+        let network = network(&[[0.0, 0.0, 40.0, 0.0], [0.0, 200.0, 40.0, 200.0]]);
+        let estimates = [
+            estimate(10.0, 0.0),
+            estimate(20.0, 0.0),
+            estimate(10.0, 200.0),
+            estimate(20.0, 200.0),
+        ];
+        let path = matched(network, &estimates);
+
+        assert_eq!(path.breaks, 1);
+        let edges: Vec<(usize, usize)> = path.steps.iter().map(|s| s.unwrap().edge).collect();
+        assert_eq!(edges, [(0, 1), (0, 1), (2, 3), (2, 3)]);
+    }
+
+    #[test]
+    fn skips_estimates_away_from_every_corridor() {
+        let network = network(&[[0.0, 0.0, 40.0, 0.0]]);
+        let path = matched(network, &[estimate(10.0, 0.0), estimate(10.0, 50.0)]);
+        assert!(path.steps[0].is_some());
+        assert!(path.steps[1].is_none());
+    }
+
+    #[test]
+    fn prefers_the_estimated_floor() {
+        let mut network = CorridorNetwork::new();
+        let elevations = ElevationTable::default();
+        let mut corridor = |floor| {
+            let mut node = |east| {
+                network.add_node(CorridorNode {
+                    position: offset(east, 0.0),
+                    floor: Some((Building::HG, floor)),
+                })
+            };
+            let (a, b) = (node(0.0), node(20.0));
+            network.add_edge(a, b, &elevations);
+            a
+        };
+        let (e, f) = (corridor("E"), corridor("F"));
+        network.add_edge(e, f, &elevations);
+
+        let on_f = Estimate {
+            location: Some(Location {
+                building: Building::HG,
+                floor: "F",
+                room: "1",
+            }),
+            ..estimate(15.0, 1.0)
+        };
+        let path = matched(network, &[on_f.clone(), on_f]);
+        for step in &path.steps {
+            assert_eq!(step.unwrap().floor, Some((Building::HG, "F")));
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use crate::geo::{LocalFrame, Point};
+use crate::radio::PathLossModel;
+use crate::rng::Rng;
+use crate::sighting::Sighting;
+use crate::Position;
+use std::f64::consts::TAU;
+
+#[derive(Debug, Clone, Copy)]
+pub enum MotionModel {
+    /// Particles move in random directions, `sigma` metres per second in each axis.
+    RandomWalk { sigma: f64 },
+    /// Particles move along the heading passed to [`ParticleFilter::predict`], in radians
+    /// clockwise from north. Without a heading this falls back to a random walk of `speed`.
+    Heading {
+        /// Walking speed in metres per second.
+        speed: f64,
+        /// Standard deviation of the speed in metres per second.
+        speed_sigma: f64,
+        /// Standard deviation of the heading in radians.
+        heading_sigma: f64,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct ParticleFilterConfig {
+    pub particles: usize,
+    pub motion: MotionModel,
+    pub path_loss: PathLossModel,
+    /// Resample once the effective sample size drops below this share of the particles.
+    pub resample_threshold: f64,
+    /// Radius in metres around the first sighted beacons the particles are spread over.
+    pub initial_spread: f64,
+    pub seed: u64,
+}
+
+impl Default for ParticleFilterConfig {
+    fn default() -> Self {
+        ParticleFilterConfig {
+            particles: 500,
+            motion: MotionModel::RandomWalk { sigma: 1.0 },
+            path_loss: PathLossModel::default(),
+            resample_threshold: 0.5,
+            initial_spread: 15.0,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    /// Position in the filter's [`LocalFrame`].
+    pub point: Point,
+    /// Normalised weight, all particles sum up to one.
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleEstimate {
+    pub position: Position,
+    /// Weighted mean of the particles in the filter's [`LocalFrame`].
+    pub mean: Point,
+    /// Weighted covariance of the particles in square metres, `[[xx, xy], [xy, yy]]`.
+    pub covariance: [[f64; 2]; 2],
+}
+
+/// Particle filter tracking a position from RSSI sightings of beacons in [`BEACONS`](crate::BEACONS).
+///
+/// Particles live in a [`LocalFrame`] around the first sightings. Each step moves them with the
+/// [`MotionModel`] and reweights them by how well the distances to the sighted beacons explain
+/// the measured RSSI under the [`PathLossModel`]. With the same seed, the same sightings always
+/// produce the same estimates.
+#[derive(Debug, Clone)]
+pub struct ParticleFilter {
+    config: ParticleFilterConfig,
+    rng: Rng,
+    frame: Option<LocalFrame>,
+    particles: Vec<Particle>,
+    timestamp: Option<u64>,
+}
+
+impl ParticleFilter {
+    pub fn new(config: ParticleFilterConfig) -> Self {
+        ParticleFilter {
+            rng: Rng::new(config.seed),
+            config,
+            frame: None,
+            particles: Vec::new(),
+            timestamp: None,
+        }
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// Frame of the particles, set by the first initialisation.
+    pub fn frame(&self) -> Option<&LocalFrame> {
+        self.frame.as_ref()
+    }
+
+    /// Spreads the particles uniformly over a disc of `radius` metres around `center`.
+    pub fn initialize(&mut self, center: Position, radius: f64) {
+        let frame = *self.frame.get_or_insert_with(|| LocalFrame::new(center));
+        let center = frame.to_local(&center);
+        let weight = 1.0 / self.config.particles.max(1) as f64;
+
+        self.particles = (0..self.config.particles)
+            .map(|_| {
+                let r = radius * self.rng.uniform().sqrt();
+                let angle = TAU * self.rng.uniform();
+                Particle {
+                    point: Point {
+                        x: center.x + r * angle.sin(),
+                        y: center.y + r * angle.cos(),
+                    },
+                    weight,
+                }
+            })
+            .collect();
+    }
+
+    /// Moves all particles by `dt` seconds of the motion model. `heading` is only used by
+    /// [`MotionModel::Heading`].
+    pub fn predict(&mut self, dt: f64, heading: Option<f64>) {
+        let rng = &mut self.rng;
+        for particle in &mut self.particles {
+            let (dx, dy) = match (self.config.motion, heading) {
+                (
+                    MotionModel::Heading {
+                        speed,
+                        speed_sigma,
+                        heading_sigma,
+                    },
+                    Some(heading),
+                ) => {
+                    let distance = (speed + speed_sigma * rng.normal()).max(0.0) * dt;
+                    let heading = heading + heading_sigma * rng.normal();
+                    (distance * heading.sin(), distance * heading.cos())
+                }
+                (MotionModel::Heading { speed, .. }, None) => {
+                    (speed * dt * rng.normal(), speed * dt * rng.normal())
+                }
+                (MotionModel::RandomWalk { sigma }, _) => {
+                    (sigma * dt * rng.normal(), sigma * dt * rng.normal())
+                }
+            };
+            particle.point.x += dx;
+            particle.point.y += dy;
+        }
+    }
+
+    /// Reweights the particles by the likelihood of `sightings` and resamples if needed.
+    pub fn update(&mut self, sightings: &[Sighting]) {
+        let Some(frame) = self.frame else {
+            return;
+        };
+        if sightings.is_empty() || self.particles.is_empty() {
+            return;
+        }
+
+        let beacons: Vec<(Point, f64)> = sightings
+            .iter()
+            .map(|s| (frame.to_local(&s.beacon.position), s.rssi))
+            .collect();
+
+        let log_weights: Vec<f64> = self
+            .particles
+            .iter()
+            .map(|particle| {
+                particle.weight.ln()
+                    + beacons
+                        .iter()
+                        .map(|(beacon, rssi)| {
+                            self.config
+                                .path_loss
+                                .log_likelihood(*rssi, particle.point.distance(beacon))
+                        })
+                        .sum::<f64>()
+            })
+            .collect();
+
+        // Shift by the maximum before exponentiating, so the weights do not underflow
+        let max = log_weights
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        for (particle, log_weight) in self.particles.iter_mut().zip(&log_weights) {
+            particle.weight = (log_weight - max).exp();
+        }
+        self.normalize();
+
+        let effective: f64 = 1.0
+            / self
+                .particles
+                .iter()
+                .map(|p| p.weight * p.weight)
+                .sum::<f64>();
+        if effective < self.config.resample_threshold * self.particles.len() as f64 {
+            self.resample();
+        }
+    }
+
+    /// Runs a full predict and update cycle for a scan and returns the new estimate.
+    ///
+    /// The time step is taken from the sighting timestamps. The first scan initialises the
+    /// particles around the sighted beacons.
+    pub fn step(
+        &mut self,
+        sightings: &[Sighting],
+        heading: Option<f64>,
+    ) -> Option<ParticleEstimate> {
+        let timestamp = sightings.iter().map(|s| s.timestamp).max();
+
+        if self.particles.is_empty() {
+            let center = centroid(sightings)?;
+            self.initialize(center, self.config.initial_spread);
+        } else if let (Some(previous), Some(timestamp)) = (self.timestamp, timestamp) {
+            let dt = timestamp.saturating_sub(previous) as f64 / 1000.0;
+            self.predict(dt, heading);
+        }
+
+        if timestamp.is_some() {
+            self.timestamp = timestamp;
+        }
+        self.update(sightings);
+        self.estimate()
+    }
+
+    /// Weighted mean and covariance of the particles.
+    pub fn estimate(&self) -> Option<ParticleEstimate> {
+        let frame = self.frame?;
+        if self.particles.is_empty() {
+            return None;
+        }
+
+        let mean = self
+            .particles
+            .iter()
+            .fold(Point::default(), |mean, p| Point {
+                x: mean.x + p.weight * p.point.x,
+                y: mean.y + p.weight * p.point.y,
+            });
+
+        let mut covariance = [[0.0; 2]; 2];
+        for p in &self.particles {
+            let (dx, dy) = (p.point.x - mean.x, p.point.y - mean.y);
+            covariance[0][0] += p.weight * dx * dx;
+            covariance[0][1] += p.weight * dx * dy;
+            covariance[1][1] += p.weight * dy * dy;
+        }
+        covariance[1][0] = covariance[0][1];
+
+        Some(ParticleEstimate {
+            position: frame.to_position(&mean),
+            mean,
+            covariance,
+        })
+    }
+
+    fn normalize(&mut self) {
+        let total: f64 = self.particles.iter().map(|p| p.weight).sum();
+        let count = self.particles.len() as f64;
+        for particle in &mut self.particles {
+            particle.weight = if total > 0.0 {
+                particle.weight / total
+            } else {
+                1.0 / count
+            };
+        }
+    }
+
+    /// Systematic resampling.
+    fn resample(&mut self) {
+        let count = self.particles.len();
+        let step = 1.0 / count as f64;
+        let mut target = self.rng.uniform() * step;
+        let mut cumulative = 0.0;
+
+        let mut resampled = Vec::with_capacity(count);
+        for particle in &self.particles {
+            cumulative += particle.weight;
+            while target < cumulative && resampled.len() < count {
+                resampled.push(Particle {
+                    point: particle.point,
+                    weight: step,
+                });
+                target += step;
+            }
+        }
+        // Rounding can leave the last slots empty
+        while resampled.len() < count {
+            resampled.push(Particle {
+                point: self.particles[count - 1].point,
+                weight: step,
+            });
+        }
+        self.particles = resampled;
+    }
+}
+
+fn centroid(sightings: &[Sighting]) -> Option<Position> {
+    if sightings.is_empty() {
+        return None;
+    }
+    let count = sightings.len() as f64;
+    Some(Position {
+        lat: sightings.iter().map(|s| s.beacon.position.lat).sum::<f64>() / count,
+        lon: sightings.iter().map(|s| s.beacon.position.lon).sum::<f64>() / count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{offset, scan, square};
+
+    fn run(seed: u64) -> ParticleEstimate {
+        let beacons = square();
+        let truth = offset(6.0, 12.0);
+        let mut filter = ParticleFilter::new(ParticleFilterConfig {
+            seed,
+            ..Default::default()
+        });
+        let mut estimate = None;
+        for step in 0..10 {
+            estimate = filter.step(&scan(&beacons, &truth, step * 1_000), None);
+        }
+        estimate.unwrap()
+    }
+
+    #[test]
+    fn converges_on_a_standing_device() {
+        let estimate = run(7);
+        assert!(estimate.position.distance(&offset(6.0, 12.0)) < 2.0);
+        // Far tighter than the initial disc of 15 metres, with a variance of 56 m² per axis
+        assert!(estimate.covariance[0][0] + estimate.covariance[1][1] < 20.0);
+    }
+
+    #[test]
+    fn is_deterministic_per_seed() {
+        assert_eq!(run(7).mean, run(7).mean);
+        assert_ne!(run(7).mean, run(8).mean);
+    }
+
+    #[test]
+    fn weights_stay_normalised() {
+        let beacons = square();
+        let mut filter = ParticleFilter::new(ParticleFilterConfig::default());
+        filter.step(&scan(&beacons, &offset(3.0, 3.0), 0), None);
+        let total: f64 = filter.particles().iter().map(|p| p.weight).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+}
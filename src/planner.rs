@@ -0,0 +1,383 @@
+use crate::coverage::{CellSums, Coverage, CoverageConfig};
+use crate::room::Room;
+use crate::swiss::Lv95;
+use crate::{Building, Position, BEACONS};
+use std::io::{self, Write};
+use strum_macros::{AsRefStr, EnumString};
+
+/// What the planner improves with every beacon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Objective {
+    /// Cover as many uncovered cells as possible, then lower the error.
+    Coverage,
+    /// Lower the mean expected positioning error.
+    Accuracy,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PlannerConfig {
+    /// Grid and radio model, the same as for the coverage report.
+    pub coverage: CoverageConfig,
+    pub objective: Objective,
+    /// Error in metres assumed for cells without a position fix, and the most a cell counts
+    /// with.
+    pub uncovered_error: f64,
+    /// Never propose a beacon closer than this many metres to another beacon.
+    pub min_spacing: f64,
+}
+
+impl Default for PlannerConfig {
+    fn default() -> Self {
+        PlannerConfig {
+            coverage: CoverageConfig::default(),
+            objective: Objective::Coverage,
+            uncovered_error: 20.0,
+            min_spacing: 3.0,
+        }
+    }
+}
+
+/// Coverage of a floor as the planner sees it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Score {
+    /// Share of the cells with enough beacons in range.
+    pub covered_share: f64,
+    /// Mean expected error of all cells in metres, counting cells without a position fix with
+    /// [`PlannerConfig::uncovered_error`].
+    pub mean_error: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Proposal {
+    pub position: Position,
+    /// The same position in LV95, to mark it on floor plans.
+    pub lv95: Lv95,
+    /// Room the position is closest to, to find it on site.
+    pub room: Option<&'static Room>,
+    /// Score with this and all earlier proposals installed.
+    pub score: Score,
+}
+
+/// Proposed beacons for a floor, in the order they improve it most.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub building: Building,
+    pub floor: &'static str,
+    /// Number of beacons already on the floor.
+    pub existing: usize,
+    /// Score of the existing beacons.
+    pub before: Score,
+    pub proposals: Vec<Proposal>,
+}
+
+impl Plan {
+    /// Score with all proposals installed.
+    pub fn after(&self) -> Score {
+        self.proposals.last().map_or(self.before, |p| p.score)
+    }
+
+    /// Writes the proposals as a CSV worklist for installers, with WGS84 and LV95 coordinates.
+    pub fn write_worklist<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "number,building,floor,near_room,lat,lon,east,north,covered_share,mean_error"
+        )?;
+        for (index, proposal) in self.proposals.iter().enumerate() {
+            let lv95 = proposal.lv95;
+            writeln!(
+                writer,
+                "{},{},{},{},{:.7},{:.7},{:.2},{:.2},{:.3},{:.2}",
+                index + 1,
+                self.building.as_ref(),
+                self.floor,
+                proposal
+                    .room
+                    .map_or(String::new(), |r| r.location.room.to_string()),
+                proposal.position.lat,
+                proposal.position.lon,
+                lv95.east,
+                lv95.north,
+                proposal.score.covered_share,
+                proposal.score.mean_error
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Cell of the floor inside the footprint.
+struct Cell {
+    column: usize,
+    row: usize,
+    centre: Lv95,
+    position: Position,
+    sums: CellSums,
+}
+
+/// Proposes positions for new beacons on a floor.
+///
+/// Beacons are placed one at a time at the centre of the cell that improves the
+/// [`Objective`] most, given the existing beacons and the ones proposed before. Greedy
+/// placement is not optimal, but every proposal is useful on its own, so a worklist can be
+/// cut short.
+#[derive(Debug, Clone)]
+pub struct Planner {
+    config: PlannerConfig,
+}
+
+impl Planner {
+    pub fn new(config: PlannerConfig) -> Self {
+        Planner { config }
+    }
+
+    /// Up to `count` proposals for a floor, fewer if no further beacon improves it. `None` if
+    /// the building has no footprint.
+    pub fn plan(&self, building: Building, floor: &'static str, count: usize) -> Option<Plan> {
+        let footprint = building.footprint()?;
+        let existing: Vec<Position> = BEACONS
+            .iter()
+            .filter(|b| b.location.building == building && b.location.floor == floor)
+            .map(|b| b.position)
+            .collect();
+        self.plan_in(building, floor, footprint, &existing, count)
+    }
+
+    /// Proposals for a floor within `footprint` with beacons at `existing`. `None` if no cell
+    /// lies inside the footprint.
+    fn plan_in(
+        &self,
+        building: Building,
+        floor: &'static str,
+        footprint: &[Position],
+        existing: &[Position],
+        count: usize,
+    ) -> Option<Plan> {
+        let config = &self.config.coverage;
+        let grid = Coverage::new(*config).floor_in(building, floor, footprint, &[]);
+
+        let mut beacons: Vec<Lv95> = existing.iter().map(Position::to_lv95).collect();
+        let existing = beacons.len();
+
+        // Index of every grid cell into `cells`, to find the cells in range of a candidate
+        let mut index = vec![None; grid.columns * grid.rows];
+        let mut cells = Vec::new();
+        for row in 0..grid.rows {
+            for column in 0..grid.columns {
+                let Some(cell) = grid.cell(column, row) else {
+                    continue;
+                };
+                let centre = grid.centre(column, row);
+                let mut sums = CellSums::default();
+                for beacon in &beacons {
+                    sums.add(config, &centre, beacon);
+                }
+                index[row * grid.columns + column] = Some(cells.len());
+                cells.push(Cell {
+                    column,
+                    row,
+                    centre,
+                    position: cell.position,
+                    sums,
+                });
+            }
+        }
+        if cells.is_empty() {
+            return None;
+        }
+
+        let reach = (config.range() / grid.cell_size).ceil() as usize;
+        // Cells in the square around a cell that holds every cell in range
+        let in_range = |cell: &Cell| -> Vec<usize> {
+            let columns =
+                cell.column.saturating_sub(reach)..(cell.column + reach + 1).min(grid.columns);
+            let mut nearby = Vec::new();
+            for row in cell.row.saturating_sub(reach)..(cell.row + reach + 1).min(grid.rows) {
+                nearby.extend(
+                    columns
+                        .clone()
+                        .filter_map(|column| index[row * grid.columns + column]),
+                );
+            }
+            nearby
+        };
+
+        let before = self.score(&cells);
+        let mut proposals = Vec::new();
+        while proposals.len() < count {
+            let mut best: Option<(f64, usize)> = None;
+            for (candidate, cell) in cells.iter().enumerate() {
+                let spaced = beacons.iter().all(|b| {
+                    (b.east - cell.centre.east).hypot(b.north - cell.centre.north)
+                        >= self.config.min_spacing
+                });
+                if !spaced {
+                    continue;
+                }
+                let gain: f64 = in_range(cell)
+                    .into_iter()
+                    .map(|i| {
+                        let mut sums = cells[i].sums;
+                        sums.add(config, &cells[i].centre, &cell.centre);
+                        self.cost(&cells[i], &cells[i].sums) - self.cost(&cells[i], &sums)
+                    })
+                    .sum();
+                if gain > 1e-9 && best.is_none_or(|(best, _)| gain > best) {
+                    best = Some((gain, candidate));
+                }
+            }
+            let Some((_, candidate)) = best else {
+                break;
+            };
+
+            let centre = cells[candidate].centre;
+            for i in in_range(&cells[candidate]) {
+                let cell = &mut cells[i];
+                cell.sums.add(config, &cell.centre, &centre);
+            }
+            beacons.push(centre);
+
+            let position = cells[candidate].position;
+            proposals.push(Proposal {
+                position,
+                lv95: centre,
+                room: Room::nearest(building, floor, &position),
+                score: self.score(&cells),
+            });
+        }
+
+        Some(Plan {
+            building,
+            floor,
+            existing,
+            before,
+            proposals,
+        })
+    }
+
+    fn error(&self, cell: &Cell, sums: &CellSums) -> f64 {
+        let coverage = sums.cell(cell.position);
+        let uncovered = coverage.beacons < self.config.coverage.min_beacons;
+        match coverage.expected_error {
+            Some(error) if !uncovered => error.min(self.config.uncovered_error),
+            _ => self.config.uncovered_error,
+        }
+    }
+
+    /// Cost of a cell the planner minimises.
+    fn cost(&self, cell: &Cell, sums: &CellSums) -> f64 {
+        let error = self.error(cell, sums);
+        match self.config.objective {
+            Objective::Accuracy => error,
+            Objective::Coverage => {
+                // Covering a cell outweighs any change of the error
+                let uncovered = sums.cell(cell.position).beacons < self.config.coverage.min_beacons;
+                f64::from(u8::from(uncovered)) + error / self.config.uncovered_error * 1e-3
+            }
+        }
+    }
+
+    fn score(&self, cells: &[Cell]) -> Score {
+        let min_beacons = self.config.coverage.min_beacons;
+        let covered = cells
+            .iter()
+            .filter(|c| c.sums.cell(c.position).beacons >= min_beacons)
+            .count();
+        let error: f64 = cells.iter().map(|c| self.error(c, &c.sums)).sum();
+        Score {
+            covered_share: covered as f64 / cells.len() as f64,
+            mean_error: error / cells.len() as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::radio::PathLossModel;
+
+    /// Position `east` and `north` metres from a point on the LV95 grid of 5 metre cells.
+    fn lv95(east: f64, north: f64) -> Position {
+        Position::from(Lv95 {
+            east: 2_683_000.0 + east,
+            north: 1_247_000.0 + north,
+            height: None,
+        })
+    }
+
+    /// Beacons reach the cells next to theirs but not the diagonal ones and one beacon covers
+    /// a cell. Every expected error is above the uncovered error, so once all cells are
+    /// covered no beacon lowers the cost.
+    fn config() -> PlannerConfig {
+        let path_loss = PathLossModel::default();
+        PlannerConfig {
+            coverage: CoverageConfig {
+                cell_size: 5.0,
+                path_loss,
+                min_rssi: path_loss.rssi(6.0),
+                min_beacons: 1,
+            },
+            objective: Objective::Coverage,
+            uncovered_error: 0.01,
+            min_spacing: 3.0,
+        }
+    }
+
+    /// Plans a 20 by 10 metre floor of 4 by 2 cells with a beacon in the south-west cell.
+    fn plan(config: PlannerConfig, count: usize) -> Plan {
+        let footprint = [
+            lv95(0.01, 0.01),
+            lv95(19.99, 0.01),
+            lv95(19.99, 9.99),
+            lv95(0.01, 9.99),
+        ];
+        Planner::new(config)
+            .plan_in(Building::HG, "E", &footprint, &[lv95(2.5, 2.5)], count)
+            .unwrap()
+    }
+
+    /// Asserts that a proposal is at the centre of the cell `east` and `north` metres from
+    /// the grid origin.
+    fn assert_at(proposal: &Proposal, east: f64, north: f64) {
+        assert!((proposal.lv95.east - 2_683_000.0 - east).abs() < 1e-6);
+        assert!((proposal.lv95.north - 1_247_000.0 - north).abs() < 1e-6);
+        assert!(proposal.position.distance(&lv95(east, north)) < 0.01);
+    }
+
+    #[test]
+    fn proposes_the_cell_covering_most_first() {
+        let plan = plan(config(), 1);
+        assert_eq!(plan.existing, 1);
+        assert_eq!(plan.before.covered_share, 3.0 / 8.0);
+        // The north cell of the third column reaches four uncovered cells
+        assert_eq!(plan.proposals.len(), 1);
+        assert_at(&plan.proposals[0], 12.5, 7.5);
+        assert_eq!(plan.after().covered_share, 7.0 / 8.0);
+    }
+
+    #[test]
+    fn stops_once_no_beacon_improves_the_floor() {
+        let plan = plan(config(), 10);
+        // The first cell reaching the last uncovered one, after which the gain is zero
+        assert_eq!(plan.proposals.len(), 2);
+        assert_at(&plan.proposals[0], 12.5, 7.5);
+        assert_at(&plan.proposals[1], 12.5, 2.5);
+        assert_eq!(plan.after().covered_share, 1.0);
+        assert_eq!(plan.after().mean_error, 0.01);
+    }
+
+    #[test]
+    fn keeps_the_spacing() {
+        let config = PlannerConfig {
+            min_spacing: 8.0,
+            ..config()
+        };
+        let plan = plan(config, 10);
+        assert!(!plan.proposals.is_empty());
+        let mut placed = vec![lv95(2.5, 2.5)];
+        for proposal in &plan.proposals {
+            assert!(placed.iter().all(|p| p.distance(&proposal.position) >= 8.0));
+            placed.push(proposal.position);
+        }
+    }
+}
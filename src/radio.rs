@@ -0,0 +1,38 @@
+/// Log-distance path loss model relating RSSI to the distance from a beacon.
+#[derive(Debug, Clone, Copy)]
+pub struct PathLossModel {
+    /// RSSI in dBm at one metre from the beacon.
+    pub rssi_at_1m: f64,
+    /// Path loss exponent, 2 in free space and higher indoors.
+    pub exponent: f64,
+    /// Standard deviation of the measured RSSI around the model in dB.
+    pub sigma: f64,
+}
+
+impl Default for PathLossModel {
+    fn default() -> Self {
+        PathLossModel {
+            rssi_at_1m: -59.0,
+            exponent: 2.2,
+            sigma: 6.0,
+        }
+    }
+}
+
+impl PathLossModel {
+    /// Expected RSSI at `distance` metres. Distances below 10 cm are clamped.
+    pub fn rssi(&self, distance: f64) -> f64 {
+        self.rssi_at_1m - 10.0 * self.exponent * distance.max(0.1).log10()
+    }
+
+    /// Distance in metres at which `rssi` is expected.
+    pub fn distance(&self, rssi: f64) -> f64 {
+        10f64.powf((self.rssi_at_1m - rssi) / (10.0 * self.exponent))
+    }
+
+    /// Log-likelihood of measuring `rssi` at `distance`, up to a constant.
+    pub fn log_likelihood(&self, rssi: f64, distance: f64) -> f64 {
+        let z = (rssi - self.rssi(distance)) / self.sigma;
+        -0.5 * z * z
+    }
+}
@@ -1,7 +1,74 @@
-use crate::Beacon;
+use crate::{Beacon, Building, Location};
+use std::collections::HashMap;
+use std::str::FromStr;
 
-pub fn find_beacon_by_id(uuid: &str, major: u16, minor: u16) -> Option<&Beacon> {
+pub fn find_beacon_by_id(uuid: &str, major: u16, minor: u16) -> Option<&'static Beacon> {
     super::BEACONS
         .iter()
         .find(|x| uuid == x.id.uuid && major == x.id.major && minor == x.id.minor)
 }
+
+/// Beacons by id, for callers resolving many advertisements against a large dataset.
+///
+/// [`find_beacon_by_id`] scans the dataset without allocating. Building the index allocates
+/// once, after which lookups take constant time.
+#[derive(Debug, Clone)]
+pub struct BeaconIndex {
+    beacons: HashMap<(u16, u16), Vec<&'static Beacon>>,
+}
+
+impl BeaconIndex {
+    pub fn new() -> Self {
+        let mut beacons: HashMap<(u16, u16), Vec<&'static Beacon>> = HashMap::new();
+        for beacon in super::BEACONS {
+            beacons
+                .entry((beacon.id.major, beacon.id.minor))
+                .or_default()
+                .push(beacon);
+        }
+        BeaconIndex { beacons }
+    }
+
+    pub fn find(&self, uuid: &str, major: u16, minor: u16) -> Option<&'static Beacon> {
+        self.beacons
+            .get(&(major, minor))?
+            .iter()
+            .copied()
+            .find(|x| uuid == x.id.uuid)
+    }
+}
+
+impl Default for BeaconIndex {
+    fn default() -> Self {
+        BeaconIndex::new()
+    }
+}
+
+/// Finds the location of a room with at least one beacon.
+pub fn find_location(building: Building, floor: &str, room: &str) -> Option<Location> {
+    super::BEACONS
+        .iter()
+        .map(|x| x.location)
+        .find(|x| building == x.building && floor == x.floor && room == x.room)
+}
+
+/// Finds a floor with at least one beacon.
+pub fn find_floor(building: Building, floor: &str) -> Option<&'static str> {
+    super::BEACONS
+        .iter()
+        .map(|x| x.location)
+        .find(|x| building == x.building && floor == x.floor)
+        .map(|x| x.floor)
+}
+
+/// Finds the location of a room by its code, e.g. `HG E 41`.
+pub fn find_room(code: &str) -> Option<Location> {
+    let mut parts = code.split_whitespace();
+    let building = Building::from_str(parts.next()?).ok()?;
+    let floor = parts.next()?;
+    let room = parts.next()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    find_location(building, floor, room)
+}
@@ -0,0 +1,161 @@
+use crate::resolver::{find_room, BeaconIndex};
+use crate::{Beacon, Building, Id, Location, Position};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+include!("rooms.gen.rs");
+
+/// A room with at least one beacon, derived from its beacons by the generator.
+#[derive(Debug, Clone)]
+pub struct Room {
+    pub location: Location,
+    /// Mean position of the beacons in the room.
+    pub centroid: Position,
+    /// Distance in metres from the centroid to the furthest beacon, as an approximate extent.
+    /// Rooms with a single beacon have a radius of 0.
+    pub radius: f64,
+    pub beacon_ids: &'static [Id],
+}
+
+/// Index of [`ROOMS`] by location, built on first use.
+fn index() -> &'static HashMap<Location, usize> {
+    static INDEX: OnceLock<HashMap<Location, usize>> = OnceLock::new();
+    INDEX.get_or_init(|| {
+        ROOMS
+            .iter()
+            .enumerate()
+            .map(|(i, room)| (room.location, i))
+            .collect()
+    })
+}
+
+/// Index of the [`BEACONS`](crate::BEACONS) of the rooms, built on first use.
+fn beacon_index() -> &'static BeaconIndex {
+    static INDEX: OnceLock<BeaconIndex> = OnceLock::new();
+    INDEX.get_or_init(BeaconIndex::new)
+}
+
+/// Room of `rooms` whose centroid is closest to `position`.
+fn closest<'a>(rooms: impl Iterator<Item = &'a Room>, position: &Position) -> Option<&'a Room> {
+    rooms.min_by(|a, b| {
+        a.centroid
+            .distance(position)
+            .total_cmp(&b.centroid.distance(position))
+    })
+}
+
+impl Room {
+    pub fn find(location: &Location) -> Option<&'static Room> {
+        index().get(location).map(|&i| &ROOMS[i])
+    }
+
+    /// Room by its code, e.g. `HG E 41`.
+    pub fn by_code(code: &str) -> Option<&'static Room> {
+        Room::find(&find_room(code)?)
+    }
+
+    /// Rooms on a floor, ordered by room code.
+    pub fn on_floor(building: Building, floor: &str) -> impl Iterator<Item = &'static Room> + '_ {
+        ROOMS
+            .iter()
+            .filter(move |room| room.location.building == building && room.location.floor == floor)
+    }
+
+    /// Room on a floor whose centroid is closest to `position`, to describe where a position
+    /// is on site.
+    pub fn nearest(building: Building, floor: &str, position: &Position) -> Option<&'static Room> {
+        closest(Room::on_floor(building, floor), position)
+    }
+
+    /// Code of the room, e.g. `HG E 41`.
+    pub fn code(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.location.building.as_ref(),
+            self.location.floor,
+            self.location.room
+        )
+    }
+
+    /// Beacons of the room, skipping ids that are not in the dataset.
+    pub fn beacons(&self) -> impl Iterator<Item = &'static Beacon> {
+        let index = beacon_index();
+        self.beacon_ids
+            .iter()
+            .filter_map(|id| index.find(id.uuid, id.major, id.minor))
+    }
+
+    /// Whether `position` lies within the radius of the room, widened by `margin` metres.
+    pub fn contains(&self, position: &Position, margin: f64) -> bool {
+        self.centroid.distance(position) <= self.radius + margin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::offset;
+
+    fn room(name: &'static str, east: f64, radius: f64) -> Room {
+        Room {
+            location: Location {
+                building: Building::HG,
+                floor: "E",
+                room: name,
+            },
+            centroid: offset(east, 0.0),
+            radius,
+            beacon_ids: &[Id {
+                uuid: "not-in-the-dataset",
+                major: 0,
+                minor: 1,
+            }],
+        }
+    }
+
+    #[test]
+    fn finds_the_closest_centroid() {
+        let rooms = [
+            room("1", 0.0, 2.0),
+            room("2", 10.0, 2.0),
+            room("3", 30.0, 2.0),
+        ];
+        let nearest = |east| closest(rooms.iter(), &offset(east, 0.0)).map(|r| r.location.room);
+        assert_eq!(nearest(-5.0), Some("1"));
+        assert_eq!(nearest(6.0), Some("2"));
+        assert_eq!(nearest(100.0), Some("3"));
+        assert_eq!(
+            closest([].iter(), &offset(0.0, 0.0)).map(|r| r.code()),
+            None
+        );
+    }
+
+    #[test]
+    fn widens_the_radius_by_the_margin() {
+        let room = room("1", 0.0, 4.0);
+        assert!(room.contains(&offset(3.9, 0.0), 0.0));
+        assert!(!room.contains(&offset(5.0, 0.0), 0.0));
+        assert!(room.contains(&offset(5.0, 0.0), 1.5));
+        assert!(!room.contains(&offset(0.0, 6.0), 1.5));
+        assert_eq!(room.code(), "HG E 1");
+    }
+
+    #[test]
+    fn skips_beacons_not_in_the_dataset() {
+        assert_eq!(room("1", 0.0, 0.0).beacons().count(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "HG")]
+    fn lists_the_generated_beacons() {
+        let rooms: Vec<&Room> = Room::on_floor(Building::HG, "E").collect();
+        assert!(!rooms.is_empty());
+        for room in rooms {
+            let ids: Vec<Id> = room.beacons().map(|b| b.id).collect();
+            assert_eq!(ids, room.beacon_ids);
+            assert!(room.beacons().all(|b| b.location == room.location));
+            let nearest = Room::nearest(Building::HG, "E", &room.centroid).unwrap();
+            assert_eq!(nearest.centroid.distance(&room.centroid), 0.0);
+        }
+    }
+}
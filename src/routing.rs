@@ -0,0 +1,685 @@
+use crate::floor::level;
+use crate::geo::LocalFrame;
+use crate::graph::{BeaconGraph, EdgeKind, GraphConfig, Visit};
+use crate::resolver::find_room;
+use crate::textformat::{commented_fields, format_error, ReadError};
+use crate::{Building, Location, Position};
+use std::collections::{BinaryHeap, HashMap};
+use std::io::BufRead;
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+pub struct RouterConfig {
+    pub graph: GraphConfig,
+    /// Beacons of different buildings on the same level at most this many metres apart are
+    /// linked, as buildings are mostly connected on the floors where they touch. Buildings
+    /// across a street need a passage in the [`RouteOverrides`].
+    pub passage_distance: f64,
+    /// A position is connected to the beacons within this many metres of it.
+    pub access_distance: f64,
+    /// Metres added for every floor change, so routes do not switch floors for a shortcut.
+    pub floor_change_penalty: f64,
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        RouterConfig {
+            graph: GraphConfig::default(),
+            passage_distance: 8.0,
+            access_distance: 30.0,
+            floor_change_penalty: 10.0,
+        }
+    }
+}
+
+/// How a waypoint is reached from the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Step {
+    /// First waypoint of a route.
+    Start,
+    Walk,
+    Stairs,
+    Elevator,
+    /// Passage to another building.
+    Passage,
+    /// Floor change over stacked beacons, which may be stairs or an elevator.
+    FloorChange,
+}
+
+/// Start or destination of a route.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Endpoint {
+    /// Any beacon in the room.
+    Room(Location),
+    /// A position, optionally restricted to beacons of a floor.
+    Position {
+        position: Position,
+        floor: Option<(Building, &'static str)>,
+    },
+}
+
+impl Endpoint {
+    /// Room by its code, e.g. `HG E 41`.
+    pub fn room(code: &str) -> Option<Self> {
+        find_room(code).map(Endpoint::Room)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouteWaypoint {
+    pub position: Position,
+    /// Location of the beacon at this waypoint, `None` for a position endpoint.
+    pub location: Option<Location>,
+    /// Building and floor, if known.
+    pub floor: Option<(Building, &'static str)>,
+    pub step: Step,
+}
+
+/// Floor change along a route, between waypoints `index - 1` and `index`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FloorChange {
+    pub index: usize,
+    pub from: (Building, &'static str),
+    pub to: (Building, &'static str),
+    pub step: Step,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub waypoints: Vec<RouteWaypoint>,
+    /// Length in metres, without floor change penalties.
+    pub distance: f64,
+}
+
+impl Route {
+    pub fn floor_changes(&self) -> Vec<FloorChange> {
+        let mut changes = Vec::new();
+        for (index, pair) in self.waypoints.windows(2).enumerate() {
+            if let (Some(from), Some(to)) = (pair[0].floor, pair[1].floor) {
+                if from != to {
+                    changes.push(FloorChange {
+                        index: index + 1,
+                        from,
+                        to,
+                        step: pair[1].step,
+                    });
+                }
+            }
+        }
+        changes
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrideKind {
+    Stairs,
+    Elevator,
+    Passage,
+    /// Removes the inferred links between two rooms.
+    Closed,
+}
+
+impl FromStr for OverrideKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stairs" => Ok(OverrideKind::Stairs),
+            "elevator" => Ok(OverrideKind::Elevator),
+            "passage" => Ok(OverrideKind::Passage),
+            "closed" => Ok(OverrideKind::Closed),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Connector between two rooms, linking their closest beacons.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConnectorOverride {
+    pub kind: OverrideKind,
+    pub from: Location,
+    pub to: Location,
+}
+
+/// Error of reading [`RouteOverrides`].
+pub type OverridesError = ReadError;
+
+/// Connectors the beacon positions do not reveal, stored as a text file with tab separated
+/// fields and `#` comments:
+///
+/// ```text
+/// <kind> <from> <to>
+/// ```
+///
+/// `kind` is `stairs`, `elevator`, `passage` or `closed`, and `from` and `to` are room codes.
+/// Room codes contain spaces, so only tabs separate the fields, as in
+/// `"passage\tHG E 33.5\tML E 23  # across the street"`.
+#[derive(Debug, Clone, Default)]
+pub struct RouteOverrides {
+    pub connectors: Vec<ConnectorOverride>,
+}
+
+impl RouteOverrides {
+    pub fn new() -> Self {
+        RouteOverrides::default()
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, OverridesError> {
+        let mut overrides = RouteOverrides::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let Some(fields) = commented_fields(&line) else {
+                continue;
+            };
+            let [kind, from, to] = fields.as_slice() else {
+                return Err(format_error(index, "expected kind, from and to"));
+            };
+            let room = |code: &str| {
+                find_room(code)
+                    .ok_or_else(|| format_error(index, &format!("unknown room `{}`", code)))
+            };
+            overrides.connectors.push(ConnectorOverride {
+                kind: kind
+                    .parse()
+                    .map_err(|_| format_error(index, &format!("unknown kind `{}`", kind)))?,
+                from: room(from)?,
+                to: room(to)?,
+            });
+        }
+        Ok(overrides)
+    }
+}
+
+/// Shortest paths between rooms and positions over the [`BeaconGraph`] of all beacons.
+///
+/// Beacons stand in for walkable space: edges between neighbouring beacons are assumed to be
+/// walkable, vertical edges to be stairs or elevators.
+#[derive(Debug, Clone)]
+pub struct Router {
+    config: RouterConfig,
+    graph: BeaconGraph,
+    steps: HashMap<(usize, usize), Step>,
+    rooms: HashMap<Location, Vec<usize>>,
+}
+
+impl Router {
+    pub fn new(config: RouterConfig) -> Self {
+        Router::with_overrides(config, &RouteOverrides::new())
+    }
+
+    pub fn with_overrides(config: RouterConfig, overrides: &RouteOverrides) -> Self {
+        Router::from_graph(BeaconGraph::new(&config.graph), config, overrides)
+    }
+
+    /// Router on an existing graph, e.g. of a single building.
+    pub fn from_graph(
+        graph: BeaconGraph,
+        config: RouterConfig,
+        overrides: &RouteOverrides,
+    ) -> Self {
+        let mut rooms: HashMap<Location, Vec<usize>> = HashMap::new();
+        for (node, beacon) in graph.beacons().iter().enumerate() {
+            rooms.entry(beacon.location).or_default().push(node);
+        }
+        let mut router = Router {
+            config,
+            graph,
+            steps: HashMap::new(),
+            rooms,
+        };
+        router.link_buildings();
+        for connector in &overrides.connectors {
+            router.apply(connector);
+        }
+        router
+    }
+
+    pub fn graph(&self) -> &BeaconGraph {
+        &self.graph
+    }
+
+    /// Links beacons of different buildings on the same level, found through a grid of cells
+    /// the size of the passage distance.
+    fn link_buildings(&mut self) {
+        let Some(first) = self.graph.beacons().first() else {
+            return;
+        };
+        let frame = LocalFrame::new(first.position);
+        let size = self.config.passage_distance.max(1.0);
+        let cell = |node: usize| {
+            let point = frame.to_local(&self.graph.beacon(node).position);
+            (
+                (point.x / size).floor() as i64,
+                (point.y / size).floor() as i64,
+            )
+        };
+
+        let mut cells: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        for node in 0..self.graph.len() {
+            cells.entry(cell(node)).or_default().push(node);
+        }
+
+        let mut passages = Vec::new();
+        for a in 0..self.graph.len() {
+            let beacon_a = self.graph.beacon(a);
+            let (x, y) = cell(a);
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for &b in cells.get(&(x + dx, y + dy)).into_iter().flatten() {
+                        let beacon_b = self.graph.beacon(b);
+                        if b <= a
+                            || beacon_a.location.building == beacon_b.location.building
+                            || level(beacon_a.location.floor) != level(beacon_b.location.floor)
+                        {
+                            continue;
+                        }
+                        let distance = beacon_a.position.distance(&beacon_b.position);
+                        if distance <= self.config.passage_distance {
+                            passages.push((a, b, distance));
+                        }
+                    }
+                }
+            }
+        }
+        for (a, b, distance) in passages {
+            self.link(a, b, distance, Step::Passage);
+        }
+    }
+
+    fn apply(&mut self, connector: &ConnectorOverride) {
+        let empty = Vec::new();
+        let from = self.rooms.get(&connector.from).unwrap_or(&empty);
+        let to = self.rooms.get(&connector.to).unwrap_or(&empty);
+
+        if connector.kind == OverrideKind::Closed {
+            let pairs: Vec<(usize, usize)> = from
+                .iter()
+                .flat_map(|&a| to.iter().map(move |&b| (a, b)))
+                .collect();
+            for (a, b) in pairs {
+                self.graph.remove_edge(a, b);
+                self.steps.remove(&key(a, b));
+            }
+            return;
+        }
+
+        let closest = from
+            .iter()
+            .flat_map(|&a| to.iter().map(move |&b| (a, b)))
+            .map(|(a, b)| (a, b, self.distance(a, b)))
+            .min_by(|x, y| x.2.total_cmp(&y.2));
+        let Some((a, b, distance)) = closest else {
+            return;
+        };
+        let step = match connector.kind {
+            OverrideKind::Stairs => Step::Stairs,
+            OverrideKind::Elevator => Step::Elevator,
+            _ => Step::Passage,
+        };
+        self.graph.remove_edge(a, b);
+        self.link(a, b, distance, step);
+    }
+
+    fn link(&mut self, a: usize, b: usize, distance: f64, step: Step) {
+        let (beacon_a, beacon_b) = (self.graph.beacon(a), self.graph.beacon(b));
+        let kind = if level(beacon_a.location.floor) != level(beacon_b.location.floor) {
+            EdgeKind::Vertical
+        } else {
+            EdgeKind::Horizontal
+        };
+        self.graph.add_edge(a, b, distance, kind);
+        self.steps.insert(key(a, b), step);
+    }
+
+    /// Distance between two beacons including the difference in floor altitude.
+    fn distance(&self, a: usize, b: usize) -> f64 {
+        let elevations = &self.config.graph.elevations;
+        let (a, b) = (self.graph.beacon(a), self.graph.beacon(b));
+        let horizontal = a.position.distance(&b.position);
+        match (
+            elevations.location_altitude(&a.location),
+            elevations.location_altitude(&b.location),
+        ) {
+            (Some(x), Some(y)) => horizontal.hypot(x - y),
+            _ => horizontal,
+        }
+    }
+
+    fn is_floor_change(&self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.graph.beacon(a), self.graph.beacon(b));
+        a.location.building != b.location.building || a.location.floor != b.location.floor
+    }
+
+    /// Beacons an endpoint connects to, with the distance to them.
+    fn access(&self, endpoint: &Endpoint) -> Vec<(usize, f64)> {
+        match endpoint {
+            Endpoint::Room(location) => self
+                .rooms
+                .get(location)
+                .map(|nodes| nodes.iter().map(|&node| (node, 0.0)).collect())
+                .unwrap_or_default(),
+            Endpoint::Position { position, floor } => self
+                .graph
+                .beacons()
+                .iter()
+                .enumerate()
+                .filter(|(_, beacon)| {
+                    floor.is_none_or(|(building, floor)| {
+                        beacon.location.building == building && beacon.location.floor == floor
+                    })
+                })
+                .map(|(node, beacon)| (node, beacon.position.distance(position)))
+                .filter(|(_, distance)| *distance <= self.config.access_distance)
+                .collect(),
+        }
+    }
+
+    /// Shortest route from `from` to `to`, `None` if either is not near any beacon or they are
+    /// not connected.
+    pub fn route(&self, from: &Endpoint, to: &Endpoint) -> Option<Route> {
+        let sources = self.access(from);
+        let targets = self.access(to);
+
+        let mut costs = vec![f64::INFINITY; self.graph.len()];
+        let mut previous = vec![usize::MAX; self.graph.len()];
+        let mut heap = BinaryHeap::new();
+        for &(node, distance) in &sources {
+            if distance < costs[node] {
+                costs[node] = distance;
+                heap.push(Visit { distance, node });
+            }
+        }
+
+        while let Some(Visit { distance, node }) = heap.pop() {
+            if distance > costs[node] {
+                continue;
+            }
+            for edge in self.graph.edges(node) {
+                let mut next = distance + edge.distance;
+                if edge.kind == EdgeKind::Vertical {
+                    next += self.config.floor_change_penalty;
+                }
+                if next < costs[edge.to] {
+                    costs[edge.to] = next;
+                    previous[edge.to] = node;
+                    heap.push(Visit {
+                        distance: next,
+                        node: edge.to,
+                    });
+                }
+            }
+        }
+
+        let (last, exit) = targets
+            .iter()
+            .copied()
+            .filter(|&(node, _)| costs[node].is_finite())
+            .min_by(|x, y| (costs[x.0] + x.1).total_cmp(&(costs[y.0] + y.1)))?;
+
+        let mut nodes = vec![last];
+        while let Some(&node) = nodes.last() {
+            if previous[node] == usize::MAX {
+                break;
+            }
+            nodes.push(previous[node]);
+        }
+        nodes.reverse();
+
+        let mut waypoints = Vec::new();
+        let mut distance = 0.0;
+        if let Endpoint::Position { position, floor } = from {
+            waypoints.push(RouteWaypoint {
+                position: *position,
+                location: None,
+                floor: *floor,
+                step: Step::Start,
+            });
+            distance += self.graph.beacon(nodes[0]).position.distance(position);
+        }
+        for (i, &node) in nodes.iter().enumerate() {
+            let beacon = self.graph.beacon(node);
+            let step = match (i, waypoints.is_empty()) {
+                (_, true) => Step::Start,
+                (0, false) => Step::Walk,
+                _ => {
+                    let before = nodes[i - 1];
+                    distance += self.distance(before, node);
+                    self.step(before, node)
+                }
+            };
+            waypoints.push(RouteWaypoint {
+                position: beacon.position,
+                location: Some(beacon.location),
+                floor: Some((beacon.location.building, beacon.location.floor)),
+                step,
+            });
+        }
+        if let Endpoint::Position { position, floor } = to {
+            waypoints.push(RouteWaypoint {
+                position: *position,
+                location: None,
+                floor: *floor,
+                step: Step::Walk,
+            });
+            distance += exit;
+        }
+
+        Some(Route {
+            waypoints,
+            distance,
+        })
+    }
+
+    fn step(&self, a: usize, b: usize) -> Step {
+        if let Some(step) = self.steps.get(&key(a, b)) {
+            return *step;
+        }
+        if self.is_floor_change(a, b) {
+            Step::FloorChange
+        } else {
+            Step::Walk
+        }
+    }
+}
+
+fn key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{beacon, offset};
+
+    fn room(floor: &'static str, room: &'static str) -> Location {
+        Location {
+            building: Building::HG,
+            floor,
+            room,
+        }
+    }
+
+    /// Rooms 1 to 3 on floor E along a bend through room 2, and rooms 10 to 12 on floor F in a
+    /// straight line above rooms 1 and 3. The bend is 22.8 metres long, the way over floor F
+    /// 14 metres plus two storeys.
+    fn router(floor_change_penalty: f64, overrides: &RouteOverrides) -> Router {
+        let config = RouterConfig {
+            graph: GraphConfig {
+                max_distance: 12.0,
+                min_neighbours: 0,
+                ..Default::default()
+            },
+            access_distance: 5.0,
+            floor_change_penalty,
+            ..Default::default()
+        };
+        let beacons = [
+            beacon(1, Building::HG, "E", "1", offset(0.0, 0.0)),
+            beacon(2, Building::HG, "E", "2", offset(7.0, 9.0)),
+            beacon(3, Building::HG, "E", "3", offset(14.0, 0.0)),
+            beacon(4, Building::HG, "F", "10", offset(0.0, 1.0)),
+            beacon(5, Building::HG, "F", "11", offset(7.0, 1.0)),
+            beacon(6, Building::HG, "F", "12", offset(14.0, 1.0)),
+        ];
+        let graph = BeaconGraph::from_beacons(beacons, &config.graph);
+        Router::from_graph(graph, config, overrides)
+    }
+
+    fn rooms(route: &Route) -> Vec<&'static str> {
+        route
+            .waypoints
+            .iter()
+            .filter_map(|w| w.location.map(|l| l.room))
+            .collect()
+    }
+
+    fn steps(route: &Route) -> Vec<Step> {
+        route.waypoints.iter().map(|w| w.step).collect()
+    }
+
+    const BEND: f64 = 22.80;
+    /// Length of the vertical links, one storey up and a metre aside.
+    const STOREY: f64 = 3.64;
+
+    #[test]
+    fn stays_on_the_floor_for_a_short_cut() {
+        let router = router(10.0, &RouteOverrides::new());
+        let route = router
+            .route(
+                &Endpoint::Room(room("E", "1")),
+                &Endpoint::Room(room("E", "3")),
+            )
+            .unwrap();
+
+        assert_eq!(rooms(&route), ["1", "2", "3"]);
+        assert_eq!(steps(&route), [Step::Start, Step::Walk, Step::Walk]);
+        assert!((route.distance - BEND).abs() < 0.01);
+        assert!(route.floor_changes().is_empty());
+    }
+
+    #[test]
+    fn takes_the_short_cut_without_a_penalty() {
+        let route = router(0.0, &RouteOverrides::new())
+            .route(
+                &Endpoint::Room(room("E", "1")),
+                &Endpoint::Room(room("E", "3")),
+            )
+            .unwrap();
+
+        assert_eq!(rooms(&route), ["1", "10", "11", "12", "3"]);
+        assert_eq!(
+            steps(&route),
+            [
+                Step::Start,
+                Step::FloorChange,
+                Step::Walk,
+                Step::Walk,
+                Step::FloorChange
+            ]
+        );
+        // The distance leaves out the penalty, which is 0 here anyway
+        assert!((route.distance - (14.0 + 2.0 * STOREY)).abs() < 0.01);
+    }
+
+    #[test]
+    fn names_connectors_of_the_overrides() {
+        let overrides = RouteOverrides {
+            connectors: vec![ConnectorOverride {
+                kind: OverrideKind::Stairs,
+                from: room("E", "1"),
+                to: room("F", "10"),
+            }],
+        };
+        let route = router(10.0, &overrides)
+            .route(
+                &Endpoint::Room(room("E", "1")),
+                &Endpoint::Room(room("F", "11")),
+            )
+            .unwrap();
+
+        assert_eq!(rooms(&route), ["1", "10", "11"]);
+        let changes = route.floor_changes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].index, 1);
+        assert_eq!(
+            (changes[0].from, changes[0].to, changes[0].step),
+            ((Building::HG, "E"), (Building::HG, "F"), Step::Stairs)
+        );
+        assert!((route.distance - (STOREY + 7.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn routes_between_positions() {
+        let floor = Some((Building::HG, "E"));
+        let route = router(10.0, &RouteOverrides::new())
+            .route(
+                &Endpoint::Position {
+                    position: offset(0.0, -1.0),
+                    floor,
+                },
+                &Endpoint::Position {
+                    position: offset(14.0, -1.0),
+                    floor,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(rooms(&route), ["1", "2", "3"]);
+        assert_eq!(
+            steps(&route),
+            [Step::Start, Step::Walk, Step::Walk, Step::Walk, Step::Walk]
+        );
+        let (first, last) = (route.waypoints[0], route.waypoints[4]);
+        assert!(first.location.is_none() && last.location.is_none());
+        assert!(first.position.distance(&offset(0.0, -1.0)) < 0.01);
+        assert!((route.distance - (1.0 + BEND + 1.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn needs_a_beacon_near_the_endpoints() {
+        let router = router(10.0, &RouteOverrides::new());
+        let far = Endpoint::Position {
+            position: offset(100.0, 100.0),
+            floor: None,
+        };
+        assert_eq!(router.route(&Endpoint::Room(room("E", "1")), &far), None);
+    }
+
+    #[test]
+    #[cfg(all(feature = "HG", feature = "ML"))]
+    fn reads_the_documented_example() {
+        let file = "passage\tHG E 33.5\tML E 23  # across the street\n";
+        let overrides = RouteOverrides::read(file.as_bytes()).unwrap();
+        assert_eq!(overrides.connectors.len(), 1);
+
+        let connector = &overrides.connectors[0];
+        assert_eq!(connector.kind, OverrideKind::Passage);
+        assert_eq!(
+            (connector.from.building, connector.from.room),
+            (Building::HG, "33.5")
+        );
+        assert_eq!(
+            (connector.to.building, connector.to.room),
+            (Building::ML, "23")
+        );
+    }
+
+    #[test]
+    fn needs_tabs_between_the_fields() {
+        let file = "# only spaces\npassage HG E 33.5 ML E 23\n";
+        assert!(matches!(
+            RouteOverrides::read(file.as_bytes()),
+            Err(OverridesError::Format { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_kinds() {
+        let file = "ramp\tHG E 33.5\tML E 23\n";
+        let Err(OverridesError::Format { message, .. }) = RouteOverrides::read(file.as_bytes())
+        else {
+            panic!("expected a format error");
+        };
+        assert_eq!(message, "unknown kind `ramp`");
+    }
+}
@@ -0,0 +1,410 @@
+use crate::resolver::find_beacon_by_id;
+use crate::sighting::Sighting;
+use crate::{Beacon, Id};
+use std::collections::VecDeque;
+
+/// A raw advertisement as received by a scanner.
+#[derive(Debug, Clone, Copy)]
+pub struct Advertisement {
+    pub id: Id,
+    /// Received signal strength in dBm.
+    pub rssi: f64,
+    /// Calibrated transmit power at one metre in dBm, if advertised.
+    pub tx_power: Option<f64>,
+    /// Time of reception in milliseconds.
+    pub timestamp: u64,
+}
+
+/// RSSI statistics of one beacon within a window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RssiStats {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub max: f64,
+    /// Population variance in dB².
+    pub variance: f64,
+    /// Mean advertised transmit power, if any advertisement carried one.
+    pub tx_power: Option<f64>,
+    /// Timestamp of the latest advertisement.
+    pub last_seen: u64,
+}
+
+impl RssiStats {
+    /// Statistics of the advertisements of `id` received in `[start, end)`. `rssi` is scratch
+    /// space for sorting, so no allocation is needed once it has grown to the buffer size.
+    fn collect<'a>(
+        advertisements: impl Iterator<Item = &'a Advertisement> + Clone,
+        id: Id,
+        rssi: &mut Vec<f64>,
+    ) -> Self {
+        let of_id = advertisements.filter(move |a| a.id == id);
+        rssi.clear();
+        rssi.extend(of_id.clone().map(|a| a.rssi));
+        rssi.sort_by(f64::total_cmp);
+
+        let count = rssi.len();
+        let mean = rssi.iter().sum::<f64>() / count as f64;
+        let median = if count % 2 == 1 {
+            rssi[count / 2]
+        } else {
+            (rssi[count / 2 - 1] + rssi[count / 2]) / 2.0
+        };
+        let (tx_power_sum, tx_power_count) = of_id
+            .clone()
+            .filter_map(|a| a.tx_power)
+            .fold((0.0, 0), |(sum, count), tx_power| {
+                (sum + tx_power, count + 1)
+            });
+
+        RssiStats {
+            count,
+            mean,
+            median,
+            max: rssi[count - 1],
+            variance: rssi.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / count as f64,
+            tx_power: (tx_power_count > 0).then(|| tx_power_sum / tx_power_count as f64),
+            last_seen: of_id.map(|a| a.timestamp).max().unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BeaconObservation {
+    pub beacon: &'static Beacon,
+    pub stats: RssiStats,
+}
+
+impl BeaconObservation {
+    /// Summarises the observation as a single sighting with the median RSSI.
+    pub fn to_sighting(&self) -> Sighting {
+        Sighting {
+            beacon: self.beacon,
+            rssi: self.stats.median,
+            timestamp: self.stats.last_seen,
+        }
+    }
+}
+
+/// Advertisements of ids that are not in the dataset.
+#[derive(Debug, Clone)]
+pub struct UnknownObservation {
+    pub id: Id,
+    pub stats: RssiStats,
+}
+
+/// Per-beacon statistics of all advertisements received in `[start, end)`.
+#[derive(Debug, Clone)]
+pub struct ScanWindow {
+    pub start: u64,
+    pub end: u64,
+    pub beacons: Vec<BeaconObservation>,
+    pub unknown: Vec<UnknownObservation>,
+}
+
+impl ScanWindow {
+    /// One sighting per known beacon, see [`BeaconObservation::to_sighting`].
+    pub fn sightings(&self) -> Vec<Sighting> {
+        self.beacons.iter().map(|b| b.to_sighting()).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanAggregatorConfig {
+    /// Length of a window in milliseconds.
+    pub window: u64,
+    /// Milliseconds between the starts of consecutive windows. Windows overlap if this is
+    /// shorter than `window`, and leave gaps if it is longer.
+    pub slide: u64,
+    /// Maximum number of buffered advertisements. When full, the oldest advertisement is
+    /// dropped. Together with [`ScanAggregator::push_with`] and [`ScanAggregator::flush_with`],
+    /// the aggregator then never allocates after construction.
+    pub capacity: Option<usize>,
+}
+
+impl Default for ScanAggregatorConfig {
+    fn default() -> Self {
+        ScanAggregatorConfig {
+            window: 2_000,
+            slide: 1_000,
+            capacity: None,
+        }
+    }
+}
+
+/// Buffers advertisements and emits per-beacon statistics for sliding time windows.
+///
+/// A window is emitted as soon as an advertisement at or after its end arrives, or on
+/// [`ScanAggregator::flush`]. Windows without advertisements are skipped. Advertisements are
+/// expected in chronological order; late ones for already emitted windows are dropped.
+#[derive(Debug, Clone)]
+pub struct ScanAggregator {
+    config: ScanAggregatorConfig,
+    buffer: VecDeque<Advertisement>,
+    window_start: Option<u64>,
+    dropped: usize,
+    /// Reused for every emitted window.
+    window: ScanWindow,
+    /// Scratch space for the RSSI of one beacon.
+    rssi: Vec<f64>,
+}
+
+impl ScanAggregator {
+    pub fn new(mut config: ScanAggregatorConfig) -> Self {
+        config.window = config.window.max(1);
+        config.slide = config.slide.max(1);
+        // A window holds at most every buffered advertisement, each of a different id
+        let capacity = config.capacity.unwrap_or(0);
+        ScanAggregator {
+            buffer: VecDeque::with_capacity(capacity),
+            window_start: None,
+            dropped: 0,
+            window: ScanWindow {
+                start: 0,
+                end: 0,
+                beacons: Vec::with_capacity(capacity),
+                unknown: Vec::with_capacity(capacity),
+            },
+            rssi: Vec::with_capacity(capacity),
+            config,
+        }
+    }
+
+    /// Number of advertisements dropped because the buffer was full or they arrived late.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Adds an advertisement and returns the windows it completed.
+    pub fn push(&mut self, advertisement: Advertisement) -> Vec<ScanWindow> {
+        let mut windows = Vec::new();
+        self.push_with(advertisement, |window| windows.push(window.clone()));
+        windows
+    }
+
+    /// Adds an advertisement and passes the windows it completed to `on_window`, without
+    /// allocating if a capacity is configured.
+    pub fn push_with(
+        &mut self,
+        advertisement: Advertisement,
+        mut on_window: impl FnMut(&ScanWindow),
+    ) {
+        let start = *self.window_start.get_or_insert(advertisement.timestamp);
+        if advertisement.timestamp < start {
+            self.dropped += 1;
+            return;
+        }
+
+        while let Some(start) = self.window_start {
+            if advertisement.timestamp < start.saturating_add(self.config.window) {
+                break;
+            }
+            if self.emit(start) {
+                on_window(&self.window);
+            }
+            self.advance(start, Some(advertisement.timestamp));
+        }
+
+        if let Some(capacity) = self.config.capacity {
+            if self.buffer.len() >= capacity {
+                self.dropped += 1;
+                if self.buffer.pop_front().is_none() {
+                    return;
+                }
+            }
+        }
+        self.buffer.push_back(advertisement);
+    }
+
+    /// Emits all remaining windows, e.g. at the end of a recording.
+    pub fn flush(&mut self) -> Vec<ScanWindow> {
+        let mut windows = Vec::new();
+        self.flush_with(|window| windows.push(window.clone()));
+        windows
+    }
+
+    /// Passes all remaining windows to `on_window`, see [`ScanAggregator::push_with`].
+    pub fn flush_with(&mut self, mut on_window: impl FnMut(&ScanWindow)) {
+        while let Some(start) = self.window_start {
+            if self.buffer.is_empty() {
+                break;
+            }
+            if self.emit(start) {
+                on_window(&self.window);
+            }
+            self.advance(start, None);
+        }
+        self.window_start = None;
+    }
+
+    /// Fills `self.window` with the window at `start`, `false` if it has no advertisements.
+    fn emit(&mut self, start: u64) -> bool {
+        let end = start.saturating_add(self.config.window);
+        let in_window = self
+            .buffer
+            .iter()
+            .filter(move |a| (start..end).contains(&a.timestamp));
+
+        let window = &mut self.window;
+        window.start = start;
+        window.end = end;
+        window.beacons.clear();
+        window.unknown.clear();
+        for advertisement in in_window.clone() {
+            let id = advertisement.id;
+            let seen = window.beacons.iter().any(|b| b.beacon.id == id)
+                || window.unknown.iter().any(|u| u.id == id);
+            if seen {
+                continue;
+            }
+
+            let stats = RssiStats::collect(in_window.clone(), id, &mut self.rssi);
+            match find_beacon_by_id(id.uuid, id.major, id.minor) {
+                Some(beacon) => window.beacons.push(BeaconObservation { beacon, stats }),
+                None => window.unknown.push(UnknownObservation { id, stats }),
+            }
+        }
+        !window.beacons.is_empty() || !window.unknown.is_empty()
+    }
+
+    /// Moves to the next window and drops advertisements that no later window contains. Windows
+    /// that cannot contain a buffered advertisement or `next` are skipped. Without either, no
+    /// window is left.
+    fn advance(&mut self, start: u64, next: Option<u64>) {
+        let slide = self.config.slide;
+        let start = start.saturating_add(slide);
+        self.buffer.retain(|a| a.timestamp >= start);
+
+        let Some(earliest) = self.buffer.front().map(|a| a.timestamp).or(next) else {
+            self.window_start = None;
+            return;
+        };
+        let first_containing = earliest.saturating_sub(self.config.window - 1);
+        self.window_start = if first_containing > start {
+            (first_containing - start)
+                .div_ceil(slide)
+                .checked_mul(slide)
+                .and_then(|skip| start.checked_add(skip))
+        } else {
+            Some(start)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn advertisement(minor: u16, rssi: f64, timestamp: u64) -> Advertisement {
+        Advertisement {
+            id: Id {
+                uuid: "not-in-the-dataset",
+                major: 0,
+                minor,
+            },
+            rssi,
+            tx_power: None,
+            timestamp,
+        }
+    }
+
+    fn aggregator(window: u64, slide: u64, capacity: Option<usize>) -> ScanAggregator {
+        ScanAggregator::new(ScanAggregatorConfig {
+            window,
+            slide,
+            capacity,
+        })
+    }
+
+    fn bounds(windows: &[ScanWindow]) -> Vec<(u64, u64)> {
+        windows.iter().map(|w| (w.start, w.end)).collect()
+    }
+
+    #[test]
+    fn window_end_is_exclusive() {
+        let mut aggregator = aggregator(1_000, 1_000, None);
+        assert!(aggregator.push(advertisement(1, -70.0, 0)).is_empty());
+        assert!(aggregator.push(advertisement(1, -60.0, 999)).is_empty());
+
+        let windows = aggregator.push(advertisement(1, -50.0, 1_000));
+        assert_eq!(bounds(&windows), [(0, 1_000)]);
+        let stats = windows[0].unknown[0].stats;
+        assert_eq!((stats.count, stats.median, stats.max), (2, -65.0, -60.0));
+        assert_eq!(stats.last_seen, 999);
+
+        assert_eq!(bounds(&aggregator.flush()), [(1_000, 2_000)]);
+    }
+
+    #[test]
+    fn overlapping_windows_share_advertisements() {
+        let mut aggregator = aggregator(2_000, 1_000, None);
+        let mut windows = Vec::new();
+        for timestamp in [0, 1_500, 2_500] {
+            windows.extend(aggregator.push(advertisement(1, -70.0, timestamp)));
+        }
+        windows.extend(aggregator.flush());
+
+        assert_eq!(
+            bounds(&windows),
+            [(0, 2_000), (1_000, 3_000), (2_000, 4_000)]
+        );
+        let counts: Vec<usize> = windows.iter().map(|w| w.unknown[0].stats.count).collect();
+        assert_eq!(counts, [2, 2, 1]);
+    }
+
+    #[test]
+    fn skips_empty_windows() {
+        let mut aggregator = aggregator(1_000, 1_000, None);
+        aggregator.push(advertisement(1, -70.0, 0));
+        let windows = aggregator.push(advertisement(1, -70.0, 10_500));
+        assert_eq!(bounds(&windows), [(0, 1_000)]);
+        assert_eq!(bounds(&aggregator.flush()), [(10_000, 11_000)]);
+    }
+
+    #[test]
+    fn flushes_with_a_slide_longer_than_the_window() {
+        let mut aggregator = aggregator(1_000, 2_000, None);
+        aggregator.push(advertisement(1, -70.0, 0));
+        aggregator.push(advertisement(2, -70.0, 10));
+
+        let windows = aggregator.flush();
+        assert_eq!(bounds(&windows), [(0, 1_000)]);
+        assert_eq!(windows[0].unknown.len(), 2);
+    }
+
+    #[test]
+    fn drops_advertisements_in_the_gaps_between_windows() {
+        let mut aggregator = aggregator(1_000, 2_000, None);
+        let mut windows = Vec::new();
+        for timestamp in [0, 1_500, 2_500, 4_100] {
+            windows.extend(aggregator.push(advertisement(1, -70.0, timestamp)));
+        }
+        windows.extend(aggregator.flush());
+        assert_eq!(
+            bounds(&windows),
+            [(0, 1_000), (2_000, 3_000), (4_000, 5_000)]
+        );
+    }
+
+    #[test]
+    fn drops_the_oldest_advertisement_when_full() {
+        let mut aggregator = aggregator(1_000, 1_000, Some(2));
+        let mut windows = Vec::new();
+        for (minor, timestamp) in [(1, 0), (2, 10), (3, 20)] {
+            aggregator.push_with(advertisement(minor, -70.0, timestamp), |_| {});
+        }
+        aggregator.flush_with(|window| windows.push(window.clone()));
+
+        let minors: Vec<u16> = windows[0].unknown.iter().map(|u| u.id.minor).collect();
+        assert_eq!(minors, [2, 3]);
+        assert_eq!(aggregator.dropped(), 1);
+    }
+
+    #[test]
+    fn drops_late_advertisements() {
+        let mut aggregator = aggregator(1_000, 1_000, None);
+        aggregator.push(advertisement(1, -70.0, 5_000));
+        aggregator.push(advertisement(1, -70.0, 4_000));
+        assert_eq!(aggregator.dropped(), 1);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::resolver::find_beacon_by_id;
+use crate::sighting::Sighting;
+use crate::textformat::{format_error, parse, parse_floor, read_header, ReadError};
+use crate::{Building, Position};
+use std::io::{self, BufRead, Write};
+
+/// Format version written by [`ScanLog::write`].
+pub const FORMAT_VERSION: u32 = 1;
+
+const MAGIC: &str = "eth-beacons-scanlog";
+
+/// Sightings of one scan together with where the scan was taken.
+#[derive(Debug, Clone)]
+pub struct Observation {
+    pub timestamp: u64,
+    /// Ground truth or estimated position of the scanner.
+    pub position: Position,
+    /// Expected error of `position` in metres, 0 for ground truth.
+    pub accuracy: f64,
+    /// Building and floor of the scanner, if known.
+    pub floor: Option<(Building, &'static str)>,
+    pub sightings: Vec<Sighting>,
+}
+
+/// Error of reading a [`ScanLog`].
+pub type ScanLogError = ReadError;
+
+/// Recorded observations, stored as a text file with tab separated fields:
+///
+/// ```text
+/// eth-beacons-scanlog 1
+/// P <timestamp> <lat> <lon> <accuracy> <building> <floor>
+/// S <timestamp> <uuid> <major> <minor> <rssi>
+/// ```
+///
+/// Every `P` line starts an observation, followed by one `S` line per sighting. Building and
+/// floor are `-` if unknown. Beacons and floors are resolved against the dataset when reading.
+#[derive(Debug, Clone, Default)]
+pub struct ScanLog {
+    pub observations: Vec<Observation>,
+    /// Beacons sighted in the log that are not in the dataset, in the order of their first
+    /// sighting. Their sightings are skipped by [`ScanLog::read`].
+    pub unknown_beacons: Vec<UnknownBeacon>,
+}
+
+/// Id of a sighted beacon that is not in the dataset, e.g. a misconfigured or foreign beacon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownBeacon {
+    pub uuid: String,
+    pub major: u16,
+    pub minor: u16,
+    pub sightings: usize,
+}
+
+impl ScanLog {
+    pub fn new() -> Self {
+        ScanLog::default()
+    }
+
+    pub fn add(&mut self, observation: Observation) {
+        self.observations.push(observation);
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "{}\t{}", MAGIC, FORMAT_VERSION)?;
+        for observation in &self.observations {
+            let (building, floor) = match &observation.floor {
+                Some((building, floor)) => (building.as_ref(), *floor),
+                None => ("-", "-"),
+            };
+            writeln!(
+                writer,
+                "P\t{}\t{}\t{}\t{}\t{}\t{}",
+                observation.timestamp,
+                observation.position.lat,
+                observation.position.lon,
+                observation.accuracy,
+                building,
+                floor
+            )?;
+
+            for sighting in &observation.sightings {
+                let id = &sighting.beacon.id;
+                writeln!(
+                    writer,
+                    "S\t{}\t{}\t{}\t{}\t{}",
+                    sighting.timestamp, id.uuid, id.major, id.minor, sighting.rssi
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a log. Sightings of beacons that are not in the dataset are skipped and their ids
+    /// collected in [`unknown_beacons`](ScanLog::unknown_beacons).
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, ScanLogError> {
+        let mut lines = reader.lines().enumerate();
+        read_header(&mut lines, MAGIC, FORMAT_VERSION)?;
+
+        let mut log = ScanLog::new();
+        for (index, line) in lines {
+            let line = line?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields.as_slice() {
+                [""] => {}
+                ["P", timestamp, lat, lon, accuracy, building, floor] => {
+                    let floor = parse_floor(building, floor, index)?;
+                    log.add(Observation {
+                        timestamp: parse(timestamp, index)?,
+                        position: Position {
+                            lat: parse(lat, index)?,
+                            lon: parse(lon, index)?,
+                        },
+                        accuracy: parse(accuracy, index)?,
+                        floor,
+                        sightings: Vec::new(),
+                    });
+                }
+                ["S", timestamp, uuid, major, minor, rssi] => {
+                    let observation = log
+                        .observations
+                        .last_mut()
+                        .ok_or_else(|| format_error(index, "sighting before first position"))?;
+                    let (major, minor) = (parse(major, index)?, parse(minor, index)?);
+                    let Some(beacon) = find_beacon_by_id(uuid, major, minor) else {
+                        let unknown = &mut log.unknown_beacons;
+                        match unknown
+                            .iter_mut()
+                            .find(|u| (u.uuid.as_str(), u.major, u.minor) == (*uuid, major, minor))
+                        {
+                            Some(beacon) => beacon.sightings += 1,
+                            None => unknown.push(UnknownBeacon {
+                                uuid: uuid.to_string(),
+                                major,
+                                minor,
+                                sightings: 1,
+                            }),
+                        }
+                        continue;
+                    };
+                    observation.sightings.push(Sighting {
+                        beacon,
+                        rssi: parse(rssi, index)?,
+                        timestamp: parse(timestamp, index)?,
+                    });
+                }
+                _ => return Err(format_error(index, "unexpected line")),
+            }
+        }
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_unknown_beacons() {
+        let file = "eth-beacons-scanlog\t1\n\
+                    P\t0\t47.3763\t8.5476\t0\t-\t-\n\
+                    S\t0\tforeign\t1\t2\t-70\n\
+                    S\t0\tforeign\t1\t3\t-70\n\
+                    P\t1000\t47.3763\t8.5476\t0\t-\t-\n\
+                    S\t1000\tforeign\t1\t2\t-75\n";
+        let log = ScanLog::read(file.as_bytes()).unwrap();
+
+        assert_eq!(log.observations.len(), 2);
+        assert!(log.observations.iter().all(|o| o.sightings.is_empty()));
+        let unknown: Vec<(u16, usize)> = log
+            .unknown_beacons
+            .iter()
+            .map(|u| (u.minor, u.sightings))
+            .collect();
+        assert_eq!(unknown, [(2, 2), (3, 1)]);
+    }
+}
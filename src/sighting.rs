@@ -0,0 +1,23 @@
+use crate::resolver::find_beacon_by_id;
+use crate::Beacon;
+
+/// A single advertisement of a beacon in the dataset.
+#[derive(Debug, Clone, Copy)]
+pub struct Sighting {
+    pub beacon: &'static Beacon,
+    /// Received signal strength in dBm.
+    pub rssi: f64,
+    /// Time of reception in milliseconds, on any monotonic clock.
+    pub timestamp: u64,
+}
+
+impl Sighting {
+    /// Resolves an advertisement against [`BEACONS`](crate::BEACONS), `None` for unknown ids.
+    pub fn resolve(uuid: &str, major: u16, minor: u16, rssi: f64, timestamp: u64) -> Option<Self> {
+        find_beacon_by_id(uuid, major, minor).map(|beacon| Sighting {
+            beacon,
+            rssi,
+            timestamp,
+        })
+    }
+}
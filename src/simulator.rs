@@ -0,0 +1,303 @@
+use crate::elevation::ElevationTable;
+use crate::floor::level;
+use crate::radio::PathLossModel;
+use crate::rng::Rng;
+use crate::scanlog::{Observation, ScanLog};
+use crate::sighting::Sighting;
+use crate::{Beacon, Building, Position, BEACONS};
+
+#[derive(Debug, Clone)]
+pub struct SimulatorConfig {
+    /// Mean RSSI by distance, `sigma` is the standard deviation of the simulated noise.
+    pub path_loss: PathLossModel,
+    /// Additional loss in dB for every floor slab between scanner and beacon.
+    pub floor_attenuation: f64,
+    /// Advertisements at or below this RSSI in dBm are not received.
+    pub min_rssi: f64,
+    /// Probability that an otherwise received advertisement is missed.
+    pub dropout: f64,
+    /// Milliseconds between scans.
+    pub interval: u64,
+    /// Beacons further away than this many metres are not simulated.
+    pub max_range: f64,
+    /// Floor heights to add the vertical distance to beacons on other floors, ignored if `None`.
+    pub elevations: Option<ElevationTable>,
+    pub seed: u64,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        SimulatorConfig {
+            path_loss: PathLossModel::default(),
+            floor_attenuation: 15.0,
+            min_rssi: -95.0,
+            dropout: 0.2,
+            interval: 1_000,
+            max_range: 40.0,
+            elevations: None,
+            seed: 0,
+        }
+    }
+}
+
+/// Ground truth point of a simulated path.
+#[derive(Debug, Clone, Copy)]
+pub struct Waypoint {
+    pub timestamp: u64,
+    pub position: Position,
+    pub floor: &'static str,
+}
+
+impl Waypoint {
+    /// Waypoint at the position and on the floor of `beacon`.
+    pub fn at_beacon(beacon: &'static Beacon, timestamp: u64) -> Self {
+        Waypoint {
+            timestamp,
+            position: beacon.position,
+            floor: beacon.location.floor,
+        }
+    }
+}
+
+/// Generates scans along a ground truth path through a building from the [`BEACONS`] around it.
+///
+/// Between waypoints the scanner moves in a straight line at constant speed and changes floor
+/// at the next waypoint. With the same seed, the same path always produces the same scans.
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    config: SimulatorConfig,
+    beacons: &'static [Beacon],
+    rng: Rng,
+}
+
+impl Simulator {
+    pub fn new(config: SimulatorConfig) -> Self {
+        Simulator::with_beacons(config, BEACONS)
+    }
+
+    /// Simulator of other beacons than the dataset, e.g. of a planned installation.
+    pub fn with_beacons(config: SimulatorConfig, beacons: &'static [Beacon]) -> Self {
+        Simulator {
+            rng: Rng::new(config.seed),
+            beacons,
+            config,
+        }
+    }
+
+    /// Scans every [`SimulatorConfig::interval`] from the first to the last waypoint.
+    pub fn simulate(&mut self, building: Building, path: &[Waypoint]) -> ScanLog {
+        let mut log = ScanLog::new();
+        let (Some(first), Some(last)) = (path.first(), path.last()) else {
+            return log;
+        };
+
+        let mut segment = 0;
+        let mut timestamp = first.timestamp;
+        while timestamp <= last.timestamp {
+            while segment + 1 < path.len() && path[segment + 1].timestamp <= timestamp {
+                segment += 1;
+            }
+            let from = &path[segment];
+            let position = match path.get(segment + 1) {
+                Some(to) => {
+                    let t = (timestamp - from.timestamp) as f64
+                        / (to.timestamp - from.timestamp) as f64;
+                    Position {
+                        lat: from.position.lat + t * (to.position.lat - from.position.lat),
+                        lon: from.position.lon + t * (to.position.lon - from.position.lon),
+                    }
+                }
+                None => from.position,
+            };
+
+            log.add(self.scan(building, position, from.floor, timestamp));
+            timestamp += self.config.interval.max(1);
+        }
+        log
+    }
+
+    /// A single scan at `position` on `floor`.
+    pub fn scan(
+        &mut self,
+        building: Building,
+        position: Position,
+        floor: &'static str,
+        timestamp: u64,
+    ) -> Observation {
+        let config = &self.config;
+        let scanner_level = level(floor);
+        let scanner_altitude = config
+            .elevations
+            .as_ref()
+            .and_then(|e| e.altitude(building, floor));
+
+        let mut sightings = Vec::new();
+        for beacon in self.beacons {
+            if beacon.location.building != building {
+                continue;
+            }
+            let horizontal = position.distance(&beacon.position);
+            if horizontal > config.max_range {
+                continue;
+            }
+            let height = match (&config.elevations, scanner_altitude) {
+                (Some(elevations), Some(scanner)) => elevations
+                    .location_altitude(&beacon.location)
+                    .map_or(0.0, |altitude| altitude - scanner),
+                _ => 0.0,
+            };
+            let distance = horizontal.hypot(height);
+
+            let floors = match (scanner_level, level(beacon.location.floor)) {
+                (Some(a), Some(b)) => (a - b).abs().ceil(),
+                _ if beacon.location.floor == floor => 0.0,
+                _ => 1.0,
+            };
+            let rssi = config.path_loss.rssi(distance) - floors * config.floor_attenuation
+                + config.path_loss.sigma * self.rng.normal();
+
+            // Always draw the dropout, so the random sequence does not depend on which beacons
+            // were received
+            let dropped = self.rng.uniform() < config.dropout;
+            if rssi > config.min_rssi && !dropped {
+                sightings.push(Sighting {
+                    beacon,
+                    rssi,
+                    timestamp,
+                });
+            }
+        }
+
+        Observation {
+            timestamp,
+            position,
+            accuracy: 0.0,
+            floor: Some((building, floor)),
+            sightings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::offset;
+    use crate::{Id, Location, ETH_UUID};
+
+    /// Beacons at the corners of a 20 metre square on floor E and one on F above the first.
+    fn beacons() -> &'static [Beacon] {
+        let beacon = |minor, floor, east, north| Beacon {
+            id: Id {
+                uuid: ETH_UUID,
+                major: 0,
+                minor,
+            },
+            position: offset(east, north),
+            location: Location {
+                building: Building::HG,
+                floor,
+                room: "1",
+            },
+        };
+        Box::leak(Box::new([
+            beacon(1, "E", 0.0, 0.0),
+            beacon(2, "E", 20.0, 0.0),
+            beacon(3, "E", 0.0, 20.0),
+            beacon(4, "E", 20.0, 20.0),
+            beacon(5, "F", 0.0, 0.0),
+        ]))
+    }
+
+    fn path() -> [Waypoint; 2] {
+        [
+            Waypoint {
+                timestamp: 0,
+                position: offset(0.0, 10.0),
+                floor: "E",
+            },
+            Waypoint {
+                timestamp: 10_000,
+                position: offset(20.0, 10.0),
+                floor: "E",
+            },
+        ]
+    }
+
+    fn rssi(observations: &[Observation]) -> Vec<Vec<(u16, f64)>> {
+        observations
+            .iter()
+            .map(|o| {
+                o.sightings
+                    .iter()
+                    .map(|s| (s.beacon.id.minor, s.rssi))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn is_deterministic_per_seed() {
+        let run = |seed| {
+            let config = SimulatorConfig {
+                seed,
+                ..Default::default()
+            };
+            Simulator::with_beacons(config, beacons()).simulate(Building::HG, &path())
+        };
+        assert_eq!(rssi(&run(3).observations), rssi(&run(3).observations));
+        assert_ne!(rssi(&run(3).observations), rssi(&run(4).observations));
+    }
+
+    #[test]
+    fn moves_along_the_path() {
+        let log = Simulator::with_beacons(SimulatorConfig::default(), beacons())
+            .simulate(Building::HG, &path());
+
+        assert_eq!(log.observations.len(), 11);
+        let middle = &log.observations[5];
+        assert_eq!(middle.timestamp, 5_000);
+        assert!(middle.position.distance(&offset(10.0, 10.0)) < 0.01);
+        assert_eq!(middle.floor, Some((Building::HG, "E")));
+    }
+
+    #[test]
+    fn attenuates_beacons_on_other_floors() {
+        let config = SimulatorConfig {
+            path_loss: PathLossModel {
+                sigma: 0.0,
+                ..Default::default()
+            },
+            min_rssi: -120.0,
+            dropout: 0.0,
+            ..Default::default()
+        };
+        let observation = Simulator::with_beacons(config.clone(), beacons()).scan(
+            Building::HG,
+            offset(0.0, 10.0),
+            "E",
+            0,
+        );
+
+        let rssi = |minor| {
+            observation
+                .sightings
+                .iter()
+                .find(|s| s.beacon.id.minor == minor)
+                .map(|s| s.rssi)
+        };
+        // Beacons 1 and 5 are 10 metres away horizontally, beacon 5 one floor up
+        let same_floor = rssi(1).unwrap();
+        assert!((same_floor - config.path_loss.rssi(10.0)).abs() < 1e-6);
+        assert!(rssi(5).unwrap() <= same_floor - config.floor_attenuation);
+    }
+
+    #[test]
+    fn drops_every_advertisement_at_full_dropout() {
+        let config = SimulatorConfig {
+            dropout: 1.0,
+            ..Default::default()
+        };
+        let log = Simulator::with_beacons(config, beacons()).simulate(Building::HG, &path());
+        assert!(log.observations.iter().all(|o| o.sightings.is_empty()));
+    }
+}
@@ -0,0 +1,248 @@
+//! Swiss national coordinates, using the approximate formulas published by swisstopo. They are
+//! accurate to about a metre, and heights to about half a metre.
+
+use crate::{Position, Position3D};
+
+/// Coordinates in CH1903+/LV95, in metres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lv95 {
+    pub east: f64,
+    pub north: f64,
+    /// Height above sea level (LN02), if known.
+    pub height: Option<f64>,
+}
+
+/// Coordinates in the legacy CH1903/LV03, in metres. As in LV03, `y` is the easting and `x`
+/// the northing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lv03 {
+    pub y: f64,
+    pub x: f64,
+    /// Height above sea level (LN02), if known.
+    pub height: Option<f64>,
+}
+
+impl Lv95 {
+    /// Converts WGS84 coordinates with an optional ellipsoidal height.
+    pub fn from_wgs84(position: &Position, height: Option<f64>) -> Self {
+        let (phi, lambda) = auxiliary(position);
+
+        let east = 2_600_072.37 + 211_455.93 * lambda
+            - 10_938.51 * lambda * phi
+            - 0.36 * lambda * phi.powi(2)
+            - 44.54 * lambda.powi(3);
+        let north =
+            1_200_147.07 + 308_807.95 * phi + 3_745.25 * lambda.powi(2) + 76.63 * phi.powi(2)
+                - 194.56 * lambda.powi(2) * phi
+                + 119.79 * phi.powi(3);
+
+        Lv95 {
+            east,
+            north,
+            height: height.map(|h| h - 49.55 + 2.73 * lambda + 6.94 * phi),
+        }
+    }
+
+    /// Converts back to WGS84, with the ellipsoidal height if the height is known.
+    ///
+    /// The inverse formulas alone are off by up to half a metre from [`Lv95::from_wgs84`], so
+    /// their result is corrected with the forward formulas until both agree and conversions
+    /// round trip.
+    pub fn to_wgs84(&self) -> (Position, Option<f64>) {
+        let estimate = approximate_wgs84(self.east, self.north);
+        let mut position = estimate;
+        for _ in 0..3 {
+            // The inverse formulas of the coordinates as far beyond these as the forward
+            // formulas miss them give the correction
+            let forward = Lv95::from_wgs84(&position, None);
+            let beyond = approximate_wgs84(
+                2.0 * self.east - forward.east,
+                2.0 * self.north - forward.north,
+            );
+            position = Position {
+                lat: position.lat + beyond.lat - estimate.lat,
+                lon: position.lon + beyond.lon - estimate.lon,
+            };
+        }
+
+        let (phi, lambda) = auxiliary(&position);
+        (
+            position,
+            self.height.map(|h| h + 49.55 - 2.73 * lambda - 6.94 * phi),
+        )
+    }
+
+    pub fn to_lv03(&self) -> Lv03 {
+        Lv03 {
+            y: self.east - 2_000_000.0,
+            x: self.north - 1_000_000.0,
+            height: self.height,
+        }
+    }
+}
+
+impl Lv03 {
+    /// Converts WGS84 coordinates with an optional ellipsoidal height.
+    pub fn from_wgs84(position: &Position, height: Option<f64>) -> Self {
+        Lv95::from_wgs84(position, height).to_lv03()
+    }
+
+    /// Converts back to WGS84, with the ellipsoidal height if the height is known.
+    pub fn to_wgs84(&self) -> (Position, Option<f64>) {
+        self.to_lv95().to_wgs84()
+    }
+
+    pub fn to_lv95(&self) -> Lv95 {
+        Lv95 {
+            east: self.y + 2_000_000.0,
+            north: self.x + 1_000_000.0,
+            height: self.height,
+        }
+    }
+}
+
+impl Position {
+    pub fn to_lv95(&self) -> Lv95 {
+        Lv95::from_wgs84(self, None)
+    }
+
+    pub fn to_lv03(&self) -> Lv03 {
+        Lv03::from_wgs84(self, None)
+    }
+}
+
+impl Position3D {
+    /// LV95 coordinates with the altitude as height, which is only above sea level if the
+    /// altitude is.
+    pub fn to_lv95(&self) -> Lv95 {
+        Lv95 {
+            height: Some(self.altitude),
+            ..self.horizontal().to_lv95()
+        }
+    }
+
+    pub fn to_lv03(&self) -> Lv03 {
+        self.to_lv95().to_lv03()
+    }
+}
+
+impl From<Lv95> for Position {
+    fn from(lv95: Lv95) -> Self {
+        lv95.to_wgs84().0
+    }
+}
+
+impl From<Lv03> for Position {
+    fn from(lv03: Lv03) -> Self {
+        lv03.to_wgs84().0
+    }
+}
+
+/// Auxiliary latitude and longitude of the formulas, in 10000 arc seconds relative to Bern.
+fn auxiliary(position: &Position) -> (f64, f64) {
+    (
+        (position.lat * 3600.0 - 169_028.66) / 10_000.0,
+        (position.lon * 3600.0 - 26_782.5) / 10_000.0,
+    )
+}
+
+/// WGS84 position of LV95 coordinates by the inverse formulas of swisstopo.
+fn approximate_wgs84(east: f64, north: f64) -> Position {
+    // Auxiliary values in 1000 km relative to Bern
+    let y = (east - 2_600_000.0) / 1_000_000.0;
+    let x = (north - 1_200_000.0) / 1_000_000.0;
+
+    let lambda = 2.677_909_4 + 4.728_982 * y + 0.791_484 * y * x + 0.130_6 * y * x.powi(2)
+        - 0.043_6 * y.powi(3);
+    let phi = 16.902_389_2 + 3.238_272 * x
+        - 0.270_978 * y.powi(2)
+        - 0.002_528 * x.powi(2)
+        - 0.044_7 * y.powi(2) * x
+        - 0.014 * x.powi(3);
+
+    Position {
+        lat: phi * 100.0 / 36.0,
+        lon: lambda * 100.0 / 36.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn degrees(degrees: f64, minutes: f64, seconds: f64) -> f64 {
+        degrees + minutes / 60.0 + seconds / 3600.0
+    }
+
+    /// The old observatory in Bern, origin of the Swiss coordinates.
+    fn bern() -> Position {
+        Position {
+            lat: degrees(46.0, 57.0, 8.66),
+            lon: degrees(7.0, 26.0, 22.50),
+        }
+    }
+
+    #[test]
+    fn maps_bern_to_the_origin() {
+        let lv95 = bern().to_lv95();
+        assert!((lv95.east - 2_600_072.37).abs() < 0.01);
+        assert!((lv95.north - 1_200_147.07).abs() < 0.01);
+        assert_eq!(lv95.height, None);
+    }
+
+    #[test]
+    fn converts_the_swisstopo_example() {
+        // Example of the swisstopo documentation of the formulas
+        let position = Position {
+            lat: degrees(46.0, 2.0, 38.87),
+            lon: degrees(8.0, 43.0, 49.79),
+        };
+        let lv95 = Lv95::from_wgs84(&position, Some(650.60));
+        assert!((lv95.east - 2_699_999.76).abs() < 0.01);
+        assert!((lv95.north - 1_099_999.97).abs() < 0.01);
+        assert!((lv95.height.unwrap() - 600.05).abs() < 0.01);
+    }
+
+    #[test]
+    fn round_trips_through_lv95() {
+        for position in [
+            bern(),
+            Position {
+                lat: 47.3763,
+                lon: 8.5476,
+            },
+            Position {
+                lat: 46.2,
+                lon: 6.15,
+            },
+        ] {
+            let (back, height) = Lv95::from_wgs84(&position, Some(500.0)).to_wgs84();
+            assert!((back.lat - position.lat).abs() < 1e-6, "{:?}", back);
+            assert!((back.lon - position.lon).abs() < 1e-6, "{:?}", back);
+            assert!((height.unwrap() - 500.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn offsets_lv03_from_lv95() {
+        let lv95 = Lv95 {
+            east: 2_683_111.0,
+            north: 1_247_877.0,
+            height: Some(470.0),
+        };
+        let lv03 = lv95.to_lv03();
+        assert_eq!(
+            lv03,
+            Lv03 {
+                y: 683_111.0,
+                x: 247_877.0,
+                height: Some(470.0),
+            }
+        );
+        assert_eq!(lv03.to_lv95(), lv95);
+
+        let position = bern();
+        assert_eq!(position.to_lv03().y, position.to_lv95().east - 2_000_000.0);
+        assert_eq!(position.to_lv03().x, position.to_lv95().north - 1_000_000.0);
+    }
+}
@@ -0,0 +1,70 @@
+//! Beacons for unit tests, independent of the buildings enabled by features.
+
+use crate::geo::{LocalFrame, Point};
+use crate::radio::PathLossModel;
+use crate::sighting::Sighting;
+use crate::{Beacon, Building, Id, Location, Position, ETH_UUID};
+
+pub(crate) const ORIGIN: Position = Position {
+    lat: 47.3763,
+    lon: 8.5476,
+};
+
+/// Position `east` and `north` metres from [`ORIGIN`].
+pub(crate) fn offset(east: f64, north: f64) -> Position {
+    LocalFrame::new(ORIGIN).to_position(&Point { x: east, y: north })
+}
+
+/// A beacon that lives for the rest of the test run, like the ones in [`crate::BEACONS`].
+pub(crate) fn beacon(
+    minor: u16,
+    building: Building,
+    floor: &'static str,
+    room: &'static str,
+    position: Position,
+) -> &'static Beacon {
+    Box::leak(Box::new(Beacon {
+        id: Id {
+            uuid: ETH_UUID,
+            major: 0,
+            minor,
+        },
+        position,
+        location: Location {
+            building,
+            floor,
+            room,
+        },
+    }))
+}
+
+pub(crate) fn sighting(beacon: &'static Beacon, rssi: f64, timestamp: u64) -> Sighting {
+    Sighting {
+        beacon,
+        rssi,
+        timestamp,
+    }
+}
+
+/// Four beacons on floor E of HG, at the corners of a square of 20 metres from [`ORIGIN`].
+pub(crate) fn square() -> Vec<&'static Beacon> {
+    [(0.0, 0.0), (20.0, 0.0), (0.0, 20.0), (20.0, 20.0)]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (east, north))| beacon(i as u16 + 1, Building::HG, "E", "1", offset(east, north)))
+        .collect()
+}
+
+/// Noise-free sightings of `beacons` at `position`, with the RSSI of the default
+/// [`PathLossModel`].
+pub(crate) fn scan(
+    beacons: &[&'static Beacon],
+    position: &Position,
+    timestamp: u64,
+) -> Vec<Sighting> {
+    let path_loss = PathLossModel::default();
+    beacons
+        .iter()
+        .map(|b| sighting(b, path_loss.rssi(b.position.distance(position)), timestamp))
+        .collect()
+}
@@ -0,0 +1,136 @@
+//! Helpers shared by the line based text formats: fingerprints, scan logs, route overrides and
+//! corridor networks.
+use crate::resolver::find_floor;
+use crate::Building;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    /// The file was written by an unknown format version.
+    Version(u32),
+    /// Line `line` (1-based) could not be read.
+    Format {
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(err) => write!(f, "{}", err),
+            ReadError::Version(version) => write!(f, "unsupported format version {}", version),
+            ReadError::Format { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        ReadError::Io(err)
+    }
+}
+
+/// Error of the line with the 0-based `index`.
+pub(crate) fn format_error(index: usize, message: &str) -> ReadError {
+    ReadError::Format {
+        line: index + 1,
+        message: message.to_string(),
+    }
+}
+
+/// Parses a field of the line with the 0-based `index`.
+pub(crate) fn parse<T: FromStr>(value: &str, index: usize) -> Result<T, ReadError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format_error(index, &format!("invalid number `{}`", value)))
+}
+
+/// Parses the building and floor fields of the line with the 0-based `index`, `-` and `-` if
+/// unknown. The floor is resolved against the dataset.
+pub(crate) fn parse_floor(
+    building: &str,
+    floor: &str,
+    index: usize,
+) -> Result<Option<(Building, &'static str)>, ReadError> {
+    if (building, floor) == ("-", "-") {
+        return Ok(None);
+    }
+    let building =
+        Building::from_str(building).map_err(|_| format_error(index, "unknown building"))?;
+    let floor = find_floor(building, floor).ok_or_else(|| format_error(index, "unknown floor"))?;
+    Ok(Some((building, floor)))
+}
+
+/// Checks the first line, `<magic>\t<version>`, of a versioned format.
+pub(crate) fn read_header(
+    lines: &mut impl Iterator<Item = (usize, io::Result<String>)>,
+    magic: &str,
+    version: u32,
+) -> Result<(), ReadError> {
+    let header = match lines.next() {
+        Some((_, line)) => line?,
+        None => return Err(format_error(0, "empty file")),
+    };
+    let found: u32 = match header.split_once('\t') {
+        Some((found, found_version)) if found == magic => found_version
+            .trim()
+            .parse()
+            .map_err(|_| format_error(0, "invalid version"))?,
+        _ => return Err(format_error(0, &format!("not a `{}` file", magic))),
+    };
+    if found != version {
+        return Err(ReadError::Version(found));
+    }
+    Ok(())
+}
+
+/// Tab separated and trimmed fields of a line with `#` comments, `None` for blank lines.
+pub(crate) fn commented_fields(line: &str) -> Option<Vec<&str>> {
+    let line = line.split('#').next().unwrap_or_default().trim();
+    (!line.is_empty()).then(|| line.split('\t').map(str::trim).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+
+    fn header(file: &str) -> Result<(), ReadError> {
+        read_header(&mut file.as_bytes().lines().enumerate(), "magic", 2)
+    }
+
+    #[test]
+    fn checks_magic_and_version() {
+        assert!(header("magic\t2\n").is_ok());
+        assert!(matches!(header("magic\t3\n"), Err(ReadError::Version(3))));
+        assert!(matches!(
+            header("other\t2\n"),
+            Err(ReadError::Format { line: 1, .. })
+        ));
+        assert!(matches!(header(""), Err(ReadError::Format { line: 1, .. })));
+    }
+
+    #[test]
+    fn strips_comments_and_blank_lines() {
+        assert_eq!(
+            commented_fields("stairs\tHG E 41 \t HG F 30 # main stairs"),
+            Some(vec!["stairs", "HG E 41", "HG F 30"])
+        );
+        assert_eq!(commented_fields("  # only a comment"), None);
+        assert_eq!(commented_fields(""), None);
+    }
+}
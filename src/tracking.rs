@@ -0,0 +1,407 @@
+use crate::estimator::PositionEstimator;
+use crate::geo::{LocalFrame, Point};
+use crate::sighting::Sighting;
+use crate::{Beacon, Id, Position};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy)]
+pub enum RssiSmoothing {
+    /// One-dimensional Kalman filter on the RSSI of each beacon.
+    Kalman {
+        /// Variance in dB² the true RSSI may drift per second.
+        process_noise: f64,
+        /// Variance in dB² of a single measurement.
+        measurement_noise: f64,
+    },
+    /// Exponential moving average, `alpha` is the weight of a new measurement.
+    Exponential { alpha: f64 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RssiFilterConfig {
+    pub smoothing: RssiSmoothing,
+    /// Beacons not seen for this many milliseconds are dropped.
+    pub dropout: u64,
+}
+
+impl Default for RssiFilterConfig {
+    fn default() -> Self {
+        RssiFilterConfig {
+            smoothing: RssiSmoothing::Kalman {
+                process_noise: 4.0,
+                measurement_noise: 36.0,
+            },
+            dropout: 5_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BeaconRssi {
+    beacon: &'static Beacon,
+    rssi: f64,
+    variance: f64,
+    last_seen: u64,
+}
+
+/// Smooths the RSSI of every beacon over time.
+///
+/// A beacon that stops being seen keeps its last smoothed value until it has been silent for
+/// [`RssiFilterConfig::dropout`]. With Kalman smoothing its uncertainty grows meanwhile, so
+/// the first sighting after a gap counts more.
+#[derive(Debug, Clone, Default)]
+pub struct RssiFilter {
+    config: RssiFilterConfig,
+    beacons: HashMap<Id, BeaconRssi>,
+}
+
+impl RssiFilter {
+    pub fn new(config: RssiFilterConfig) -> Self {
+        RssiFilter {
+            config,
+            beacons: HashMap::new(),
+        }
+    }
+
+    pub fn update(&mut self, sighting: &Sighting) {
+        let smoothing = self.config.smoothing;
+        // The first sighting of a beacon seeds its state, with the uncertainty of a single
+        // measurement
+        let state = match self.beacons.entry(sighting.beacon.id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert(BeaconRssi {
+                    beacon: sighting.beacon,
+                    rssi: sighting.rssi,
+                    variance: match smoothing {
+                        RssiSmoothing::Kalman {
+                            measurement_noise, ..
+                        } => measurement_noise,
+                        RssiSmoothing::Exponential { .. } => 0.0,
+                    },
+                    last_seen: sighting.timestamp,
+                });
+                return;
+            }
+        };
+
+        match smoothing {
+            RssiSmoothing::Kalman {
+                process_noise,
+                measurement_noise,
+            } => {
+                let dt = sighting.timestamp.saturating_sub(state.last_seen) as f64 / 1000.0;
+                let predicted = state.variance + process_noise * dt;
+                let gain = predicted / (predicted + measurement_noise);
+                state.rssi += gain * (sighting.rssi - state.rssi);
+                state.variance = (1.0 - gain) * predicted;
+            }
+            RssiSmoothing::Exponential { alpha } => {
+                state.rssi += alpha * (sighting.rssi - state.rssi);
+            }
+        }
+        state.last_seen = state.last_seen.max(sighting.timestamp);
+    }
+
+    /// Drops beacons silent for longer than the dropout at time `now`.
+    pub fn prune(&mut self, now: u64) {
+        let dropout = self.config.dropout;
+        self.beacons
+            .retain(|_, state| now.saturating_sub(state.last_seen) <= dropout);
+    }
+
+    /// Smoothed RSSI of every beacon still present at time `now`, stamped with its last
+    /// sighting and ordered by beacon id.
+    pub fn sightings(&self, now: u64) -> Vec<Sighting> {
+        let mut sightings: Vec<Sighting> = self
+            .beacons
+            .values()
+            .filter(|state| now.saturating_sub(state.last_seen) <= self.config.dropout)
+            .map(|state| Sighting {
+                beacon: state.beacon,
+                rssi: state.rssi,
+                timestamp: state.last_seen,
+            })
+            .collect();
+        sightings.sort_by_key(|sighting| sighting.beacon.id);
+        sightings
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PositionFilterConfig {
+    /// Standard deviation of the acceleration in m/s², how quickly the velocity may change.
+    pub acceleration_sigma: f64,
+    /// Velocity uncertainty in m/s of a new track.
+    pub initial_speed_sigma: f64,
+}
+
+impl Default for PositionFilterConfig {
+    fn default() -> Self {
+        PositionFilterConfig {
+            acceleration_sigma: 0.5,
+            initial_speed_sigma: 1.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TrackedPosition {
+    pub position: Position,
+    /// Velocity in metres per second, `x` east and `y` north.
+    pub velocity: Point,
+    /// Standard deviation of the position in metres.
+    pub accuracy: f64,
+    pub timestamp: u64,
+}
+
+/// Constant velocity Kalman filter on positions in a [`LocalFrame`] around the first fix.
+#[derive(Debug, Clone, Default)]
+pub struct PositionFilter {
+    config: PositionFilterConfig,
+    frame: Option<LocalFrame>,
+    /// `[x, y, vx, vy]`
+    state: [f64; 4],
+    covariance: [[f64; 4]; 4],
+    timestamp: u64,
+}
+
+impl PositionFilter {
+    pub fn new(config: PositionFilterConfig) -> Self {
+        PositionFilter {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Incorporates a fix with an accuracy in metres taken at `timestamp` milliseconds.
+    pub fn update(&mut self, position: Position, accuracy: f64, timestamp: u64) -> TrackedPosition {
+        let variance = accuracy.max(0.1).powi(2);
+
+        let Some(frame) = self.frame else {
+            let speed_variance = self.config.initial_speed_sigma.powi(2);
+            self.frame = Some(LocalFrame::new(position));
+            self.state = [0.0; 4];
+            self.covariance = diagonal([variance, variance, speed_variance, speed_variance]);
+            self.timestamp = timestamp;
+            return self.tracked().unwrap();
+        };
+
+        let dt = timestamp.saturating_sub(self.timestamp) as f64 / 1000.0;
+        self.predict(dt);
+        self.timestamp = self.timestamp.max(timestamp);
+
+        // Only the position is measured, so the innovation covariance is the upper left
+        // 2x2 block of P plus R
+        let measured = frame.to_local(&position);
+        let innovation = [measured.x - self.state[0], measured.y - self.state[1]];
+        let p = self.covariance;
+        let s = [[p[0][0] + variance, p[0][1]], [p[1][0], p[1][1] + variance]];
+        let det = s[0][0] * s[1][1] - s[0][1] * s[1][0];
+        let s_inv = [
+            [s[1][1] / det, -s[0][1] / det],
+            [-s[1][0] / det, s[0][0] / det],
+        ];
+
+        // K = P H^T S^-1, where P H^T are the first two columns of P
+        let mut gain = [[0.0; 2]; 4];
+        for (i, row) in gain.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = p[i][0] * s_inv[0][j] + p[i][1] * s_inv[1][j];
+            }
+        }
+
+        for (i, row) in gain.iter().enumerate() {
+            self.state[i] += row[0] * innovation[0] + row[1] * innovation[1];
+        }
+
+        // P = (I - K H) P
+        let mut updated = p;
+        for i in 0..4 {
+            for j in 0..4 {
+                updated[i][j] -= gain[i][0] * p[0][j] + gain[i][1] * p[1][j];
+            }
+        }
+        self.covariance = updated;
+
+        self.tracked().unwrap()
+    }
+
+    /// Current state, `None` before the first fix.
+    pub fn tracked(&self) -> Option<TrackedPosition> {
+        let frame = self.frame?;
+        Some(TrackedPosition {
+            position: frame.to_position(&Point {
+                x: self.state[0],
+                y: self.state[1],
+            }),
+            velocity: Point {
+                x: self.state[2],
+                y: self.state[3],
+            },
+            accuracy: ((self.covariance[0][0] + self.covariance[1][1]) / 2.0).sqrt(),
+            timestamp: self.timestamp,
+        })
+    }
+
+    fn predict(&mut self, dt: f64) {
+        // x' = F x with F = [[I, dt I], [0, I]]
+        self.state[0] += dt * self.state[2];
+        self.state[1] += dt * self.state[3];
+
+        // P' = F P F^T + Q
+        let p = self.covariance;
+        let mut fp = p;
+        for j in 0..4 {
+            fp[0][j] += dt * p[2][j];
+            fp[1][j] += dt * p[3][j];
+        }
+        let mut fpf = fp;
+        for row in fpf.iter_mut() {
+            row[0] += dt * row[2];
+            row[1] += dt * row[3];
+        }
+
+        // Piecewise white noise acceleration
+        let q = self.config.acceleration_sigma.powi(2);
+        let (q_pos, q_cross, q_vel) = (q * dt.powi(4) / 4.0, q * dt.powi(3) / 2.0, q * dt * dt);
+        for axis in 0..2 {
+            fpf[axis][axis] += q_pos;
+            fpf[axis][axis + 2] += q_cross;
+            fpf[axis + 2][axis] += q_cross;
+            fpf[axis + 2][axis + 2] += q_vel;
+        }
+        self.covariance = fpf;
+    }
+}
+
+fn diagonal(values: [f64; 4]) -> [[f64; 4]; 4] {
+    let mut matrix = [[0.0; 4]; 4];
+    for (i, value) in values.into_iter().enumerate() {
+        matrix[i][i] = value;
+    }
+    matrix
+}
+
+/// Smooths the input and output of a one-shot [`PositionEstimator`].
+///
+/// Sightings go through an [`RssiFilter`], the estimator runs on the smoothed RSSI of every
+/// beacon still present, and its estimates are tracked by a [`PositionFilter`].
+#[derive(Debug, Clone)]
+pub struct Tracker<E> {
+    estimator: E,
+    rssi: RssiFilter,
+    position: PositionFilter,
+}
+
+impl<E: PositionEstimator> Tracker<E> {
+    pub fn new(estimator: E, rssi: RssiFilterConfig, position: PositionFilterConfig) -> Self {
+        Tracker {
+            estimator,
+            rssi: RssiFilter::new(rssi),
+            position: PositionFilter::new(position),
+        }
+    }
+
+    pub fn estimator(&self) -> &E {
+        &self.estimator
+    }
+
+    /// Adds a scan of sightings and returns the tracked position, `None` as long as the
+    /// estimator has not produced a fix.
+    pub fn update(&mut self, sightings: &[Sighting]) -> Option<TrackedPosition> {
+        for sighting in sightings {
+            self.rssi.update(sighting);
+        }
+
+        let Some(now) = sightings.iter().map(|s| s.timestamp).max() else {
+            return self.position.tracked();
+        };
+        self.rssi.prune(now);
+
+        match self.estimator.estimate(&self.rssi.sightings(now)) {
+            Some(estimate) => Some(
+                self.position
+                    .update(estimate.position, estimate.accuracy, now),
+            ),
+            None => self.position.tracked(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::estimator::WeightedCentroid;
+    use crate::testing::{beacon, offset, scan, sighting, square};
+    use crate::Building;
+
+    #[test]
+    fn first_sighting_seeds_the_rssi() {
+        let beacon = beacon(1, Building::HG, "E", "1", offset(0.0, 0.0));
+        let mut filter = RssiFilter::default();
+        filter.update(&sighting(beacon, -70.0, 0));
+        assert_eq!(filter.sightings(0)[0].rssi, -70.0);
+
+        // With equal variances of state and measurement, the second sighting counts about half
+        filter.update(&sighting(beacon, -60.0, 0));
+        assert_eq!(filter.sightings(0)[0].rssi, -65.0);
+    }
+
+    #[test]
+    fn drops_silent_beacons_and_orders_by_id() {
+        let beacons = square();
+        let mut filter = RssiFilter::default();
+        for (i, beacon) in beacons.iter().enumerate().rev() {
+            filter.update(&sighting(beacon, -70.0, i as u64 * 2_000));
+        }
+
+        let minors: Vec<u16> = filter
+            .sightings(5_000)
+            .iter()
+            .map(|s| s.beacon.id.minor)
+            .collect();
+        assert_eq!(minors, [1, 2, 3, 4]);
+
+        filter.prune(9_000);
+        let minors: Vec<u16> = filter
+            .sightings(9_000)
+            .iter()
+            .map(|s| s.beacon.id.minor)
+            .collect();
+        assert_eq!(minors, [3, 4]);
+    }
+
+    #[test]
+    fn follows_a_constant_velocity() {
+        let mut filter = PositionFilter::default();
+        for second in 0..20 {
+            filter.update(offset(second as f64, 0.0), 2.0, second * 1_000);
+        }
+
+        let tracked = filter.tracked().unwrap();
+        assert!(tracked.position.distance(&offset(19.0, 0.0)) < 0.5);
+        assert!((tracked.velocity.x - 1.0).abs() < 0.1);
+        assert!(tracked.velocity.y.abs() < 0.1);
+        assert!(tracked.accuracy < 2.0);
+    }
+
+    #[test]
+    fn tracks_a_standing_device() {
+        let beacons = square();
+        let truth = offset(8.0, 12.0);
+        let mut tracker = Tracker::new(
+            WeightedCentroid::default(),
+            RssiFilterConfig::default(),
+            PositionFilterConfig::default(),
+        );
+        assert!(tracker.update(&[]).is_none());
+
+        let mut tracked = None;
+        for step in 0..10 {
+            tracked = tracker.update(&scan(&beacons, &truth, step * 1_000));
+        }
+        assert!(tracked.unwrap().position.distance(&truth) < 5.0);
+    }
+}
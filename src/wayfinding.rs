@@ -0,0 +1,472 @@
+//! Turn-by-turn instructions for a [`Route`], in German and English.
+
+use crate::geo::LocalFrame;
+use crate::routing::{Route, RouteWaypoint, Step};
+use crate::{Building, Location, Position};
+use strum_macros::{AsRefStr, EnumString};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString)]
+pub enum Language {
+    #[strum(serialize = "de")]
+    German,
+    #[strum(serialize = "en")]
+    English,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl Direction {
+    /// Closest compass direction from `from` to `to`.
+    pub fn between(from: &Position, to: &Position) -> Self {
+        let point = LocalFrame::new(*from).to_local(to);
+        // Clockwise from north, in eighths of a full turn
+        let octant = (point.x.atan2(point.y).to_degrees() / 45.0).round() as i64;
+        match octant.rem_euclid(8) {
+            0 => Direction::North,
+            1 => Direction::NorthEast,
+            2 => Direction::East,
+            3 => Direction::SouthEast,
+            4 => Direction::South,
+            5 => Direction::SouthWest,
+            6 => Direction::West,
+            _ => Direction::NorthWest,
+        }
+    }
+
+    pub fn name(&self, language: Language) -> &'static str {
+        match (language, self) {
+            (Language::German, Direction::North) => "Norden",
+            (Language::German, Direction::NorthEast) => "Nordosten",
+            (Language::German, Direction::East) => "Osten",
+            (Language::German, Direction::SouthEast) => "Südosten",
+            (Language::German, Direction::South) => "Süden",
+            (Language::German, Direction::SouthWest) => "Südwesten",
+            (Language::German, Direction::West) => "Westen",
+            (Language::German, Direction::NorthWest) => "Nordwesten",
+            (Language::English, Direction::North) => "north",
+            (Language::English, Direction::NorthEast) => "north-east",
+            (Language::English, Direction::East) => "east",
+            (Language::English, Direction::SouthEast) => "south-east",
+            (Language::English, Direction::South) => "south",
+            (Language::English, Direction::SouthWest) => "south-west",
+            (Language::English, Direction::West) => "west",
+            (Language::English, Direction::NorthWest) => "north-west",
+        }
+    }
+}
+
+/// One step of the directions along a route.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    /// Start in a room, `None` when starting from a position.
+    Start { location: Option<Location> },
+    /// Walk on the current floor.
+    Walk {
+        /// Metres, straight-line sum of the legs.
+        distance: f64,
+        direction: Direction,
+        /// Last room passed, `None` if the leg ends at a position.
+        towards: Option<Location>,
+    },
+    /// Change floor within a building, `step` is how, if known.
+    ChangeFloor {
+        floor: &'static str,
+        step: Step,
+        near: Option<Location>,
+    },
+    /// Change to another building.
+    ChangeBuilding {
+        building: Building,
+        floor: &'static str,
+        near: Option<Location>,
+    },
+    /// Arrive in a room, `None` when the destination is a position.
+    Arrive { location: Option<Location> },
+}
+
+/// Instructions for walking `route`.
+///
+/// Consecutive walks on a floor become one instruction, as do consecutive floor changes, so
+/// stairs past a mezzanine are a single change to the floor they end on.
+pub fn instructions(route: &Route) -> Vec<Instruction> {
+    let Some(first) = route.waypoints.first() else {
+        return Vec::new();
+    };
+    let mut instructions = vec![Instruction::Start {
+        location: first.location,
+    }];
+
+    let mut leg_start = first.position;
+    let mut previous = first;
+    for waypoint in &route.waypoints[1..] {
+        match classify(previous, waypoint) {
+            Move::Walk => {
+                let distance = previous.position.distance(&waypoint.position);
+                match instructions.last_mut() {
+                    Some(Instruction::Walk {
+                        distance: total,
+                        direction,
+                        towards,
+                    }) => {
+                        *total += distance;
+                        *direction = Direction::between(&leg_start, &waypoint.position);
+                        *towards = waypoint.location;
+                    }
+                    _ => {
+                        leg_start = previous.position;
+                        instructions.push(Instruction::Walk {
+                            distance,
+                            direction: Direction::between(&leg_start, &waypoint.position),
+                            towards: waypoint.location,
+                        });
+                    }
+                }
+            }
+            Move::Floor(floor) => match instructions.last_mut() {
+                Some(Instruction::ChangeFloor {
+                    floor: to, step, ..
+                }) => {
+                    *to = floor;
+                    if *step != waypoint.step {
+                        *step = Step::FloorChange;
+                    }
+                }
+                _ => instructions.push(Instruction::ChangeFloor {
+                    floor,
+                    step: waypoint.step,
+                    near: previous.location,
+                }),
+            },
+            Move::Building(building, floor) => instructions.push(Instruction::ChangeBuilding {
+                building,
+                floor,
+                near: previous.location,
+            }),
+        }
+        previous = waypoint;
+    }
+
+    // Walks of less than a metre only come from position endpoints at a beacon
+    instructions.retain(|i| !matches!(i, Instruction::Walk { distance, .. } if *distance < 1.0));
+    instructions.push(Instruction::Arrive {
+        location: previous.location,
+    });
+    instructions
+}
+
+enum Move {
+    Walk,
+    Floor(&'static str),
+    Building(Building, &'static str),
+}
+
+fn classify(from: &RouteWaypoint, to: &RouteWaypoint) -> Move {
+    match (from.floor, to.floor) {
+        (Some((a, _)), Some((b, floor))) if a != b => Move::Building(b, floor),
+        (Some((_, a)), Some((_, floor))) if a != floor => Move::Floor(floor),
+        _ => Move::Walk,
+    }
+}
+
+impl Instruction {
+    pub fn text(&self, language: Language) -> String {
+        match (language, self) {
+            (Language::German, Instruction::Start { location: Some(l) }) => {
+                format!("Starten Sie im Raum {}", full_code(l))
+            }
+            (Language::German, Instruction::Start { location: None }) => {
+                "Starten Sie an Ihrem Standort".to_string()
+            }
+            (Language::English, Instruction::Start { location: Some(l) }) => {
+                format!("Start in room {}", full_code(l))
+            }
+            (Language::English, Instruction::Start { location: None }) => {
+                "Start at your position".to_string()
+            }
+            (
+                _,
+                Instruction::Walk {
+                    distance,
+                    direction,
+                    towards,
+                },
+            ) => {
+                let distance = round_distance(*distance);
+                let direction = direction.name(language);
+                match (language, towards) {
+                    (Language::German, Some(l)) => format!(
+                        "Gehen Sie {} m nach {} in Richtung {}",
+                        distance,
+                        direction,
+                        room_code(l)
+                    ),
+                    (Language::German, None) => {
+                        format!("Gehen Sie {} m nach {}", distance, direction)
+                    }
+                    (Language::English, Some(l)) => format!(
+                        "Continue {} m {} towards {}",
+                        distance,
+                        direction,
+                        room_code(l)
+                    ),
+                    (Language::English, None) => {
+                        format!("Continue {} m {}", distance, direction)
+                    }
+                }
+            }
+            (_, Instruction::ChangeFloor { floor, step, near }) => {
+                let near = near.map(|l| near_text(language, &l)).unwrap_or_default();
+                match (language, step) {
+                    (Language::German, Step::Stairs) => {
+                        format!("Gehen Sie{} über die Treppe in den Stock {}", near, floor)
+                    }
+                    (Language::German, Step::Elevator) => {
+                        format!("Fahren Sie{} mit dem Lift in den Stock {}", near, floor)
+                    }
+                    (Language::German, _) => format!("Gehen Sie{} in den Stock {}", near, floor),
+                    (Language::English, Step::Stairs) => {
+                        format!("Go to floor {} by stairs{}", floor, near)
+                    }
+                    (Language::English, Step::Elevator) => {
+                        format!("Go to floor {} by elevator{}", floor, near)
+                    }
+                    (Language::English, _) => format!("Go to floor {}{}", floor, near),
+                }
+            }
+            (
+                _,
+                Instruction::ChangeBuilding {
+                    building,
+                    floor,
+                    near,
+                },
+            ) => {
+                let near = near.map(|l| near_text(language, &l)).unwrap_or_default();
+                match language {
+                    Language::German => format!(
+                        "Wechseln Sie{} ins Gebäude {}, Stock {}",
+                        near,
+                        building.as_ref(),
+                        floor
+                    ),
+                    Language::English => format!(
+                        "Cross into building {}, floor {}{}",
+                        building.as_ref(),
+                        floor,
+                        near
+                    ),
+                }
+            }
+            (Language::German, Instruction::Arrive { location: Some(l) }) => {
+                format!("Sie haben Raum {} erreicht", full_code(l))
+            }
+            (Language::German, Instruction::Arrive { location: None }) => {
+                "Sie haben Ihr Ziel erreicht".to_string()
+            }
+            (Language::English, Instruction::Arrive { location: Some(l) }) => {
+                format!("You have reached room {}", full_code(l))
+            }
+            (Language::English, Instruction::Arrive { location: None }) => {
+                "You have reached your destination".to_string()
+            }
+        }
+    }
+}
+
+/// Instructions as text, one line each.
+pub fn describe(route: &Route, language: Language) -> Vec<String> {
+    instructions(route)
+        .iter()
+        .map(|i| i.text(language))
+        .collect()
+}
+
+fn near_text(language: Language, location: &Location) -> String {
+    match language {
+        Language::German => format!(" beim Raum {}", room_code(location)),
+        Language::English => format!(" near room {}", room_code(location)),
+    }
+}
+
+/// Room code within its building, e.g. `E 41`.
+fn room_code(location: &Location) -> String {
+    format!("{} {}", location.floor, location.room)
+}
+
+/// Room code with the building, e.g. `HG E 41`.
+fn full_code(location: &Location) -> String {
+    format!("{} {}", location.building.as_ref(), room_code(location))
+}
+
+/// Whole metres below 10 m, otherwise steps of 5 m, which is as precise as beacon positions.
+fn round_distance(distance: f64) -> u64 {
+    if distance < 10.0 {
+        distance.round().max(1.0) as u64
+    } else {
+        ((distance / 5.0).round() * 5.0) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::offset;
+
+    fn waypoint(
+        building: Building,
+        floor: &'static str,
+        room: Option<&'static str>,
+        east: f64,
+        north: f64,
+        step: Step,
+    ) -> RouteWaypoint {
+        RouteWaypoint {
+            position: offset(east, north),
+            location: room.map(|room| Location {
+                building,
+                floor,
+                room,
+            }),
+            floor: Some((building, floor)),
+            step,
+        }
+    }
+
+    fn route(waypoints: Vec<RouteWaypoint>) -> Route {
+        Route {
+            waypoints,
+            distance: 0.0,
+        }
+    }
+
+    /// North along a corridor on E, up the stairs past F to G, east and across into ML.
+    fn through_two_buildings() -> Route {
+        use Building::{HG, ML};
+        route(vec![
+            waypoint(HG, "E", Some("1"), 0.0, 0.0, Step::Start),
+            waypoint(HG, "E", Some("2"), 0.0, 10.0, Step::Walk),
+            waypoint(HG, "E", Some("3"), 0.0, 20.0, Step::Walk),
+            waypoint(HG, "F", Some("4"), 0.0, 20.0, Step::Stairs),
+            waypoint(HG, "G", Some("5"), 0.0, 20.0, Step::Stairs),
+            waypoint(HG, "G", Some("6"), 10.0, 20.0, Step::Walk),
+            waypoint(ML, "G", Some("7"), 15.0, 20.0, Step::Passage),
+            waypoint(ML, "G", Some("8"), 15.0, 25.0, Step::Walk),
+        ])
+    }
+
+    /// From a position south of room E 1 by elevator to a position on F.
+    fn between_positions() -> Route {
+        use Building::HG;
+        route(vec![
+            waypoint(HG, "E", None, 0.0, -5.0, Step::Start),
+            waypoint(HG, "E", Some("1"), 0.0, 0.0, Step::Walk),
+            waypoint(HG, "F", Some("2"), 0.0, 0.0, Step::Elevator),
+            waypoint(HG, "F", None, -8.0, 0.0, Step::Walk),
+        ])
+    }
+
+    #[test]
+    fn merges_walks_and_floor_changes() {
+        let instructions = instructions(&through_two_buildings());
+        assert_eq!(instructions.len(), 7);
+        let Instruction::Walk {
+            distance,
+            direction,
+            towards,
+        } = instructions[1]
+        else {
+            panic!("expected a walk, got {:?}", instructions[1]);
+        };
+        assert!((distance - 20.0).abs() < 0.01);
+        assert_eq!(direction, Direction::North);
+        assert_eq!(towards.map(|l| l.room), Some("3"));
+        assert!(matches!(
+            instructions[2],
+            Instruction::ChangeFloor {
+                floor: "G",
+                step: Step::Stairs,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn describes_in_english() {
+        assert_eq!(
+            describe(&through_two_buildings(), Language::English),
+            [
+                "Start in room HG E 1",
+                "Continue 20 m north towards E 3",
+                "Go to floor G by stairs near room E 3",
+                "Continue 10 m east towards G 6",
+                "Cross into building ML, floor G near room G 6",
+                "Continue 5 m north towards G 8",
+                "You have reached room ML G 8",
+            ]
+        );
+        assert_eq!(
+            describe(&between_positions(), Language::English),
+            [
+                "Start at your position",
+                "Continue 5 m north towards E 1",
+                "Go to floor F by elevator near room E 1",
+                "Continue 8 m west",
+                "You have reached your destination",
+            ]
+        );
+    }
+
+    #[test]
+    fn describes_in_german() {
+        assert_eq!(
+            describe(&through_two_buildings(), Language::German),
+            [
+                "Starten Sie im Raum HG E 1",
+                "Gehen Sie 20 m nach Norden in Richtung E 3",
+                "Gehen Sie beim Raum E 3 über die Treppe in den Stock G",
+                "Gehen Sie 10 m nach Osten in Richtung G 6",
+                "Wechseln Sie beim Raum G 6 ins Gebäude ML, Stock G",
+                "Gehen Sie 5 m nach Norden in Richtung G 8",
+                "Sie haben Raum ML G 8 erreicht",
+            ]
+        );
+        assert_eq!(
+            describe(&between_positions(), Language::German),
+            [
+                "Starten Sie an Ihrem Standort",
+                "Gehen Sie 5 m nach Norden in Richtung E 1",
+                "Fahren Sie beim Raum E 1 mit dem Lift in den Stock F",
+                "Gehen Sie 8 m nach Westen",
+                "Sie haben Ihr Ziel erreicht",
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_out_how_for_mixed_floor_changes() {
+        use Building::HG;
+        let route = route(vec![
+            waypoint(HG, "E", Some("1"), 0.0, 0.0, Step::Start),
+            waypoint(HG, "F", Some("2"), 0.0, 0.0, Step::Stairs),
+            waypoint(HG, "G", Some("3"), 0.0, 0.0, Step::Elevator),
+        ]);
+        assert_eq!(
+            describe(&route, Language::English),
+            [
+                "Start in room HG E 1",
+                "Go to floor G near room E 1",
+                "You have reached room HG G 3",
+            ]
+        );
+    }
+}
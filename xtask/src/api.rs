@@ -1,27 +1,26 @@
+use crate::client::{self, ClientConfig, FetchError};
 use anyhow::Context;
+use reqwest::Url;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::io::Read;
 
-#[derive(Deserialize, Debug)]
-pub(crate) struct BeaconResponse {
-    beacons: Vec<Beacon>,
-}
-
-impl BeaconResponse {
-    pub fn get_beacons(self) -> Beacons {
-        let mut grouped_beacons: Beacons = Beacons::new();
-        for beacon in self.beacons {
-            grouped_beacons
-                .entry(beacon.indoor.building.clone())
-                .or_default()
-                .push(beacon);
-        }
-        grouped_beacons
-    }
+/// Pagination information of a single response page. Beacons are not part of it, they are
+/// grouped into [`Beacons`] while the page is parsed.
+#[derive(Debug, Default)]
+pub(crate) struct Page {
+    /// Number of beacons on this page.
+    pub count: usize,
+    /// Absolute or relative url of the next page.
+    pub next: Option<String>,
+    /// Opaque cursor to pass as `cursor` query parameter for the next page.
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
-pub(crate) struct Beacon {
+pub struct Beacon {
     pub major: u16,
     pub minor: u16,
     pub location: Location,
@@ -29,13 +28,27 @@ pub(crate) struct Beacon {
 }
 
 #[derive(Deserialize, Debug)]
-pub(crate) struct Location {
+pub struct Location {
     pub lat: f64,
     pub lon: f64,
 }
 
+impl Location {
+    /// Great-circle distance to `other` in metres, the same as `Position::distance` of the
+    /// generated crate.
+    pub fn distance(&self, other: &Location) -> f64 {
+        const EARTH_RADIUS: f64 = 6_371_008.8;
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.lon - self.lon).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
+    }
+}
+
 #[derive(Deserialize, Debug)]
-pub(crate) struct Room {
+pub struct Room {
     pub building: String,
     pub floor: String,
     pub room: String,
@@ -43,14 +56,219 @@ pub(crate) struct Room {
 
 pub type Beacons = HashMap<String, Vec<Beacon>>;
 
-pub(crate) fn fetch_beacons(url: &str) -> anyhow::Result<Beacons> {
-    let response =
-        reqwest::blocking::get(url).context(format!("Failed to fetch data from {}", url))?;
+/// Fetches all pages of `url`.
+///
+/// Pages are followed through a `next` url or a `next_cursor` in the response. With a
+/// `page_size`, pages are requested as `page=<n>&limit=<page_size>` until a page comes back
+/// short or adds no new beacon. Beacons repeated on later pages are skipped. Every page is
+/// parsed while it is downloaded.
+pub fn fetch_beacons(
+    url: &str,
+    config: &ClientConfig,
+    page_size: Option<usize>,
+) -> anyhow::Result<Beacons> {
+    let base = Url::parse(url).context(format!("Invalid url {}", url))?;
+
+    let mut grouped_beacons = Beacons::new();
+    let mut unknown_fields = BTreeSet::new();
+    let mut visited = HashSet::new();
+    let mut ids = HashSet::new();
+    let mut duplicates = 0;
+    let mut page_number = 1;
+    let mut next_url = Some(match page_size {
+        Some(limit) => page_url(&base, page_number, limit),
+        None => base.clone(),
+    });
+
+    while let Some(current_url) = next_url.take() {
+        if !visited.insert(current_url.clone()) {
+            anyhow::bail!("Pagination loops back to {}", current_url);
+        }
+
+        let (page, page_beacons) = client::fetch(current_url.as_str(), config, |body| {
+            let mut page_beacons = Beacons::new();
+            let page = read_page(body, &mut page_beacons, &mut unknown_fields)
+                .map_err(|source| FetchError::from_json(current_url.as_str(), source))?;
+            Ok((page, page_beacons))
+        })?;
+        // Beacons already seen on an earlier page are dropped, e.g. when the server ignores `page`
+        let mut added = 0;
+        for (building, beacons) in page_beacons {
+            for beacon in beacons {
+                if ids.insert((beacon.major, beacon.minor)) {
+                    grouped_beacons
+                        .entry(building.clone())
+                        .or_default()
+                        .push(beacon);
+                    added += 1;
+                } else {
+                    duplicates += 1;
+                }
+            }
+        }
+
+        next_url = if let Some(next) = page.next {
+            Some(
+                current_url
+                    .join(&next)
+                    .context(format!("Invalid next page url {}", next))?,
+            )
+        } else if let Some(cursor) = page.next_cursor {
+            let mut cursor_url = base.clone();
+            cursor_url.query_pairs_mut().append_pair("cursor", &cursor);
+            Some(cursor_url)
+        } else {
+            match page_size {
+                Some(limit) if page.count >= limit && added > 0 => {
+                    page_number += 1;
+                    Some(page_url(&base, page_number, limit))
+                }
+                _ => None,
+            }
+        };
+    }
+
+    println!("Pages fetched       : {}", visited.len());
+    if duplicates > 0 {
+        eprintln!("Skipped {} beacons repeated on later pages", duplicates);
+    }
+    warn_unknown_fields(&unknown_fields);
+    Ok(with_static_beacons(grouped_beacons))
+}
+
+fn page_url(base: &Url, page: usize, limit: usize) -> Url {
+    let mut url = base.clone();
+    url.query_pairs_mut()
+        .append_pair("page", &page.to_string())
+        .append_pair("limit", &limit.to_string());
+    url
+}
 
-    let api_response: BeaconResponse = response.json().context("Failed to read json body")?;
+fn warn_unknown_fields(unknown_fields: &BTreeSet<String>) {
+    if !unknown_fields.is_empty() {
+        let fields: Vec<&str> = unknown_fields.iter().map(String::as_str).collect();
+        eprintln!("Ignoring unknown fields in response: {}", fields.join(", "));
+    }
+}
+
+/// Parses one page from `reader`, adding its beacons to `grouped_beacons` one by one instead
+/// of collecting the whole body first. Fields this version does not know about are skipped
+/// and their paths, e.g. `beacons[].indoor.wing`, added to `unknown_fields`.
+pub(crate) fn read_page<R: Read>(
+    reader: R,
+    grouped_beacons: &mut Beacons,
+    unknown_fields: &mut BTreeSet<String>,
+) -> Result<Page, serde_json::Error> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let mut track = |path: serde_ignored::Path| {
+        unknown_fields.insert(field_path(&path));
+    };
+
+    let page = PageSeed { grouped_beacons }.deserialize(serde_ignored::Deserializer::new(
+        &mut deserializer,
+        &mut track,
+    ))?;
+    deserializer.end()?;
+    Ok(page)
+}
+
+fn field_path(path: &serde_ignored::Path) -> String {
+    match path {
+        serde_ignored::Path::Root => String::new(),
+        serde_ignored::Path::Seq { parent, .. } => format!("{}[]", field_path(parent)),
+        serde_ignored::Path::Map { parent, key } => match field_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{}.{}", parent, key),
+        },
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => field_path(parent),
+    }
+}
 
-    let mut grouped_beacons = api_response.get_beacons();
+struct PageSeed<'a> {
+    grouped_beacons: &'a mut Beacons,
+}
+
+impl<'de> DeserializeSeed<'de> for PageSeed<'_> {
+    type Value = Page;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Page, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for PageSeed<'_> {
+    type Value = Page;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a beacon response object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Page, A::Error> {
+        let mut page = Page::default();
+        let mut has_beacons = false;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "beacons" => {
+                    has_beacons = true;
+                    page.count = map.next_value_seed(BeaconsSeed {
+                        grouped_beacons: &mut *self.grouped_beacons,
+                    })?;
+                }
+                "next" => page.next = map.next_value()?,
+                "next_cursor" => page.next_cursor = map.next_value()?,
+                "page" | "limit" | "total" => {
+                    map.next_value::<Option<u64>>()?;
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        if !has_beacons {
+            return Err(de::Error::missing_field("beacons"));
+        }
+        Ok(page)
+    }
+}
+
+struct BeaconsSeed<'a> {
+    grouped_beacons: &'a mut Beacons,
+}
+
+impl<'de> DeserializeSeed<'de> for BeaconsSeed<'_> {
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<usize, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for BeaconsSeed<'_> {
+    type Value = usize;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of beacons")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<usize, A::Error> {
+        let mut count = 0;
+        while let Some(beacon) = seq.next_element::<Beacon>()? {
+            self.grouped_beacons
+                .entry(beacon.indoor.building.clone())
+                .or_default()
+                .push(beacon);
+            count += 1;
+        }
+        Ok(count)
+    }
+}
 
+/// Adds the beacons that are not (yet) served by the API.
+fn with_static_beacons(mut grouped_beacons: Beacons) -> Beacons {
     grouped_beacons.insert(
         "SON".to_string(),
         vec![
@@ -95,5 +313,5 @@ pub(crate) fn fetch_beacons(url: &str) -> anyhow::Result<Beacons> {
             },
         ],
     );
-    Ok(grouped_beacons)
+    grouped_beacons
 }
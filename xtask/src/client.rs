@@ -0,0 +1,375 @@
+use clap::Args;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{
+    HeaderName, HeaderValue, AUTHORIZATION, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::StatusCode;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// Bearer token sent as `Authorization: Bearer <token>`.
+pub(crate) const TOKEN_ENV: &str = "BEACONS_API_TOKEN";
+/// Arbitrary authentication header in the form `Name: value`.
+pub(crate) const HEADER_ENV: &str = "BEACONS_API_HEADER";
+
+#[derive(Args, Debug, Clone)]
+pub struct ClientConfig {
+    /// Timeout of a single request in seconds
+    #[arg(long, default_value_t = 30)]
+    pub timeout: u64,
+
+    /// Number of retries after a failed request
+    #[arg(long, default_value_t = 3)]
+    pub retries: u32,
+
+    /// Delay before the first retry in milliseconds, doubled on every further retry
+    #[arg(long, default_value_t = 500)]
+    pub backoff: u64,
+
+    /// Directory of the response cache used for conditional requests
+    #[arg(long, default_value = "target/xtask-cache")]
+    pub cache_dir: PathBuf,
+
+    /// Always download the full response, ignoring and not updating the cache
+    #[arg(long)]
+    pub no_cache: bool,
+}
+
+#[derive(Debug)]
+pub(crate) enum FetchError {
+    /// The request could not be sent or the response could not be read.
+    Transport {
+        url: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The server answered with a non-success status.
+    Status { url: String, status: StatusCode },
+    /// The body does not match the expected schema.
+    Schema {
+        url: String,
+        source: serde_json::Error,
+    },
+    /// Authentication from the environment is malformed.
+    Auth(String),
+    /// The response cache could not be read or written.
+    Cache {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+impl FetchError {
+    /// Transport errors, server errors and rate limiting may go away on their own.
+    fn is_transient(&self) -> bool {
+        match self {
+            FetchError::Transport { .. } => true,
+            FetchError::Status { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+
+    /// Error of parsing a response body. Failures to read the body, e.g. a dropped connection,
+    /// are transport errors, everything else means the body does not match the schema.
+    pub(crate) fn from_json(url: &str, source: serde_json::Error) -> Self {
+        if source.is_io() {
+            FetchError::Transport {
+                url: url.to_string(),
+                source: Box::new(source),
+            }
+        } else {
+            FetchError::Schema {
+                url: url.to_string(),
+                source,
+            }
+        }
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Transport { url, source } => {
+                write!(f, "Failed to fetch data from {}: {}", url, source)
+            }
+            FetchError::Status { url, status } => {
+                write!(f, "Server responded with {} for {}", status, url)
+            }
+            FetchError::Schema { url, source } => {
+                write!(f, "Unexpected response body from {}: {}", url, source)
+            }
+            FetchError::Auth(message) => write!(f, "Invalid authentication: {}", message),
+            FetchError::Cache { path, source } => {
+                write!(f, "Failed to access cache {}: {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FetchError::Transport { source, .. } => Some(source.as_ref()),
+            FetchError::Schema { source, .. } => Some(source),
+            FetchError::Cache { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Validators of a cached response, stored next to its body.
+#[derive(Debug, Default)]
+struct CacheEntry {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl CacheEntry {
+    fn to_meta(&self) -> String {
+        let mut meta = String::new();
+        if let Some(etag) = &self.etag {
+            meta.push_str(&format!("etag: {}\n", etag));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            meta.push_str(&format!("last-modified: {}\n", last_modified));
+        }
+        meta
+    }
+}
+
+/// Response body that is read incrementally. If the response can be revalidated later, it is
+/// copied into the cache while it is read and committed by [`Body::finish`].
+pub(crate) struct Body {
+    reader: Box<dyn Read>,
+    cache: Option<CacheWriter>,
+}
+
+struct CacheWriter {
+    file: BufWriter<File>,
+    tmp_path: PathBuf,
+    body_path: PathBuf,
+    meta_path: PathBuf,
+    entry: CacheEntry,
+}
+
+impl Read for Body {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        if let Some(cache) = &mut self.cache {
+            cache.file.write_all(&buf[..n])?;
+        }
+        Ok(n)
+    }
+}
+
+impl Body {
+    /// Moves the completely read body into the cache.
+    pub fn finish(mut self) -> Result<(), FetchError> {
+        let Some(mut cache) = self.cache.take() else {
+            return Ok(());
+        };
+
+        let result = cache
+            .file
+            .flush()
+            .and_then(|_| fs::rename(&cache.tmp_path, &cache.body_path))
+            .and_then(|_| fs::write(&cache.meta_path, cache.entry.to_meta()));
+
+        result.map_err(|source| FetchError::Cache {
+            path: cache.body_path,
+            source,
+        })
+    }
+}
+
+impl Drop for Body {
+    fn drop(&mut self) {
+        if let Some(cache) = &self.cache {
+            let _ = fs::remove_file(&cache.tmp_path);
+        }
+    }
+}
+
+/// Fetches `url` and passes the body to `read`, with authentication and, unless disabled, a
+/// conditional request against the local cache.
+///
+/// Transient errors of the request and of reading the body are retried with exponential
+/// backoff, so `read` may be called more than once and should start from scratch every time.
+pub(crate) fn fetch<T>(
+    url: &str,
+    config: &ClientConfig,
+    mut read: impl FnMut(&mut Body) -> Result<T, FetchError>,
+) -> Result<T, FetchError> {
+    let client = Client::builder()
+        .timeout(Duration::from_secs(config.timeout))
+        .build()
+        .map_err(|source| FetchError::Transport {
+            url: url.to_string(),
+            source: Box::new(source),
+        })?;
+
+    let mut attempt = 0;
+    loop {
+        let result = open(&client, url, config).and_then(|mut body| {
+            let value = read(&mut body)?;
+            body.finish()?;
+            Ok(value)
+        });
+        match result {
+            Err(err) if err.is_transient() && attempt < config.retries => {
+                let delay =
+                    Duration::from_millis(config.backoff.saturating_mul(1 << attempt.min(16)));
+                eprintln!("{}, retrying in {:?}", err, delay);
+                thread::sleep(delay);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Sends a single request for `url` and opens its body, from the cache if it is not modified.
+fn open(client: &Client, url: &str, config: &ClientConfig) -> Result<Body, FetchError> {
+    let (body_path, meta_path) = cache_paths(&config.cache_dir, url);
+    let cached = if config.no_cache {
+        None
+    } else {
+        read_cache_entry(&body_path, &meta_path)
+    };
+    let response = send(client, url, cached.as_ref())?;
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        println!("Response not modified, using cache {}", body_path.display());
+        let file = File::open(&body_path).map_err(|source| FetchError::Cache {
+            path: body_path,
+            source,
+        })?;
+        return Ok(Body {
+            reader: Box::new(BufReader::new(file)),
+            cache: None,
+        });
+    }
+
+    let entry = CacheEntry {
+        etag: header_string(&response, ETAG),
+        last_modified: header_string(&response, LAST_MODIFIED),
+    };
+
+    let cache = if !config.no_cache && (entry.etag.is_some() || entry.last_modified.is_some()) {
+        let tmp_path = body_path.with_extension("json.tmp");
+        let file = fs::create_dir_all(&config.cache_dir)
+            .and_then(|_| File::create(&tmp_path))
+            .map_err(|source| FetchError::Cache {
+                path: tmp_path.clone(),
+                source,
+            })?;
+        Some(CacheWriter {
+            file: BufWriter::new(file),
+            tmp_path,
+            body_path,
+            meta_path,
+            entry,
+        })
+    } else {
+        None
+    };
+
+    Ok(Body {
+        reader: Box::new(response),
+        cache,
+    })
+}
+
+fn send(client: &Client, url: &str, cached: Option<&CacheEntry>) -> Result<Response, FetchError> {
+    let mut request = client.get(url);
+
+    if let Some((name, value)) = auth_header()? {
+        request = request.header(name, value);
+    }
+    if let Some(entry) = cached {
+        if let Some(etag) = &entry.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let response = request.send().map_err(|source| FetchError::Transport {
+        url: url.to_string(),
+        source: Box::new(source),
+    })?;
+
+    let status = response.status();
+    if status.is_success() || (status == StatusCode::NOT_MODIFIED && cached.is_some()) {
+        Ok(response)
+    } else {
+        Err(FetchError::Status {
+            url: url.to_string(),
+            status,
+        })
+    }
+}
+
+/// Reads the authentication header from [`TOKEN_ENV`] or [`HEADER_ENV`].
+fn auth_header() -> Result<Option<(HeaderName, HeaderValue)>, FetchError> {
+    if let Ok(token) = std::env::var(TOKEN_ENV) {
+        let value = HeaderValue::from_str(&format!("Bearer {}", token.trim()))
+            .map_err(|e| FetchError::Auth(format!("{}: {}", TOKEN_ENV, e)))?;
+        return Ok(Some((AUTHORIZATION, value)));
+    }
+
+    if let Ok(header) = std::env::var(HEADER_ENV) {
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| FetchError::Auth(format!("{} must be `Name: value`", HEADER_ENV)))?;
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|e| FetchError::Auth(format!("{}: {}", HEADER_ENV, e)))?;
+        let value = HeaderValue::from_str(value.trim())
+            .map_err(|e| FetchError::Auth(format!("{}: {}", HEADER_ENV, e)))?;
+        return Ok(Some((name, value)));
+    }
+
+    Ok(None)
+}
+
+fn header_string(response: &Response, name: HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Cache files are named after a FNV-1a hash of the url, so they are stable between runs.
+fn cache_paths(cache_dir: &Path, url: &str) -> (PathBuf, PathBuf) {
+    let hash = url.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    (
+        cache_dir.join(format!("{:016x}.json", hash)),
+        cache_dir.join(format!("{:016x}.meta", hash)),
+    )
+}
+
+fn read_cache_entry(body_path: &Path, meta_path: &Path) -> Option<CacheEntry> {
+    if !body_path.exists() {
+        return None;
+    }
+
+    let meta = fs::read_to_string(meta_path).ok()?;
+    let mut entry = CacheEntry::default();
+    for line in meta.lines() {
+        match line.split_once(": ") {
+            Some(("etag", value)) => entry.etag = Some(value.to_string()),
+            Some(("last-modified", value)) => entry.last_modified = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Some(entry)
+}
@@ -0,0 +1,9 @@
+//! Fetching of the beacon API for the generator.
+//!
+//! Nothing here depends on the generated crate, so the generator still builds when a
+//! generated file is broken.
+
+pub mod api;
+mod client;
+
+pub use client::ClientConfig;
@@ -1,10 +1,11 @@
-mod api;
-
-use api::{fetch_beacons, Beacons};
+use anyhow::{bail, Context};
+use std::collections::BTreeMap;
 use std::fs;
 use std::fs::File;
 use std::io::{BufWriter, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use xtask::api::{self, fetch_beacons, Beacons};
+use xtask::ClientConfig;
 
 use clap::{Parser, Subcommand};
 use regex::Regex;
@@ -21,20 +22,44 @@ enum Command {
     Generate {
         #[arg(short, long)]
         uri: String,
+        /// Request pages of this many beacons with `page` and `limit` query parameters
+        #[arg(long)]
+        page_size: Option<usize>,
+        /// JSON file mapping buildings to footprint polygons that override the convex hull of
+        /// their beacons, e.g. `{"HPH": [[47.4085, 8.5070], ...]}`. Ignored if missing
+        #[arg(long, default_value = "footprints.json")]
+        footprints: PathBuf,
+        #[command(flatten)]
+        client: ClientConfig,
     },
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
-    let url = match args.command {
-        Command::Generate { uri } => uri,
-    };
+    match args.command {
+        Command::Generate {
+            uri,
+            page_size,
+            footprints,
+            client,
+        } => generate(&uri, &client, page_size, &footprints),
+    }
+}
 
+fn generate(
+    url: &str,
+    client: &ClientConfig,
+    page_size: Option<usize>,
+    footprints: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
     let buildings_output = "./src/buildings.gen.rs";
     let beacons_output = "./src/beacons.gen.rs";
+    let footprints_output = "./src/footprints.gen.rs";
+    let rooms_output = "./src/rooms.gen.rs";
 
-    let grouped_beacons = fetch_beacons(&url)?;
+    let grouped_beacons = fetch_beacons(url, client, page_size)?;
+    let footprint_overrides = read_footprints(footprints, &grouped_beacons)?;
 
     let mut writer = BufWriter::new(File::create(Path::new(&beacons_output))?);
     write_beacons(&mut writer, &grouped_beacons)?;
@@ -45,6 +70,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         grouped_beacons.keys().cloned().collect(),
     )?;
 
+    let mut footprints_writer = BufWriter::new(File::create(Path::new(&footprints_output))?);
+    write_footprints(&mut footprints_writer, &footprint_overrides)?;
+
+    let mut rooms_writer = BufWriter::new(File::create(Path::new(&rooms_output))?);
+    write_rooms(&mut rooms_writer, &grouped_beacons)?;
+
     let cargo_toml_path = "./Cargo.toml";
 
     // Read the existing Cargo.toml file
@@ -54,6 +85,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     keys.sort();
 
     println!("Beacons file        : {:?}", beacons_output);
+    println!("Rooms file          : {:?}", rooms_output);
+    if !footprint_overrides.is_empty() {
+        println!(
+            "Footprints          : {} overrides from {}",
+            footprint_overrides.len(),
+            footprints.display()
+        );
+    }
     println!("Cargo.toml          : {}", cargo_toml_path);
 
     replace_features_cargo_toml(cargo_toml_path, &content, keys);
@@ -90,9 +129,9 @@ fn write_buildings(writer: &mut BufWriter<File>, mut buildings: Vec<String>) ->
     writeln!(
         writer,
         r#"
-use strum_macros::AsRefStr;
+use strum_macros::{{AsRefStr, EnumString}};
 
-#[derive(Debug, Clone, AsRefStr)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsRefStr, EnumString)]
 pub enum Building {{"#
     )?;
 
@@ -110,6 +149,103 @@ pub enum Building {{"#
     Ok(())
 }
 
+/// Footprint polygons by building, as `[lat, lon]` vertices.
+type Footprints = BTreeMap<String, Vec<[f64; 2]>>;
+
+fn read_footprints(path: &Path, grouped_beacons: &Beacons) -> anyhow::Result<Footprints> {
+    if !path.exists() {
+        return Ok(Footprints::new());
+    }
+    let footprints: Footprints = serde_json::from_str(&fs::read_to_string(path)?)
+        .with_context(|| format!("Invalid footprints file {}", path.display()))?;
+
+    for (building, polygon) in &footprints {
+        if !grouped_beacons.contains_key(building) {
+            bail!("Footprint for unknown building {}", building);
+        }
+        if polygon.len() < 3 {
+            bail!("Footprint of {} needs at least 3 vertices", building);
+        }
+    }
+    Ok(footprints)
+}
+
+fn write_footprints(writer: &mut dyn Write, footprints: &Footprints) -> anyhow::Result<()> {
+    writeln!(
+        writer,
+        r#"
+pub(crate) static FOOTPRINT_OVERRIDES: &[(Building, &[Position])] = &["#
+    )?;
+
+    for (building, polygon) in footprints {
+        writeln!(writer, "    (Building::{}, &[", building)?;
+        for [lat, lon] in polygon {
+            writeln!(writer, "        Position {{ lat: {}, lon: {} }},", lat, lon)?;
+        }
+        writeln!(writer, "    ]),")?;
+    }
+    writeln!(writer, "];")?;
+    Ok(())
+}
+
+/// Writes one entry per room with the ids of its beacons, their mean position and the distance
+/// to the furthest of them.
+fn write_rooms(writer: &mut dyn Write, grouped_beacons: &Beacons) -> anyhow::Result<()> {
+    writeln!(
+        writer,
+        r#"
+pub static ROOMS: &[Room] = &["#
+    )?;
+
+    let mut keys: Vec<&String> = grouped_beacons.keys().collect();
+    keys.sort();
+
+    for feature_flag in keys {
+        let mut rooms: BTreeMap<(&str, &str), Vec<&api::Beacon>> = BTreeMap::new();
+        for beacon in &grouped_beacons[feature_flag] {
+            rooms
+                .entry((&beacon.indoor.floor, &beacon.indoor.room))
+                .or_default()
+                .push(beacon);
+        }
+
+        for ((floor, room), mut beacons) in rooms {
+            beacons.sort_by_key(|b| (b.major, b.minor));
+            let positions: Vec<&api::Location> = beacons.iter().map(|b| &b.location).collect();
+            let count = positions.len() as f64;
+            let centroid = api::Location {
+                lat: positions.iter().map(|p| p.lat).sum::<f64>() / count,
+                lon: positions.iter().map(|p| p.lon).sum::<f64>() / count,
+            };
+            let radius = positions
+                .iter()
+                .map(|p| centroid.distance(p))
+                .fold(0.0, f64::max);
+
+            writeln!(writer, "    #[cfg(feature = \"{}\")]", feature_flag)?;
+            writeln!(
+                writer,
+                r#"    Room {{
+        location: Location {{ building: Building::{}, floor: "{}", room: "{}" }},
+        centroid: Position {{ lat: {}, lon: {} }},
+        radius: {:?},
+        beacon_ids: &["#,
+                feature_flag, floor, room, centroid.lat, centroid.lon, radius
+            )?;
+            for beacon in beacons {
+                writeln!(
+                    writer,
+                    "            Id {{ uuid: crate::ETH_UUID, major: {}, minor: {} }},",
+                    beacon.major, beacon.minor
+                )?;
+            }
+            writeln!(writer, "        ],\n    }},")?;
+        }
+    }
+    writeln!(writer, "];")?;
+    Ok(())
+}
+
 fn write_beacons(writer: &mut dyn Write, grouped_beacons: &Beacons) -> anyhow::Result<()> {
     writeln!(
         writer,
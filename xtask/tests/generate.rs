@@ -0,0 +1,418 @@
+//! End-to-end tests of `xtask generate` against a local stub of the beacon API.
+//!
+//! Every test runs the xtask binary in a temporary copy of the library crate. The generated
+//! files are compared with `tests/snapshots`; run with `UPDATE_SNAPSHOTS=1` to accept changes.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use tempfile::TempDir;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+const SNAPSHOTS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots");
+
+#[derive(Debug, Clone)]
+struct Request {
+    path: String,
+    headers: HashMap<String, String>,
+}
+
+struct Response {
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+    /// Announced instead of the length of `body`, to drop the connection mid-body.
+    content_length: Option<usize>,
+}
+
+impl Response {
+    fn ok(body: String) -> Self {
+        Response {
+            status: 200,
+            headers: Vec::new(),
+            body,
+            content_length: None,
+        }
+    }
+
+    fn status(status: u16) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+            content_length: None,
+        }
+    }
+}
+
+/// Minimal HTTP/1.1 server answering every request with `handler` and recording it.
+struct StubApi {
+    url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl StubApi {
+    fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&Request, usize) -> Response + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let request = read_request(&stream);
+                let index = {
+                    let mut recorded = recorded.lock().unwrap();
+                    recorded.push(request.clone());
+                    recorded.len() - 1
+                };
+                write_response(&mut stream, handler(&request, index));
+            }
+        });
+
+        StubApi { url, requests }
+    }
+
+    fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Request {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let path = line.split_whitespace().nth(1).unwrap().to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        match line.trim_end().split_once(':') {
+            Some((name, value)) => {
+                headers.insert(name.to_ascii_lowercase(), value.trim().to_string());
+            }
+            None => break,
+        }
+    }
+
+    Request { path, headers }
+}
+
+fn write_response(stream: &mut TcpStream, response: Response) {
+    let mut head = format!(
+        "HTTP/1.1 {} Stub\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.content_length.unwrap_or(response.body.len())
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    stream.write_all(head.as_bytes()).unwrap();
+    stream.write_all(response.body.as_bytes()).unwrap();
+}
+
+fn fixture(name: &str) -> String {
+    fs::read_to_string(Path::new(FIXTURES).join(name)).unwrap()
+}
+
+/// Adds a `next` link to the response object in `body`.
+fn with_next(body: String, next: &str) -> String {
+    let end = body.rfind('}').unwrap();
+    format!("{}, \"next\": \"{}\" }}", body[..end].trim_end(), next)
+}
+
+/// Copy of the library crate the generator can write into. The workspace section is dropped,
+/// so the copy builds on its own.
+fn library_copy() -> TempDir {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let dir = TempDir::new().unwrap();
+
+    fs::create_dir(dir.path().join("src")).unwrap();
+    for entry in fs::read_dir(root.join("src")).unwrap() {
+        let entry = entry.unwrap();
+        fs::copy(entry.path(), dir.path().join("src").join(entry.file_name())).unwrap();
+    }
+    fs::copy(root.join("Cargo.lock"), dir.path().join("Cargo.lock")).unwrap();
+
+    let manifest = fs::read_to_string(root.join("Cargo.toml")).unwrap();
+    let start = manifest.find("[workspace]").unwrap();
+    let end = start + manifest[start..].find("\n[").unwrap() + 1;
+    let manifest = format!("{}{}", &manifest[..start], &manifest[end..]);
+    fs::write(dir.path().join("Cargo.toml"), manifest).unwrap();
+
+    dir
+}
+
+fn generate(dir: &Path, url: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_xtask"))
+        .current_dir(dir)
+        .args(["generate", "--uri", url, "--backoff", "1"])
+        .args(args)
+        .env_remove("BEACONS_API_TOKEN")
+        .env_remove("BEACONS_API_HEADER")
+        .output()
+        .unwrap()
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "xtask failed\nstdout:\n{}\nstderr:\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = PathBuf::from(SNAPSHOTS).join(name);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected =
+        fs::read_to_string(&path).unwrap_or_else(|_| panic!("missing snapshot {}", path.display()));
+    assert!(
+        expected == actual,
+        "{} differs from the snapshot, rerun with UPDATE_SNAPSHOTS=1 to accept\n{}",
+        name,
+        actual
+    );
+}
+
+/// Compares the generated files of `dir` with the snapshots of the `beacons.json` fixture.
+fn assert_generated_snapshots(dir: &Path) {
+    let generated = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+
+    assert_snapshot("beacons.gen.rs.snap", &generated("src/beacons.gen.rs"));
+    assert_snapshot("buildings.gen.rs.snap", &generated("src/buildings.gen.rs"));
+    assert_snapshot(
+        "footprints.gen.rs.snap",
+        &generated("src/footprints.gen.rs"),
+    );
+    assert_snapshot("rooms.gen.rs.snap", &generated("src/rooms.gen.rs"));
+
+    let manifest = generated("Cargo.toml");
+    let features = &manifest[manifest.find("[features]").unwrap()..];
+    assert_snapshot("features.toml.snap", features);
+}
+
+#[test]
+fn generated_crate_matches_snapshots_and_compiles() {
+    let api = StubApi::start(|_, _| Response::ok(fixture("beacons.json")));
+    let dir = library_copy();
+
+    let output = generate(dir.path(), &format!("{}/beacons", api.url), &["--no-cache"]);
+    assert_success(&output);
+    assert_generated_snapshots(dir.path());
+
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let check = Command::new(cargo)
+        .current_dir(dir.path())
+        .args(["check", "--all-features", "--quiet"])
+        .env(
+            "CARGO_TARGET_DIR",
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join("generated"),
+        )
+        .output()
+        .unwrap();
+    assert!(
+        check.status.success(),
+        "generated crate does not compile\n{}",
+        String::from_utf8_lossy(&check.stderr)
+    );
+}
+
+#[test]
+fn writes_footprint_overrides() {
+    let api = StubApi::start(|_, _| Response::ok(fixture("beacons.json")));
+    let dir = library_copy();
+    let footprints = Path::new(FIXTURES).join("footprints.json");
+
+    let output = generate(
+        dir.path(),
+        &format!("{}/beacons", api.url),
+        &["--no-cache", "--footprints", footprints.to_str().unwrap()],
+    );
+    assert_success(&output);
+    assert_snapshot(
+        "footprints-override.gen.rs.snap",
+        &fs::read_to_string(dir.path().join("src/footprints.gen.rs")).unwrap(),
+    );
+
+    fs::write(
+        dir.path().join("footprints.json"),
+        r#"{ "XYZ": [[0, 0], [0, 1], [1, 1]] }"#,
+    )
+    .unwrap();
+    let output = generate(dir.path(), &format!("{}/beacons", api.url), &["--no-cache"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown building XYZ"));
+}
+
+#[test]
+fn follows_next_pages_and_reports_unknown_fields() {
+    let api = StubApi::start(|request, _| match request.path.as_str() {
+        "/paged/1" => Response::ok(with_next(fixture("page-1.json"), "/paged/2")),
+        "/paged/2" => Response::ok(fixture("page-2.json")),
+        _ => Response::status(404),
+    });
+    let dir = library_copy();
+
+    let output = generate(dir.path(), &format!("{}/paged/1", api.url), &["--no-cache"]);
+    assert_success(&output);
+    assert_generated_snapshots(dir.path());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("beacons[].battery"), "{}", stderr);
+    assert!(stderr.contains("beacons[].indoor.wing"), "{}", stderr);
+    assert_eq!(api.requests().len(), 2);
+}
+
+#[test]
+fn requests_pages_with_page_size() {
+    let api = StubApi::start(|request, _| match request.path.as_str() {
+        "/beacons?page=1&limit=2" => Response::ok(fixture("page-1.json")),
+        "/beacons?page=2&limit=2" => Response::ok(fixture("page-2.json")),
+        "/beacons?page=3&limit=2" => Response::ok(r#"{ "beacons": [] }"#.to_string()),
+        _ => Response::status(404),
+    });
+    let dir = library_copy();
+
+    let output = generate(
+        dir.path(),
+        &format!("{}/beacons", api.url),
+        &["--no-cache", "--page-size", "2"],
+    );
+    assert_success(&output);
+    assert_generated_snapshots(dir.path());
+    assert_eq!(api.requests().len(), 3);
+}
+
+#[test]
+fn stops_when_the_server_ignores_the_page() {
+    let api = StubApi::start(|_, _| Response::ok(fixture("page-1.json")));
+    let dir = library_copy();
+
+    let output = generate(
+        dir.path(),
+        &format!("{}/beacons", api.url),
+        &["--no-cache", "--page-size", "2"],
+    );
+    assert_success(&output);
+    assert_eq!(api.requests().len(), 2);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Skipped 2 beacons"));
+
+    let beacons = fs::read_to_string(dir.path().join("src/beacons.gen.rs")).unwrap();
+    assert_eq!(beacons.matches("minor: 4711 }").count(), 1);
+}
+
+#[test]
+fn retries_server_errors() {
+    let api = StubApi::start(|_, index| match index {
+        0 | 1 => Response::status(503),
+        _ => Response::ok(fixture("beacons.json")),
+    });
+    let dir = library_copy();
+
+    let output = generate(dir.path(), &format!("{}/beacons", api.url), &["--no-cache"]);
+    assert_success(&output);
+    assert_generated_snapshots(dir.path());
+    assert_eq!(api.requests().len(), 3);
+}
+
+#[test]
+fn retries_dropped_connections_while_reading_the_body() {
+    let api = StubApi::start(|_, index| {
+        let body = fixture("beacons.json");
+        match index {
+            0 => Response {
+                content_length: Some(body.len()),
+                body: body[..body.len() / 2].to_string(),
+                ..Response::ok(String::new())
+            },
+            _ => Response::ok(body),
+        }
+    });
+    let dir = library_copy();
+
+    let output = generate(dir.path(), &format!("{}/beacons", api.url), &["--no-cache"]);
+    assert_success(&output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to fetch data"));
+    assert_generated_snapshots(dir.path());
+    assert_eq!(api.requests().len(), 2);
+}
+
+#[test]
+fn revalidates_cached_response() {
+    let api = StubApi::start(|request, _| {
+        if request.headers.get("if-none-match").map(String::as_str) == Some("\"v1\"") {
+            return Response::status(304);
+        }
+        let mut response = Response::ok(fixture("beacons.json"));
+        response.headers.push(("ETag", "\"v1\"".to_string()));
+        response
+    });
+    let dir = library_copy();
+    let url = format!("{}/beacons", api.url);
+
+    assert_success(&generate(dir.path(), &url, &[]));
+    fs::remove_file(dir.path().join("src/beacons.gen.rs")).unwrap();
+
+    let output = generate(dir.path(), &url, &[]);
+    assert_success(&output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("not modified"));
+    assert_generated_snapshots(dir.path());
+}
+
+#[test]
+fn sends_bearer_token_from_environment() {
+    let api = StubApi::start(|_, _| Response::ok(fixture("beacons.json")));
+    let dir = library_copy();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_xtask"))
+        .current_dir(dir.path())
+        .args(["generate", "--no-cache", "--uri", &api.url])
+        .env("BEACONS_API_TOKEN", "secret")
+        .output()
+        .unwrap();
+    assert_success(&output);
+
+    let requests = api.requests();
+    assert_eq!(
+        requests[0].headers.get("authorization").map(String::as_str),
+        Some("Bearer secret")
+    );
+}
+
+#[test]
+fn tells_status_errors_from_schema_errors() {
+    let api = StubApi::start(|request, _| match request.path.as_str() {
+        "/invalid" => Response::ok(fixture("invalid.json")),
+        _ => Response::status(404),
+    });
+    let dir = library_copy();
+
+    let output = generate(dir.path(), &format!("{}/missing", api.url), &["--no-cache"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Server responded with 404"));
+    // Client errors are not retried
+    assert_eq!(api.requests().len(), 1);
+
+    let output = generate(dir.path(), &format!("{}/invalid", api.url), &["--no-cache"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unexpected response body"));
+}